@@ -10,9 +10,8 @@ fn main() {
     rosrust::init("talker");
 
     // Create publisher
-    let mut chatter_pub_latched = rosrust::publish("chatter", 2).unwrap();
+    let chatter_pub_latched = rosrust::publish_latched("chatter", 2).unwrap();
     let chatter_pub_unlatched = rosrust::publish("chatter", 2).unwrap();
-    chatter_pub_latched.set_latching(true);
 
     let mut msg = msg::std_msgs::String::default();
     msg.data = String::from("hello world latched");
@@ -0,0 +1,312 @@
+use crate::msg::rosgraph_msgs::Log;
+use crate::time::Time;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// Format used when `ROSCONSOLE_FORMAT` is not set.
+pub const DEFAULT_FORMAT: &str = "[${severity} @ ${file}:${line}]: ${message}";
+
+static FILE_FORMAT: &str =
+    "[${severity}] [${time}] [${logger}] [${file}:${line}(${function})]: ${message}";
+
+/// Size at which log files get rotated.
+pub const LOG_FILE_MAX_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Number of rotated log files that are kept.
+pub const LOG_FILE_BACKUPS: usize = 10;
+
+/// Single message passed to the console and log file.
+pub struct LogRecord<'a> {
+    pub level: i8,
+    pub msg: &'a str,
+    pub node: &'a str,
+    pub logger: &'a str,
+    pub function: &'a str,
+    pub file: &'a str,
+    pub line: u32,
+    pub time: &'a Time,
+}
+
+/// Streams that console messages are written to.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ConsoleOutput {
+    /// Debug and info messages go to stdout, warnings and errors go to stderr.
+    #[default]
+    Split,
+    Stdout,
+    Stderr,
+    /// Messages are only published and written to the log file.
+    Disabled,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Text(String),
+    Severity,
+    Time,
+    Node,
+    Function,
+    File,
+    Line,
+    Logger,
+    Message,
+}
+
+/// Message format with `ROSCONSOLE_FORMAT` tokens, like `[${severity}] [${time}]: ${message}`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConsoleFormat {
+    tokens: Vec<Token>,
+}
+
+impl Default for ConsoleFormat {
+    fn default() -> Self {
+        Self::parse(DEFAULT_FORMAT)
+    }
+}
+
+impl ConsoleFormat {
+    /// Reads the format from `ROSCONSOLE_FORMAT`, falling back to the default format.
+    pub fn from_env() -> Self {
+        match env::var("ROSCONSOLE_FORMAT") {
+            Ok(ref format) if !format.is_empty() => Self::parse(format),
+            _ => Self::default(),
+        }
+    }
+
+    /// Parses a format string, keeping unknown tokens as plain text.
+    pub fn parse(format: &str) -> Self {
+        let mut tokens = vec![];
+        let mut rest = format;
+        while let Some(start) = rest.find("${") {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+            let token = match &rest[start + 2..end] {
+                "severity" => Token::Severity,
+                "time" => Token::Time,
+                "node" => Token::Node,
+                "function" => Token::Function,
+                "file" => Token::File,
+                "line" => Token::Line,
+                "logger" => Token::Logger,
+                "message" => Token::Message,
+                _ => Token::Text(rest[start..=end].into()),
+            };
+            if start > 0 {
+                tokens.push(Token::Text(rest[..start].into()));
+            }
+            tokens.push(token);
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            tokens.push(Token::Text(rest.into()));
+        }
+        Self { tokens }
+    }
+
+    pub fn format(&self, record: &LogRecord) -> String {
+        let mut output = String::new();
+        for token in &self.tokens {
+            match *token {
+                Token::Text(ref v) => output.push_str(v),
+                Token::Severity => output.push_str(severity(record.level)),
+                Token::Time => {
+                    output.push_str(&format!("{}.{:09}", record.time.sec, record.time.nsec))
+                }
+                Token::Node => output.push_str(record.node),
+                Token::Function => output.push_str(record.function),
+                Token::File => output.push_str(record.file),
+                Token::Line => output.push_str(&record.line.to_string()),
+                Token::Logger => output.push_str(record.logger),
+                Token::Message => output.push_str(record.msg),
+            }
+        }
+        output
+    }
+}
+
+fn severity(level: i8) -> &'static str {
+    match level {
+        Log::DEBUG => "DEBUG",
+        Log::INFO => "INFO",
+        Log::WARN => "WARN",
+        Log::ERROR => "ERROR",
+        Log::FATAL => "FATAL",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Prints the record, colored only if the target stream is a terminal.
+pub fn write_to_terminal(format: &ConsoleFormat, output: ConsoleOutput, record: &LogRecord) {
+    use colored::{Color, Colorize};
+
+    let to_stderr = match output {
+        ConsoleOutput::Split => record.level >= Log::WARN,
+        ConsoleOutput::Stdout => false,
+        ConsoleOutput::Stderr => true,
+        ConsoleOutput::Disabled => return,
+    };
+    let color = match record.level {
+        Log::WARN => Color::Yellow,
+        Log::ERROR | Log::FATAL => Color::Red,
+        _ => Color::White,
+    };
+    let message = format.format(record);
+    if to_stderr {
+        if io::stderr().is_terminal() {
+            eprintln!("{}", message.color(color));
+        } else {
+            eprintln!("{}", message);
+        }
+    } else if io::stdout().is_terminal() {
+        println!("{}", message.color(color));
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// Directory for log files, from `ROS_LOG_DIR`, `ROS_HOME` or the home directory.
+pub fn log_directory() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("ROS_LOG_DIR") {
+        return Some(path.into());
+    }
+    if let Some(path) = env::var_os("ROS_HOME") {
+        return Some(PathBuf::from(path).join("log"));
+    }
+    env::var_os("HOME").map(|path| PathBuf::from(path).join(".ros").join("log"))
+}
+
+/// Log file of a node, rotated once it grows too large.
+pub struct LogFile {
+    path: PathBuf,
+    format: ConsoleFormat,
+    file: File,
+    size: u64,
+    max_size: u64,
+    backups: usize,
+}
+
+impl LogFile {
+    /// Opens the log file of the node in the log directory.
+    pub fn for_node(node: &str) -> io::Result<Self> {
+        let directory = log_directory()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No directory for log files"))?;
+        let name = format!(
+            "{}-{}.log",
+            node.trim_start_matches('/').replace('/', "-"),
+            std::process::id()
+        );
+        Self::new(&directory.join(name), LOG_FILE_MAX_SIZE, LOG_FILE_BACKUPS)
+    }
+
+    pub fn new(path: &Path, max_size: u64, backups: usize) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: path.into(),
+            format: ConsoleFormat::parse(FILE_FORMAT),
+            file,
+            size,
+            max_size,
+            backups,
+        })
+    }
+
+    pub fn write(&mut self, record: &LogRecord) -> io::Result<()> {
+        let mut line = self.format.format(record);
+        line.push('\n');
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn backup_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", index));
+        name.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.backups == 0 {
+            self.file.set_len(0)?;
+        } else {
+            for index in (1..self.backups).rev() {
+                let source = self.backup_path(index);
+                if source.exists() {
+                    fs::rename(&source, self.backup_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.backup_path(1))?;
+            self.file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(time: &Time) -> LogRecord {
+        LogRecord {
+            level: Log::WARN,
+            msg: "hello",
+            node: "/talker",
+            logger: "ros.talker",
+            function: "talker::main",
+            file: "src/main.rs",
+            line: 12,
+            time,
+        }
+    }
+
+    #[test]
+    fn formats_all_tokens() {
+        let time = Time { sec: 5, nsec: 42 };
+        let format = ConsoleFormat::parse(
+            "${severity} ${time} ${node} ${function} ${file}:${line} ${logger} ${foo} ${message}$",
+        );
+        assert_eq!(
+            "WARN 5.000000042 /talker talker::main src/main.rs:12 ros.talker ${foo} hello$",
+            format.format(&record(&time))
+        );
+        assert_eq!(
+            "[WARN @ src/main.rs:12]: hello",
+            ConsoleFormat::default().format(&record(&time))
+        );
+        assert_eq!(
+            "unterminated ${message",
+            ConsoleFormat::parse("unterminated ${message").format(&record(&time))
+        );
+    }
+
+    #[test]
+    fn rotates_log_files() {
+        let directory = env::temp_dir().join(format!("rosrust_log_test_{}", std::process::id()));
+        let path = directory.join("node.log");
+        let time = Time::new();
+        let mut file = LogFile::new(&path, 150, 2).unwrap();
+        for _ in 0..5 {
+            file.write(&record(&time)).unwrap();
+        }
+        assert!(path.exists());
+        assert!(directory.join("node.log.1").exists());
+        assert!(directory.join("node.log.2").exists());
+        assert!(!directory.join("node.log.3").exists());
+        assert!(fs::metadata(&path).unwrap().len() <= 150);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
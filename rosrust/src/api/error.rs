@@ -1,6 +1,7 @@
 #![allow(deprecated)]
 
 pub use crate::api::naming::error as naming;
+pub use crate::dynamic::error as dynamic;
 pub use crate::rosxmlrpc::error as rosxmlrpc;
 pub use crate::rosxmlrpc::ResponseError;
 pub use crate::tcpros::error as tcpros;
@@ -17,6 +18,7 @@ error_chain! {
         XmlRpc(rosxmlrpc::Error, rosxmlrpc::ErrorKind);
         Tcpros(tcpros::Error, tcpros::ErrorKind);
         Naming(naming::Error, naming::ErrorKind);
+        Dynamic(dynamic::Error, dynamic::ErrorKind);
     }
     errors {
         Duplicate(t: String) {
@@ -28,6 +30,10 @@ error_chain! {
             display("Cannot initialize multiple nodes")
         }
         TimeoutError
+        ShuttingDown {
+            description("Node is shutting down")
+            display("Node is shutting down")
+        }
         BadYamlData(details: String) {
             description("Bad YAML data provided")
             display("Bad YAML data provided: {}", details)
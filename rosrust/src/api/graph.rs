@@ -0,0 +1,562 @@
+use super::master::{Master, SystemState, Topic};
+use super::node::{BusDirection, BusInfo, RemoteNode};
+use super::{graph_export, ShutdownManager};
+use crate::rosxmlrpc::Response;
+use crossbeam::channel::{bounded, Receiver, RecvTimeoutError, Sender};
+use log::error;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time that nodes get to describe themselves before the snapshot gets taken without them.
+const NODE_QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Snapshot of the computation graph: nodes, topics, services and the connections between them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Graph {
+    pub nodes: Vec<GraphNode>,
+    pub topics: Vec<GraphTopic>,
+    pub services: Vec<GraphService>,
+    pub edges: Vec<GraphEdge>,
+}
+
+/// Node of the graph, as described by its own slave API.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GraphNode {
+    pub name: String,
+    pub uri: Option<String>,
+    pub publications: Vec<String>,
+    pub subscriptions: Vec<String>,
+    pub services: Vec<String>,
+    pub connections: Vec<BusInfo>,
+    /// Reason the node could not be looked up or queried, if it failed.
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphTopic {
+    pub name: String,
+    /// Type registered with the master, which is only known for published topics.
+    pub msg_type: Option<String>,
+    pub publishers: Vec<String>,
+    pub subscribers: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphService {
+    pub name: String,
+    pub providers: Vec<String>,
+}
+
+/// Publisher and subscriber of a topic, registered with the master.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GraphEdge {
+    pub topic: String,
+    pub publisher: String,
+    pub subscriber: String,
+    /// Transport of the connection, if either node reported it in its bus info.
+    pub transport: Option<String>,
+}
+
+/// Differences between two snapshots of the graph.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GraphChanges {
+    pub added_nodes: Vec<String>,
+    pub removed_nodes: Vec<String>,
+    pub added_topics: Vec<String>,
+    pub removed_topics: Vec<String>,
+    pub added_services: Vec<String>,
+    pub removed_services: Vec<String>,
+    pub added_edges: Vec<GraphEdge>,
+    pub removed_edges: Vec<GraphEdge>,
+}
+
+impl GraphChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.added_topics.is_empty()
+            && self.removed_topics.is_empty()
+            && self.added_services.is_empty()
+            && self.removed_services.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+    }
+}
+
+impl Graph {
+    /// Combines the master's system state with the descriptions that nodes gave of themselves.
+    pub fn new(state: SystemState, topic_types: Vec<Topic>, nodes: Vec<GraphNode>) -> Graph {
+        let types = topic_types
+            .into_iter()
+            .map(|topic| (topic.name, topic.datatype))
+            .collect::<BTreeMap<_, _>>();
+        let mut topics = BTreeMap::<String, GraphTopic>::new();
+        for topic in state.publishers {
+            topic_entry(&mut topics, &types, &topic.name).publishers = sorted(topic.connections);
+        }
+        for topic in state.subscribers {
+            topic_entry(&mut topics, &types, &topic.name).subscribers = sorted(topic.connections);
+        }
+        let mut services = state
+            .services
+            .into_iter()
+            .map(|service| GraphService {
+                name: service.name,
+                providers: sorted(service.connections),
+            })
+            .collect::<Vec<_>>();
+        services.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut nodes = nodes;
+        nodes.sort_by(|a, b| a.name.cmp(&b.name));
+        for node in &mut nodes {
+            node.services = services
+                .iter()
+                .filter(|service| service.providers.contains(&node.name))
+                .map(|service| service.name.clone())
+                .collect();
+        }
+
+        let mut edges = vec![];
+        for topic in topics.values() {
+            for publisher in &topic.publishers {
+                for subscriber in &topic.subscribers {
+                    edges.push(GraphEdge {
+                        topic: topic.name.clone(),
+                        publisher: publisher.clone(),
+                        subscriber: subscriber.clone(),
+                        transport: find_transport(&nodes, &topic.name, publisher, subscriber),
+                    });
+                }
+            }
+        }
+
+        Graph {
+            nodes,
+            topics: topics.into_values().collect(),
+            services,
+            edges,
+        }
+    }
+
+    pub fn node(&self, name: &str) -> Option<&GraphNode> {
+        self.nodes.iter().find(|node| node.name == name)
+    }
+
+    pub fn topic(&self, name: &str) -> Option<&GraphTopic> {
+        self.topics.iter().find(|topic| topic.name == name)
+    }
+
+    /// Leaves out a node along with its connections, and topics and services only it used.
+    ///
+    /// Useful for tools that should not show up in the graph they inspect.
+    pub fn without_node(&self, name: &str) -> Graph {
+        let mut graph = self.clone();
+        graph.nodes.retain(|node| node.name != name);
+        for topic in &mut graph.topics {
+            topic.publishers.retain(|v| v != name);
+            topic.subscribers.retain(|v| v != name);
+        }
+        graph
+            .topics
+            .retain(|topic| !topic.publishers.is_empty() || !topic.subscribers.is_empty());
+        for service in &mut graph.services {
+            service.providers.retain(|v| v != name);
+        }
+        graph
+            .services
+            .retain(|service| !service.providers.is_empty());
+        graph
+            .edges
+            .retain(|edge| edge.publisher != name && edge.subscriber != name);
+        graph
+    }
+
+    /// Renders the graph in Graphviz DOT format.
+    #[inline]
+    pub fn to_dot(&self) -> String {
+        graph_export::to_dot(self)
+    }
+
+    /// Renders the graph as JSON, leaving out fields that change between runs.
+    #[inline]
+    pub fn to_json(&self) -> String {
+        graph_export::to_json(self)
+    }
+
+    /// Lists what was added to and removed from the graph since the previous snapshot.
+    pub fn changes_since(&self, previous: &Graph) -> GraphChanges {
+        let (added_nodes, removed_nodes) = compare(
+            self.nodes.iter().map(|v| v.name.clone()),
+            previous.nodes.iter().map(|v| v.name.clone()),
+        );
+        let (added_topics, removed_topics) = compare(
+            self.topics.iter().map(|v| v.name.clone()),
+            previous.topics.iter().map(|v| v.name.clone()),
+        );
+        let (added_services, removed_services) = compare(
+            self.services.iter().map(|v| v.name.clone()),
+            previous.services.iter().map(|v| v.name.clone()),
+        );
+        // Transports get reported with delay, so edges are identified by their ends only
+        let (added_keys, removed_keys) = compare(
+            self.edges.iter().map(edge_key),
+            previous.edges.iter().map(edge_key),
+        );
+        let added_edges = pick_edges(&self.edges, &added_keys);
+        let removed_edges = pick_edges(&previous.edges, &removed_keys);
+        GraphChanges {
+            added_nodes,
+            removed_nodes,
+            added_topics,
+            removed_topics,
+            added_services,
+            removed_services,
+            added_edges,
+            removed_edges,
+        }
+    }
+}
+
+/// Stops polling the graph when dropped.
+pub struct GraphWatcher {
+    _stop: Sender<()>,
+}
+
+/// Names of all nodes that have publishers, subscribers or services.
+pub(crate) fn node_names(state: &SystemState) -> Vec<String> {
+    sorted(
+        state
+            .publishers
+            .iter()
+            .chain(&state.subscribers)
+            .chain(&state.services)
+            .flat_map(|topic| topic.connections.iter().cloned())
+            .collect(),
+    )
+}
+
+pub(crate) fn fetch(master: &Master, caller_id: &str) -> Response<Graph> {
+    let state = master.get_system_state()?.into();
+    let topics = master
+        .get_topic_types()?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    // Nodes are queried in parallel, so unresponsive ones only hold up the snapshot until the timeout
+    let pending = node_names(&state)
+        .into_iter()
+        .map(|name| inspect_node(master, caller_id, name))
+        .collect::<Vec<_>>();
+    let deadline = Instant::now() + NODE_QUERY_TIMEOUT;
+    let nodes = pending
+        .into_iter()
+        .map(|(node, response)| match response {
+            Some(response) => response
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .unwrap_or_else(|_| GraphNode {
+                    error: Some(format!(
+                        "Node did not respond within {:?}",
+                        NODE_QUERY_TIMEOUT
+                    )),
+                    ..node
+                }),
+            None => node,
+        })
+        .collect();
+    Ok(Graph::new(state, topics, nodes))
+}
+
+/// Polls the graph with the given period, calling back with every change.
+pub(crate) fn watch<F>(
+    master: Arc<Master>,
+    caller_id: String,
+    shutdown_manager: Arc<ShutdownManager>,
+    period: Duration,
+    mut callback: F,
+) -> GraphWatcher
+where
+    F: FnMut(&Graph, &GraphChanges) + Send + 'static,
+{
+    let (stop_tx, stop_rx) = bounded::<()>(0);
+    thread::spawn(move || {
+        let mut previous = Graph::default();
+        while !shutdown_manager.awaiting_shutdown() {
+            match fetch(&master, &caller_id) {
+                Ok(graph) => {
+                    let changes = graph.changes_since(&previous);
+                    if !changes.is_empty() {
+                        callback(&graph, &changes);
+                    }
+                    previous = graph;
+                }
+                Err(err) => error!("Failed to fetch the computation graph: {}", err),
+            }
+            if let Err(RecvTimeoutError::Disconnected) = stop_rx.recv_timeout(period) {
+                break;
+            }
+        }
+    });
+    GraphWatcher { _stop: stop_tx }
+}
+
+/// Looks up the node, and starts querying it if that succeeds.
+fn inspect_node(
+    master: &Master,
+    caller_id: &str,
+    name: String,
+) -> (GraphNode, Option<Receiver<GraphNode>>) {
+    let mut node = GraphNode {
+        name,
+        ..Default::default()
+    };
+    let uri = match master.lookup_node(&node.name) {
+        Ok(uri) => uri,
+        Err(err) => {
+            node.error = Some(format!("Failed to look up node: {}", err));
+            return (node, None);
+        }
+    };
+    node.uri = Some(uri);
+    let (tx, rx) = bounded(1);
+    let caller_id = String::from(caller_id);
+    let query = node.clone();
+    thread::spawn(move || {
+        let _ = tx.send(query_node(&caller_id, query));
+    });
+    (node, Some(rx))
+}
+
+fn query_node(caller_id: &str, mut node: GraphNode) -> GraphNode {
+    let uri = node.uri.clone().unwrap_or_default();
+    let remote = match RemoteNode::new(&uri, caller_id, &node.name) {
+        Ok(remote) => remote,
+        Err(err) => {
+            node.error = Some(format!("Failed to connect to node: {}", err));
+            return node;
+        }
+    };
+    let result = remote
+        .publications()
+        .and_then(|publications| Ok((publications, remote.subscriptions()?)));
+    match result {
+        Ok((publications, subscriptions)) => {
+            node.publications = sorted(publications.into_iter().map(|v| v.0).collect());
+            node.subscriptions = sorted(subscriptions.into_iter().map(|v| v.0).collect());
+        }
+        Err(err) => {
+            node.error = Some(format!("Failed to query node: {}", err));
+            return node;
+        }
+    }
+    // Bus info is optional in some client libraries, so nodes without it are still usable
+    node.connections = remote.bus_info().unwrap_or_default();
+    node
+}
+
+fn topic_entry<'a>(
+    topics: &'a mut BTreeMap<String, GraphTopic>,
+    types: &BTreeMap<String, String>,
+    name: &str,
+) -> &'a mut GraphTopic {
+    topics.entry(name.into()).or_insert_with(|| GraphTopic {
+        name: name.into(),
+        msg_type: types.get(name).cloned(),
+        publishers: vec![],
+        subscribers: vec![],
+    })
+}
+
+/// Transport of a connection, as reported by either of its ends.
+///
+/// Some client libraries identify the other end by its URI instead of its name.
+fn find_transport(
+    nodes: &[GraphNode],
+    topic: &str,
+    publisher: &str,
+    subscriber: &str,
+) -> Option<String> {
+    let refers_to = |destination: &str, name: &str| {
+        destination == name
+            || nodes
+                .iter()
+                .any(|node| node.name == name && node.uri.as_deref() == Some(destination))
+    };
+    let reported = |node: &str, direction: BusDirection, other: &str| {
+        nodes
+            .iter()
+            .filter(|v| v.name == node)
+            .flat_map(|v| &v.connections)
+            .find(|bus: &&BusInfo| {
+                bus.topic == topic
+                    && bus.direction == direction
+                    && refers_to(&bus.destination_id, other)
+            })
+            .map(|bus| bus.transport.clone())
+    };
+    reported(publisher, BusDirection::Outbound, subscriber)
+        .or_else(|| reported(subscriber, BusDirection::Inbound, publisher))
+}
+
+fn compare<T: Clone + Ord>(
+    current: impl Iterator<Item = T>,
+    previous: impl Iterator<Item = T>,
+) -> (Vec<T>, Vec<T>) {
+    let current = current.collect::<BTreeSet<_>>();
+    let previous = previous.collect::<BTreeSet<_>>();
+    let added = current.difference(&previous).cloned().collect();
+    let removed = previous.difference(&current).cloned().collect();
+    (added, removed)
+}
+
+fn edge_key(edge: &GraphEdge) -> (String, String, String) {
+    (
+        edge.topic.clone(),
+        edge.publisher.clone(),
+        edge.subscriber.clone(),
+    )
+}
+
+fn pick_edges(edges: &[GraphEdge], keys: &[(String, String, String)]) -> Vec<GraphEdge> {
+    edges
+        .iter()
+        .filter(|edge| keys.binary_search(&edge_key(edge)).is_ok())
+        .cloned()
+        .collect()
+}
+
+fn sorted(mut items: Vec<String>) -> Vec<String> {
+    items.sort();
+    items.dedup();
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::master::TopicData;
+    use super::*;
+
+    fn topic_data(name: &str, connections: &[&str]) -> TopicData {
+        TopicData {
+            name: name.into(),
+            connections: connections.iter().map(|v| String::from(*v)).collect(),
+        }
+    }
+
+    fn node(name: &str, uri: &str, connections: Vec<BusInfo>) -> GraphNode {
+        GraphNode {
+            name: name.into(),
+            uri: Some(uri.into()),
+            connections,
+            ..Default::default()
+        }
+    }
+
+    fn bus(direction: BusDirection, destination_id: &str) -> BusInfo {
+        BusInfo {
+            connection_id: 1,
+            destination_id: destination_id.into(),
+            direction,
+            transport: "TCPROS".into(),
+            topic: "/chatter".into(),
+            connected: true,
+        }
+    }
+
+    fn sample_graph() -> Graph {
+        let state = SystemState {
+            publishers: vec![topic_data("/chatter", &["/talker"])],
+            subscribers: vec![
+                topic_data("/chatter", &["/listener", "/recorder"]),
+                topic_data("/unused", &["/listener"]),
+            ],
+            services: vec![topic_data("/talker/get_loggers", &["/talker"])],
+        };
+        let topics = vec![Topic {
+            name: "/chatter".into(),
+            datatype: "std_msgs/String".into(),
+        }];
+        let nodes = vec![
+            node("/talker", "http://talker:1/", vec![]),
+            node(
+                "/listener",
+                "http://listener:2/",
+                vec![bus(BusDirection::Inbound, "http://talker:1/")],
+            ),
+            GraphNode {
+                name: "/recorder".into(),
+                error: Some("Failed to look up node".into()),
+                ..Default::default()
+            },
+        ];
+        Graph::new(state, topics, nodes)
+    }
+
+    #[test]
+    fn builds_edges_with_reported_transports() {
+        let graph = sample_graph();
+        assert_eq!(
+            vec!["/listener", "/recorder", "/talker"],
+            graph.nodes.iter().map(|v| &v.name).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["/talker/get_loggers"],
+            graph.node("/talker").unwrap().services
+        );
+        let chatter = graph.topic("/chatter").unwrap();
+        assert_eq!(Some("std_msgs/String".into()), chatter.msg_type);
+        assert_eq!(None, graph.topic("/unused").unwrap().msg_type);
+        assert_eq!(
+            vec![
+                GraphEdge {
+                    topic: "/chatter".into(),
+                    publisher: "/talker".into(),
+                    subscriber: "/listener".into(),
+                    transport: Some("TCPROS".into()),
+                },
+                GraphEdge {
+                    topic: "/chatter".into(),
+                    publisher: "/talker".into(),
+                    subscriber: "/recorder".into(),
+                    transport: None,
+                },
+            ],
+            graph.edges
+        );
+    }
+
+    #[test]
+    fn lists_changes_between_snapshots() {
+        let graph = sample_graph();
+        assert!(graph.changes_since(&graph).is_empty());
+
+        let changes = graph.changes_since(&Graph::default());
+        assert_eq!(3, changes.added_nodes.len());
+        assert_eq!(2, changes.added_edges.len());
+
+        let mut smaller = graph.clone();
+        smaller.nodes.retain(|v| v.name != "/recorder");
+        smaller.edges.retain(|v| v.subscriber != "/recorder");
+        let changes = smaller.changes_since(&graph);
+        assert_eq!(vec!["/recorder"], changes.removed_nodes);
+        assert_eq!("/recorder", changes.removed_edges[0].subscriber);
+        assert!(changes.added_nodes.is_empty() && changes.added_topics.is_empty());
+
+        let mut retransported = graph.clone();
+        for edge in &mut retransported.edges {
+            edge.transport = None;
+        }
+        assert!(retransported.changes_since(&graph).is_empty());
+    }
+
+    #[test]
+    fn leaves_out_node() {
+        let graph = sample_graph().without_node("/talker");
+        assert!(graph.node("/talker").is_none());
+        assert!(graph.services.is_empty() && graph.edges.is_empty());
+        assert!(graph.topic("/chatter").unwrap().publishers.is_empty());
+        assert_eq!(2, graph.topic("/chatter").unwrap().subscribers.len());
+    }
+}
@@ -0,0 +1,276 @@
+use super::graph::Graph;
+use serde_derive::Serialize;
+use std::fmt::Write;
+
+/// Renders the graph in Graphviz DOT format.
+///
+/// Nodes are drawn as ellipses, topics as boxes and services as diamonds. Edges from
+/// topics to subscribers are labelled with the transports of their connections, and nodes
+/// that could not be queried are dashed.
+pub fn to_dot(graph: &Graph) -> String {
+    let mut out = String::from("digraph ros {\n    rankdir=LR;\n");
+    for node in &graph.nodes {
+        let style = if node.error.is_some() {
+            ", style=dashed"
+        } else {
+            ""
+        };
+        line(
+            &mut out,
+            format!(
+                "{} [label={}, shape=ellipse{}];",
+                quote(&format!("n:{}", node.name)),
+                quote(&node.name),
+                style
+            ),
+        );
+    }
+    for topic in &graph.topics {
+        let label = match topic.msg_type {
+            Some(ref msg_type) => format!("{}\n{}", topic.name, msg_type),
+            None => topic.name.clone(),
+        };
+        line(
+            &mut out,
+            format!(
+                "{} [label={}, shape=box];",
+                quote(&format!("t:{}", topic.name)),
+                quote(&label)
+            ),
+        );
+    }
+    for service in &graph.services {
+        line(
+            &mut out,
+            format!(
+                "{} [label={}, shape=diamond];",
+                quote(&format!("s:{}", service.name)),
+                quote(&service.name)
+            ),
+        );
+    }
+    for topic in &graph.topics {
+        for publisher in &topic.publishers {
+            line(
+                &mut out,
+                format!(
+                    "{} -> {};",
+                    quote(&format!("n:{}", publisher)),
+                    quote(&format!("t:{}", topic.name))
+                ),
+            );
+        }
+        for subscriber in &topic.subscribers {
+            let mut transports = graph
+                .edges
+                .iter()
+                .filter(|edge| edge.topic == topic.name && edge.subscriber == *subscriber)
+                .filter_map(|edge| edge.transport.as_ref())
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+            transports.sort_unstable();
+            transports.dedup();
+            let label = if transports.is_empty() {
+                String::new()
+            } else {
+                format!(" [label={}]", quote(&transports.join(", ")))
+            };
+            line(
+                &mut out,
+                format!(
+                    "{} -> {}{};",
+                    quote(&format!("t:{}", topic.name)),
+                    quote(&format!("n:{}", subscriber)),
+                    label
+                ),
+            );
+        }
+    }
+    for service in &graph.services {
+        for provider in &service.providers {
+            line(
+                &mut out,
+                format!(
+                    "{} -> {} [style=dotted, arrowhead=none];",
+                    quote(&format!("n:{}", provider)),
+                    quote(&format!("s:{}", service.name))
+                ),
+            );
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Renders the graph as pretty-printed JSON.
+///
+/// Node URIs and connection IDs change between runs, so they are left out to keep the
+/// output comparable across runs. The top level object has `nodes`, `topics`, `services`
+/// and `edges` arrays, all sorted by name. Edges have no message rates, since `getBusInfo`
+/// only describes connections and not the traffic over them.
+pub fn to_json(graph: &Graph) -> String {
+    let document = JsonGraph {
+        nodes: graph
+            .nodes
+            .iter()
+            .map(|node| JsonNode {
+                name: &node.name,
+                publications: &node.publications,
+                subscriptions: &node.subscriptions,
+                services: &node.services,
+                error: node.error.as_deref(),
+            })
+            .collect(),
+        topics: graph
+            .topics
+            .iter()
+            .map(|topic| JsonTopic {
+                name: &topic.name,
+                msg_type: topic.msg_type.as_deref(),
+                publishers: &topic.publishers,
+                subscribers: &topic.subscribers,
+            })
+            .collect(),
+        services: graph
+            .services
+            .iter()
+            .map(|service| JsonService {
+                name: &service.name,
+                providers: &service.providers,
+            })
+            .collect(),
+        edges: graph
+            .edges
+            .iter()
+            .map(|edge| JsonEdge {
+                topic: &edge.topic,
+                publisher: &edge.publisher,
+                subscriber: &edge.subscriber,
+                transport: edge.transport.as_deref(),
+            })
+            .collect(),
+    };
+    // Serializing plain strings and vectors cannot fail
+    serde_json::to_string_pretty(&document).expect("Failed to serialize graph")
+}
+
+#[derive(Serialize)]
+struct JsonGraph<'a> {
+    nodes: Vec<JsonNode<'a>>,
+    topics: Vec<JsonTopic<'a>>,
+    services: Vec<JsonService<'a>>,
+    edges: Vec<JsonEdge<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonNode<'a> {
+    name: &'a str,
+    publications: &'a [String],
+    subscriptions: &'a [String],
+    services: &'a [String],
+    error: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct JsonTopic<'a> {
+    name: &'a str,
+    #[serde(rename = "type")]
+    msg_type: Option<&'a str>,
+    publishers: &'a [String],
+    subscribers: &'a [String],
+}
+
+#[derive(Serialize)]
+struct JsonService<'a> {
+    name: &'a str,
+    providers: &'a [String],
+}
+
+#[derive(Serialize)]
+struct JsonEdge<'a> {
+    topic: &'a str,
+    publisher: &'a str,
+    subscriber: &'a str,
+    transport: Option<&'a str>,
+}
+
+fn line(out: &mut String, content: String) {
+    // Writing to a string cannot fail
+    writeln!(out, "    {}", content).expect("Failed to write to string");
+}
+
+fn quote(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::graph::{GraphEdge, GraphNode, GraphService, GraphTopic};
+    use super::*;
+
+    fn sample_graph() -> Graph {
+        Graph {
+            nodes: vec![
+                GraphNode {
+                    name: "/listener".into(),
+                    uri: Some("http://listener:2/".into()),
+                    subscriptions: vec!["/chatter".into()],
+                    ..Default::default()
+                },
+                GraphNode {
+                    name: "/talker".into(),
+                    publications: vec!["/chatter".into()],
+                    services: vec!["/talker/get_loggers".into()],
+                    error: Some("Failed to query node".into()),
+                    ..Default::default()
+                },
+            ],
+            topics: vec![GraphTopic {
+                name: "/chatter".into(),
+                msg_type: Some("std_msgs/String".into()),
+                publishers: vec!["/talker".into()],
+                subscribers: vec!["/listener".into()],
+            }],
+            services: vec![GraphService {
+                name: "/talker/get_loggers".into(),
+                providers: vec!["/talker".into()],
+            }],
+            edges: vec![GraphEdge {
+                topic: "/chatter".into(),
+                publisher: "/talker".into(),
+                subscriber: "/listener".into(),
+                transport: Some("TCPROS".into()),
+            }],
+        }
+    }
+
+    #[test]
+    fn renders_dot() {
+        let dot = to_dot(&sample_graph());
+        assert!(dot.starts_with("digraph ros {\n"));
+        assert!(dot.contains("\"n:/talker\" [label=\"/talker\", shape=ellipse, style=dashed];"));
+        assert!(dot.contains("\"t:/chatter\" [label=\"/chatter\\nstd_msgs/String\", shape=box];"));
+        assert!(dot.contains("\"n:/talker\" -> \"t:/chatter\";"));
+        assert!(dot.contains("\"t:/chatter\" -> \"n:/listener\" [label=\"TCPROS\"];"));
+        assert!(dot.contains("\"n:/talker\" -> \"s:/talker/get_loggers\""));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn renders_json_without_volatile_fields() {
+        let json = to_json(&sample_graph());
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!("/listener", value["nodes"][0]["name"]);
+        assert!(value["nodes"][0].get("uri").is_none());
+        assert_eq!("Failed to query node", value["nodes"][1]["error"]);
+        assert_eq!("std_msgs/String", value["topics"][0]["type"]);
+        assert_eq!("/talker", value["services"][0]["providers"][0]);
+        assert_eq!("TCPROS", value["edges"][0]["transport"]);
+    }
+}
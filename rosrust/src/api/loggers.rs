@@ -0,0 +1,253 @@
+use crate::msg::rosgraph_msgs::Log;
+use crate::time::Time;
+use crate::util::FAILED_TO_LOCK;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+
+/// Name of the logger that all other loggers descend from.
+pub const ROOT_LOGGER: &str = "ros";
+
+/// Minimum levels of named loggers.
+///
+/// Logger names are hierarchical and separated by dots, like `ros.my_crate.module`.
+/// A logger without a level of its own inherits the level of its closest ancestor.
+pub struct LoggerLevels {
+    levels: Mutex<BTreeMap<String, i8>>,
+    used: Mutex<BTreeSet<String>>,
+}
+
+impl Default for LoggerLevels {
+    fn default() -> Self {
+        let mut levels = BTreeMap::new();
+        levels.insert(ROOT_LOGGER.into(), Log::INFO);
+        Self {
+            levels: Mutex::new(levels),
+            used: Mutex::new(BTreeSet::new()),
+        }
+    }
+}
+
+impl LoggerLevels {
+    pub fn level(&self, logger: &str) -> i8 {
+        let levels = self.levels.lock().expect(FAILED_TO_LOCK);
+        let mut name = logger;
+        loop {
+            if let Some(level) = levels.get(name) {
+                return *level;
+            }
+            match name.rfind('.') {
+                Some(index) => name = &name[..index],
+                None => return levels.get(ROOT_LOGGER).cloned().unwrap_or(Log::INFO),
+            }
+        }
+    }
+
+    pub fn set_level(&self, logger: &str, level: i8) {
+        self.levels
+            .lock()
+            .expect(FAILED_TO_LOCK)
+            .insert(logger.into(), level);
+    }
+
+    /// Checks if a message of the given level passes the logger's level, and remembers the logger.
+    pub fn enabled(&self, logger: &str, level: i8) -> bool {
+        {
+            let mut used = self.used.lock().expect(FAILED_TO_LOCK);
+            if !used.contains(logger) {
+                used.insert(logger.into());
+            }
+        }
+        level >= self.level(logger)
+    }
+
+    /// Lists all loggers that were used or configured, with their effective levels.
+    pub fn loggers(&self) -> Vec<(String, i8)> {
+        let mut names = self.used.lock().expect(FAILED_TO_LOCK).clone();
+        names.extend(self.levels.lock().expect(FAILED_TO_LOCK).keys().cloned());
+        names
+            .into_iter()
+            .map(|name| {
+                let level = self.level(&name);
+                (name, level)
+            })
+            .collect()
+    }
+}
+
+/// Derives a logger name from a module path, like `ros.my_crate.module` from `my_crate::module`.
+pub fn logger_name(module_path: &str) -> String {
+    if module_path.is_empty() {
+        return ROOT_LOGGER.into();
+    }
+    format!("{}.{}", ROOT_LOGGER, module_path.replace("::", "."))
+}
+
+/// Derives the name of a named logger, like `ros.my_crate.name` for a call in `my_crate::module`.
+pub fn named_logger_name(module_path: &str, name: &str) -> String {
+    let crate_name = module_path.split("::").next().unwrap_or("");
+    format!("{}.{}", logger_name(crate_name), name)
+}
+
+/// Path of the function that contains the given function item, named `__rosrust_log_function`.
+pub fn function_name<F>(_: F) -> &'static str {
+    let name = std::any::type_name::<F>();
+    name.trim_end_matches("::__rosrust_log_function")
+}
+
+/// Time of the last message logged by a throttled logging call site.
+pub struct LastHit(AtomicI64);
+
+const NEVER: i64 = i64::MIN;
+
+impl Default for LastHit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LastHit {
+    pub const fn new() -> Self {
+        LastHit(AtomicI64::new(NEVER))
+    }
+
+    /// Checks if at least `period` seconds passed since the last hit, and records the hit.
+    ///
+    /// The first call always passes, and so does any call after time jumps backwards.
+    pub fn throttle(&self, now: Time, period: f64) -> bool {
+        let now = now.nanos();
+        let mut last = self.0.load(Ordering::SeqCst);
+        loop {
+            if last != NEVER && now >= last && ((now - last) as f64) < period * 1e9 {
+                return false;
+            }
+            // Only one of the threads hitting at the same time gets to record the hit
+            match self
+                .0
+                .compare_exchange(last, now, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => return true,
+                Err(current) => last = current,
+            }
+        }
+    }
+
+    /// Like `throttle`, but the first call only records the hit, without passing.
+    pub fn delayed_throttle(&self, now: Time, period: f64) -> bool {
+        let first = self
+            .0
+            .compare_exchange(NEVER, now.nanos(), Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok();
+        !first && self.throttle(now, period)
+    }
+}
+
+pub fn level_name(level: i8) -> &'static str {
+    match level {
+        Log::DEBUG => "debug",
+        Log::INFO => "info",
+        Log::WARN => "warn",
+        Log::ERROR => "error",
+        Log::FATAL => "fatal",
+        _ => "unknown",
+    }
+}
+
+pub fn level_from_name(name: &str) -> Option<i8> {
+    match name.to_lowercase().as_str() {
+        "debug" => Some(Log::DEBUG),
+        "info" => Some(Log::INFO),
+        "warn" => Some(Log::WARN),
+        "error" => Some(Log::ERROR),
+        "fatal" => Some(Log::FATAL),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inherits_levels_from_ancestors() {
+        let levels = LoggerLevels::default();
+        assert_eq!(Log::INFO, levels.level("ros.foo.bar"));
+        levels.set_level("ros.foo", Log::DEBUG);
+        assert_eq!(Log::DEBUG, levels.level("ros.foo.bar"));
+        assert_eq!(Log::DEBUG, levels.level("ros.foo"));
+        assert_eq!(Log::INFO, levels.level("ros.foobar"));
+        levels.set_level("ros", Log::ERROR);
+        assert_eq!(Log::ERROR, levels.level("ros.baz"));
+        assert_eq!(Log::DEBUG, levels.level("ros.foo.bar"));
+    }
+
+    #[test]
+    fn filters_and_lists_loggers() {
+        let levels = LoggerLevels::default();
+        assert!(!levels.enabled("ros.foo", Log::DEBUG));
+        assert!(levels.enabled("ros.foo", Log::WARN));
+        levels.set_level("ros.bar", Log::FATAL);
+        assert_eq!(
+            vec![
+                ("ros".into(), Log::INFO),
+                ("ros.bar".into(), Log::FATAL),
+                ("ros.foo".into(), Log::INFO),
+            ],
+            levels.loggers()
+        );
+    }
+
+    #[test]
+    fn throttles_by_period() {
+        let last_hit = LastHit::new();
+        assert!(last_hit.throttle(Time::from_nanos(5_000_000_000), 2.0));
+        assert!(!last_hit.throttle(Time::from_nanos(6_000_000_000), 2.0));
+        assert!(last_hit.throttle(Time::from_nanos(7_000_000_000), 2.0));
+        assert!(!last_hit.throttle(Time::from_nanos(8_500_000_000), 2.0));
+        assert!(last_hit.throttle(Time::from_nanos(1_000_000_000), 2.0));
+    }
+
+    #[test]
+    fn lets_one_of_concurrent_hits_through() {
+        let last_hit = std::sync::Arc::new(LastHit::new());
+        let threads = (0..8)
+            .map(|_| {
+                let last_hit = std::sync::Arc::clone(&last_hit);
+                std::thread::spawn(move || last_hit.throttle(Time::from_nanos(5_000_000_000), 2.0))
+            })
+            .collect::<Vec<_>>();
+        let passed = threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .filter(|&passed| passed)
+            .count();
+        assert_eq!(1, passed);
+    }
+
+    #[test]
+    fn delays_first_throttled_message() {
+        let last_hit = LastHit::new();
+        assert!(!last_hit.delayed_throttle(Time::from_nanos(5_000_000_000), 2.0));
+        assert!(!last_hit.delayed_throttle(Time::from_nanos(6_000_000_000), 2.0));
+        assert!(last_hit.delayed_throttle(Time::from_nanos(7_000_000_000), 2.0));
+        assert!(!last_hit.delayed_throttle(Time::from_nanos(8_000_000_000), 2.0));
+    }
+
+    #[test]
+    fn converts_names() {
+        assert_eq!("ros.my_crate.module", logger_name("my_crate::module"));
+        assert_eq!(
+            "ros.my_crate.name",
+            named_logger_name("my_crate::module", "name")
+        );
+        assert_eq!("ros", logger_name(""));
+        fn __rosrust_log_function() {}
+        assert_eq!(
+            "rosrust::api::loggers::tests::converts_names",
+            function_name(__rosrust_log_function)
+        );
+        assert_eq!(Some(Log::WARN), level_from_name("Warn"));
+        assert_eq!(None, level_from_name("verbose"));
+        assert_eq!("fatal", level_name(Log::FATAL));
+    }
+}
@@ -65,7 +65,6 @@ impl Master {
         request!(self; unregisterPublisher; topic, &self.caller_api)
     }
 
-    #[allow(dead_code)]
     pub fn lookup_node(&self, node_name: &str) -> Result<String> {
         request!(self; lookupNode; node_name)
     }
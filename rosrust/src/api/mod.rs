@@ -1,35 +1,27 @@
-pub use self::clock::{Clock, Delay, Rate};
-pub use self::master::{SystemState, Topic};
-pub use self::ros::{Parameter, Ros};
-use std::sync::atomic::{AtomicBool, Ordering};
+pub use self::clock::{Clock, Delay, Rate, RealClock};
+pub use self::graph::{
+    Graph, GraphChanges, GraphEdge, GraphNode, GraphService, GraphTopic, GraphWatcher,
+};
+pub use self::graph_export::{to_dot, to_json};
+pub use self::loggers::{function_name, logger_name, named_logger_name, LastHit, ROOT_LOGGER};
+pub use self::master::{SystemState, Topic, TopicData};
+pub use self::node::{BusDirection, BusInfo, RemoteNode};
+pub use self::node_handle::NodeHandle;
+pub use self::ros::{Parameter, Ros, RosBuilder};
+pub use self::shutdown::{ShutdownManager, ShutdownReason};
 
 mod clock;
+pub mod console;
 pub mod error;
+mod graph;
+mod graph_export;
+mod loggers;
 mod master;
 mod naming;
+mod node;
+mod node_handle;
 pub mod raii;
 pub mod resolve;
 mod ros;
+mod shutdown;
 mod slave;
-
-pub struct ShutdownManager {
-    should_shutdown: AtomicBool,
-}
-
-impl Default for ShutdownManager {
-    fn default() -> Self {
-        Self {
-            should_shutdown: AtomicBool::new(false),
-        }
-    }
-}
-
-impl ShutdownManager {
-    pub fn awaiting_shutdown(&self) -> bool {
-        self.should_shutdown.load(Ordering::Relaxed)
-    }
-
-    pub fn shutdown(&self) {
-        self.should_shutdown.store(true, Ordering::Relaxed)
-    }
-}
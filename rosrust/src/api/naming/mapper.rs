@@ -1,6 +1,7 @@
 use super::path::{Buffer, Path, Slice};
 use std::collections::HashMap;
 
+#[derive(Clone)]
 pub struct Mapper {
     children: HashMap<String, Mapper>,
     value: Option<Buffer>,
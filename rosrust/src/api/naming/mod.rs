@@ -6,6 +6,7 @@ pub mod error;
 mod mapper;
 mod path;
 
+#[derive(Clone)]
 pub struct Resolver {
     path: path::Buffer,
     namespace: path::Buffer,
@@ -23,6 +24,29 @@ impl Resolver {
         })
     }
 
+    /// Creates a resolver for a namespace relative to this one.
+    ///
+    /// Private names still resolve relative to the node, and remappings are inherited.
+    pub fn child(&self, namespace: &str) -> Result<Resolver, Error> {
+        let namespace = if namespace.is_empty() {
+            self.namespace.clone()
+        } else {
+            self.resolve(namespace)?
+        };
+        Ok(Resolver {
+            path: self.path.clone(),
+            namespace,
+            mapper: self.mapper.clone(),
+        })
+    }
+
+    pub fn namespace(&self) -> String {
+        match format!("{}", self.namespace) {
+            ref v if v.is_empty() => String::from("/"),
+            v => v,
+        }
+    }
+
     pub fn map(&mut self, source: &str, destination: &str) -> Result<(), Error> {
         let source = self.resolve(source)?;
         let destination = self.resolve(destination)?;
@@ -51,6 +75,17 @@ impl Resolver {
     }
 }
 
+/// Makes a node name unique, like `talker_myhost_1234_1546300800000`.
+///
+/// Characters of the hostname that are not allowed in names are replaced with underscores.
+pub fn anonymous_name(name: &str, hostname: &str, pid: u32, millis: u128) -> String {
+    let hostname = hostname
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    format!("{}_{}_{}_{}", name, hostname, pid, millis)
+}
+
 #[cfg(test)]
 mod tests {
     use super::path::Path;
@@ -186,6 +221,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn resolves_within_child_namespaces() {
+        let mut r = Resolver::new("/some/long/path").expect(FAILED_TO_RESOLVE);
+        r.map("a", "/d").expect(FAILED_TO_RESOLVE);
+        let child = r.child("arm").expect(FAILED_TO_RESOLVE);
+        assert_eq!("/some/long/arm", child.namespace());
+        assert_eq!(
+            String::from("/some/long/arm/joint"),
+            child.translate("joint").expect(FAILED_TO_RESOLVE)
+        );
+        assert_eq!(
+            String::from("/some/long/path/foo"),
+            child.translate("~foo").expect(FAILED_TO_RESOLVE)
+        );
+        assert_eq!(
+            String::from("/d"),
+            child.translate("/some/long/a").expect(FAILED_TO_RESOLVE)
+        );
+        let private = r.child("~").expect(FAILED_TO_RESOLVE);
+        assert_eq!("/some/long/path", private.namespace());
+        assert_eq!(
+            String::from("/some/long/path/foo"),
+            private.translate("foo").expect(FAILED_TO_RESOLVE)
+        );
+        let root = Resolver::new("/node")
+            .expect(FAILED_TO_RESOLVE)
+            .child("")
+            .expect(FAILED_TO_RESOLVE);
+        assert_eq!("/", root.namespace());
+    }
+
+    #[test]
+    fn makes_anonymous_names() {
+        assert_eq!(
+            "talker_my_host_local_42_1000",
+            anonymous_name("talker", "my-host.local", 42, 1000)
+        );
+        Resolver::new(&format!(
+            "/{}",
+            anonymous_name("talker", "192.168.0.1", 42, 1000)
+        ))
+        .expect(FAILED_TO_RESOLVE);
+    }
+
     #[test]
     fn supports_remapping() {
         let mut r = Resolver::new("/some/long/path").expect(FAILED_TO_RESOLVE);
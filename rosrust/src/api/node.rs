@@ -0,0 +1,164 @@
+use super::super::rosxmlrpc::{self, Response as Result, ResponseError};
+use xml_rpc::Value;
+
+/// Client for the slave API of another node.
+pub struct RemoteNode {
+    client: rosxmlrpc::Client,
+    client_id: String,
+    name: String,
+    uri: String,
+}
+
+macro_rules! request {
+    ($s:expr; $name:ident; $($item:expr),*)=> ({
+        $s.client.request(stringify!($name),&(&$s.client_id,
+            $(
+                $item,
+            )*
+            ))
+    })
+}
+
+macro_rules! request_tree {
+    ($s:expr; $name:ident; $($item:expr),*)=> ({
+        $s.client.request_tree(stringify!($name),&(&$s.client_id,
+            $(
+                $item,
+            )*
+            ))
+    })
+}
+
+impl RemoteNode {
+    pub fn new(uri: &str, client_id: &str, name: &str) -> rosxmlrpc::error::Result<RemoteNode> {
+        Ok(RemoteNode {
+            client: rosxmlrpc::Client::new(uri)?,
+            client_id: client_id.to_owned(),
+            name: name.to_owned(),
+            uri: uri.to_owned(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    pub fn pid(&self) -> Result<i32> {
+        request!(self; getPid;)
+    }
+
+    pub fn master_uri(&self) -> Result<String> {
+        request!(self; getMasterUri;)
+    }
+
+    pub fn publications(&self) -> Result<Vec<(String, String)>> {
+        request!(self; getPublications;)
+    }
+
+    pub fn subscriptions(&self) -> Result<Vec<(String, String)>> {
+        request!(self; getSubscriptions;)
+    }
+
+    pub fn bus_info(&self) -> Result<Vec<BusInfo>> {
+        match request_tree!(self; getBusInfo;)? {
+            Value::Array(items) => items.iter().map(BusInfo::from_value).collect(),
+            data => Err(bad_bus_info(&data)),
+        }
+    }
+
+    pub fn shutdown(&self, reason: &str) -> Result<()> {
+        request!(self; shutdown; reason).map(|_: i32| ())
+    }
+}
+
+/// Connection of a node to a topic, as reported by `getBusInfo`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BusInfo {
+    pub connection_id: i32,
+    pub destination_id: String,
+    pub direction: BusDirection,
+    pub transport: String,
+    pub topic: String,
+    pub connected: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BusDirection {
+    Inbound,
+    Outbound,
+    Both,
+}
+
+impl BusInfo {
+    fn from_value(value: &Value) -> Result<BusInfo> {
+        let items = match *value {
+            Value::Array(ref items) if items.len() >= 5 => items,
+            _ => return Err(bad_bus_info(value)),
+        };
+        let direction = match items[2] {
+            Value::String(ref v) if v == "i" => BusDirection::Inbound,
+            Value::String(ref v) if v == "o" => BusDirection::Outbound,
+            Value::String(ref v) if v == "b" => BusDirection::Both,
+            _ => return Err(bad_bus_info(value)),
+        };
+        let connected = match items.get(5) {
+            Some(&Value::Bool(v)) => v,
+            Some(&Value::Int(v)) => v != 0,
+            _ => true,
+        };
+        match (&items[0], &items[1], &items[3], &items[4]) {
+            (
+                Value::Int(connection_id),
+                Value::String(destination_id),
+                Value::String(transport),
+                Value::String(topic),
+            ) => Ok(BusInfo {
+                connection_id: *connection_id,
+                destination_id: destination_id.clone(),
+                direction,
+                transport: transport.clone(),
+                topic: topic.clone(),
+                connected,
+            }),
+            _ => Err(bad_bus_info(value)),
+        }
+    }
+}
+
+fn bad_bus_info(data: &Value) -> ResponseError {
+    ResponseError::Server(format!("Bad bus info received: {:?}", data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bus_info() {
+        let value = Value::Array(vec![
+            Value::Int(3),
+            Value::String("/talker".into()),
+            Value::String("i".into()),
+            Value::String("TCPROS".into()),
+            Value::String("/chatter".into()),
+            Value::Bool(true),
+            Value::String("TCPROS connection on port 4 to [/talker]".into()),
+        ]);
+        assert_eq!(
+            BusInfo {
+                connection_id: 3,
+                destination_id: "/talker".into(),
+                direction: BusDirection::Inbound,
+                transport: "TCPROS".into(),
+                topic: "/chatter".into(),
+                connected: true,
+            },
+            BusInfo::from_value(&value).unwrap()
+        );
+        assert!(BusInfo::from_value(&Value::Array(vec![Value::Int(3)])).is_err());
+    }
+}
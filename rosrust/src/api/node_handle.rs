@@ -0,0 +1,222 @@
+use super::error::Result;
+use super::naming::Resolver;
+use super::raii::{Publisher, Service, Subscriber};
+use super::ros::{Parameter, Ros};
+use crate::tcpros::{
+    Client, Message, ServiceConcurrency, ServiceEvent, ServicePair, ServiceResult,
+    SingleSubscriberPublisher, TransportHints,
+};
+use crate::RawMessageDescription;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Handle to a node that resolves names relative to its own namespace.
+///
+/// Remappings added to a handle only apply to names resolved through it and its children,
+/// while the node's global remappings apply on top of them.
+#[derive(Clone)]
+pub struct NodeHandle {
+    ros: Arc<Ros>,
+    resolver: Resolver,
+}
+
+impl NodeHandle {
+    /// Creates a handle in a namespace relative to the node's namespace.
+    ///
+    /// An empty namespace keeps the node's namespace, and `~` is the node's private namespace.
+    pub fn new(ros: Arc<Ros>, namespace: &str) -> Result<NodeHandle> {
+        let resolver = Resolver::new(ros.name())?.child(namespace)?;
+        Ok(NodeHandle { ros, resolver })
+    }
+
+    /// Creates a handle in a namespace relative to this one, inheriting its remappings.
+    pub fn child(&self, namespace: &str) -> Result<NodeHandle> {
+        Ok(NodeHandle {
+            ros: Arc::clone(&self.ros),
+            resolver: self.resolver.child(namespace)?,
+        })
+    }
+
+    pub fn namespace(&self) -> String {
+        self.resolver.namespace()
+    }
+
+    pub fn map(&mut self, source: &str, destination: &str) -> Result<()> {
+        self.resolver.map(source, destination)?;
+        Ok(())
+    }
+
+    /// Resolves a name relative to the handle, applying the handle's remappings.
+    pub fn resolve_name(&self, name: &str) -> Result<String> {
+        Ok(self.resolver.translate(name)?)
+    }
+
+    pub fn param(&self, name: &str) -> Option<Parameter> {
+        self.ros.param(&self.resolve_name(name).ok()?)
+    }
+
+    pub fn service_uri(&self, service: &str) -> Result<String> {
+        self.ros.service_uri(&self.resolve_name(service)?)
+    }
+
+    pub fn client<T: ServicePair>(&self, service: &str) -> Result<Client<T>> {
+        self.ros.client::<T>(&self.resolve_name(service)?)
+    }
+
+    pub fn service_type(&self, service: &str) -> Result<String> {
+        self.ros.service_type(&self.resolve_name(service)?)
+    }
+
+    pub fn resilient_client<T: ServicePair>(&self, service: &str) -> Result<Client<T>> {
+        self.ros.resilient_client::<T>(&self.resolve_name(service)?)
+    }
+
+    pub fn wait_for_service(
+        &self,
+        service: &str,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<()> {
+        self.ros
+            .wait_for_service(&self.resolve_name(service)?, timeout)
+    }
+
+    pub fn service<T, F>(&self, service: &str, handler: F) -> Result<Service>
+    where
+        T: ServicePair,
+        F: Fn(T::Request) -> ServiceResult<T::Response> + Send + Sync + 'static,
+    {
+        self.ros
+            .service::<T, F>(&self.resolve_name(service)?, handler)
+    }
+
+    pub fn set_max_message_size(&self, name: &str, max_message_size: usize) -> Result<()> {
+        self.ros
+            .set_max_message_size(&self.resolve_name(name)?, max_message_size)
+    }
+
+    pub fn service_with_events<T, F>(
+        &self,
+        service: &str,
+        concurrency: ServiceConcurrency,
+        handler: F,
+    ) -> Result<Service>
+    where
+        T: ServicePair,
+        F: Fn(T::Request, ServiceEvent) -> ServiceResult<T::Response> + Send + Sync + 'static,
+    {
+        self.ros
+            .service_with_events::<T, F>(&self.resolve_name(service)?, concurrency, handler)
+    }
+
+    pub fn subscribe<T, F>(&self, topic: &str, queue_size: usize, callback: F) -> Result<Subscriber>
+    where
+        T: Message,
+        F: Fn(T) + Send + 'static,
+    {
+        self.ros
+            .subscribe::<T, F>(&self.resolve_name(topic)?, queue_size, callback)
+    }
+
+    pub fn subscribe_with_hints<T, F>(
+        &self,
+        topic: &str,
+        queue_size: usize,
+        transport_hints: TransportHints,
+        callback: F,
+    ) -> Result<Subscriber>
+    where
+        T: Message,
+        F: Fn(T) + Send + 'static,
+    {
+        self.ros.subscribe_with_hints::<T, F>(
+            &self.resolve_name(topic)?,
+            queue_size,
+            transport_hints,
+            callback,
+        )
+    }
+
+    pub fn subscribe_with_ids_and_headers<T, F, G>(
+        &self,
+        topic: &str,
+        queue_size: usize,
+        on_message: F,
+        on_connect: G,
+    ) -> Result<Subscriber>
+    where
+        T: Message,
+        F: Fn(T, &str) + Send + 'static,
+        G: Fn(HashMap<String, String>) + Send + 'static,
+    {
+        self.ros.subscribe_with_ids_and_headers::<T, F, G>(
+            &self.resolve_name(topic)?,
+            queue_size,
+            on_message,
+            on_connect,
+        )
+    }
+
+    pub fn wait_for_message<T>(
+        &self,
+        topic: &str,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<T>
+    where
+        T: Message,
+    {
+        self.ros
+            .wait_for_message::<T>(&self.resolve_name(topic)?, timeout)
+    }
+
+    pub fn publish<T>(&self, topic: &str, queue_size: usize) -> Result<Publisher<T>>
+    where
+        T: Message,
+    {
+        self.ros
+            .publish::<T>(&self.resolve_name(topic)?, queue_size)
+    }
+
+    pub fn publish_with_callbacks<T, F, G>(
+        &self,
+        topic: &str,
+        queue_size: usize,
+        on_connect: F,
+        on_disconnect: G,
+    ) -> Result<Publisher<T>>
+    where
+        T: Message,
+        F: Fn(SingleSubscriberPublisher<T>) + Send + Sync + 'static,
+        G: Fn(SingleSubscriberPublisher<T>) + Send + Sync + 'static,
+    {
+        self.ros.publish_with_callbacks::<T, F, G>(
+            &self.resolve_name(topic)?,
+            queue_size,
+            on_connect,
+            on_disconnect,
+        )
+    }
+
+    pub fn publish_latched<T>(&self, topic: &str, queue_size: usize) -> Result<Publisher<T>>
+    where
+        T: Message,
+    {
+        self.ros
+            .publish_latched::<T>(&self.resolve_name(topic)?, queue_size)
+    }
+
+    pub fn publish_with_description<T>(
+        &self,
+        topic: &str,
+        queue_size: usize,
+        message_description: RawMessageDescription,
+    ) -> Result<Publisher<T>>
+    where
+        T: Message,
+    {
+        self.ros.publish_with_description::<T>(
+            &self.resolve_name(topic)?,
+            queue_size,
+            message_description,
+        )
+    }
+}
@@ -1,31 +1,51 @@
 use super::clock::Clock;
-use super::error::Result;
+use super::error::{ErrorKind, Result};
 use super::master::Master;
+use super::shutdown::ShutdownManager;
 use super::slave::Slave;
 use crate::rosxmlrpc::Response;
-use crate::tcpros::{Message, PublisherStream, ServicePair, ServiceResult};
+use crate::tcpros::{
+    Message, PublisherStream, ServiceConcurrency, ServiceEvent, ServicePair, ServiceResult,
+    SingleSubscriberPublisher, TransportHints, TypeMismatch,
+};
+use crate::time::Duration as RosDuration;
+use crate::util::FAILED_TO_LOCK;
+use crate::RawMessageDescription;
 use log::error;
-use std::sync::atomic::AtomicUsize;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
 
 #[derive(Clone)]
 pub struct Publisher<T: Message> {
     clock: Arc<Clock>,
+    shutdown_manager: Arc<ShutdownManager>,
     seq: Arc<AtomicUsize>,
     stream: PublisherStream<T>,
     _raii: Arc<InteractorRaii<PublisherInfo>>,
 }
 
 impl<T: Message> Publisher<T> {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         master: Arc<Master>,
         slave: Arc<Slave>,
         clock: Arc<Clock>,
+        shutdown_manager: Arc<ShutdownManager>,
         hostname: &str,
         name: &str,
         queue_size: usize,
+        latching: bool,
+        message_description: RawMessageDescription,
     ) -> Result<Self> {
-        let stream = slave.add_publication::<T>(hostname, name, queue_size)?;
+        let mut stream =
+            slave.add_publication::<T>(hostname, name, queue_size, &message_description)?;
+        // Set before registering, so the first subscribers already see it in the header
+        if latching {
+            stream.set_latching(true);
+        }
 
         let raii = Arc::new(InteractorRaii::new(PublisherInfo {
             master,
@@ -35,7 +55,7 @@ impl<T: Message> Publisher<T> {
 
         raii.interactor
             .master
-            .register_publisher(name, &T::msg_type())
+            .register_publisher(name, &message_description.msg_type)
             .map_err(|err| {
                 error!("Failed to register publisher for topic '{}': {}", name, err);
                 err
@@ -44,6 +64,7 @@ impl<T: Message> Publisher<T> {
         Ok(Self {
             stream,
             clock,
+            shutdown_manager,
             seq: Arc::new(AtomicUsize::new(0)),
             _raii: raii,
         })
@@ -59,6 +80,11 @@ impl<T: Message> Publisher<T> {
         self.stream.set_latching(latching);
     }
 
+    #[inline]
+    pub fn is_latching(&self) -> bool {
+        self.stream.is_latching()
+    }
+
     #[inline]
     pub fn set_queue_size(&mut self, queue_size: usize) {
         self.stream.set_queue_size(queue_size);
@@ -69,6 +95,39 @@ impl<T: Message> Publisher<T> {
         message.set_header(&self.clock, &self.seq);
         self.stream.send(&message).map_err(Into::into)
     }
+
+    /// Waits until queued messages are written to subscribers, returning false on timeout.
+    pub fn flush(&self, timeout: Duration) -> bool {
+        let start = Instant::now();
+        while self.stream.pending() > 0 {
+            if start.elapsed() > timeout {
+                return false;
+            }
+            sleep(Duration::from_millis(5));
+        }
+        true
+    }
+
+    /// Waits until at least `count` subscribers are connected.
+    ///
+    /// The timeout is measured with the node's clock, and waiting stops early on shutdown.
+    pub fn wait_for_subscribers(&self, count: usize, timeout: Option<Duration>) -> Result<()> {
+        wait_for(&*self.clock, &self.shutdown_manager, timeout, || {
+            self.subscriber_count() >= count
+        })
+    }
+
+    pub(crate) fn add_status_callbacks<F, G>(&self, on_connect: F, on_disconnect: G)
+    where
+        F: Fn(SingleSubscriberPublisher<T>) + Send + Sync + 'static,
+        G: Fn(SingleSubscriberPublisher<T>) + Send + Sync + 'static,
+    {
+        self.stream.add_status_callbacks(on_connect, on_disconnect)
+    }
+
+    pub(crate) fn registration(&self) -> Weak<dyn Registration> {
+        Arc::downgrade(&self._raii) as Weak<dyn Registration>
+    }
 }
 
 struct PublisherInfo {
@@ -78,7 +137,7 @@ struct PublisherInfo {
 }
 
 impl Interactor for PublisherInfo {
-    fn unregister(&mut self) -> Response<()> {
+    fn unregister(&self) -> Response<()> {
         self.slave.remove_publication(&self.name);
         self.master.unregister_publisher(&self.name).map(|_| ())
     }
@@ -86,18 +145,38 @@ impl Interactor for PublisherInfo {
 
 #[derive(Clone)]
 pub struct Subscriber {
+    clock: Arc<dyn Clock>,
+    shutdown_manager: Arc<ShutdownManager>,
     info: Arc<InteractorRaii<SubscriberInfo>>,
 }
 
 impl Subscriber {
-    pub(crate) fn new<T: Message, F: Fn(T) -> () + Send + 'static>(
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new<T, F, G>(
         master: Arc<Master>,
         slave: Arc<Slave>,
+        clock: Arc<dyn Clock>,
+        shutdown_manager: Arc<ShutdownManager>,
         name: &str,
         queue_size: usize,
-        callback: F,
-    ) -> Result<Self> {
-        slave.add_subscription::<T, F>(name, queue_size, callback)?;
+        transport_hints: TransportHints,
+        max_message_size: usize,
+        on_message: F,
+        on_connect: G,
+    ) -> Result<Self>
+    where
+        T: Message,
+        F: Fn(T, &str) + Send + 'static,
+        G: Fn(HashMap<String, String>) + Send + 'static,
+    {
+        slave.add_subscription::<T, F, G>(
+            name,
+            queue_size,
+            transport_hints,
+            max_message_size,
+            on_message,
+            on_connect,
+        )?;
 
         let info = Arc::new(InteractorRaii::new(SubscriberInfo {
             master,
@@ -121,7 +200,11 @@ impl Subscriber {
             );
         }
 
-        Ok(Self { info })
+        Ok(Self {
+            clock,
+            shutdown_manager,
+            info,
+        })
     }
 
     #[inline]
@@ -131,6 +214,56 @@ impl Subscriber {
             .slave
             .get_publisher_count_of_subscription(&self.info.interactor.name)
     }
+
+    /// Checks if a connected publisher advertised latching in its connection header.
+    #[inline]
+    pub fn is_latched(&self) -> bool {
+        self.info
+            .interactor
+            .slave
+            .is_subscription_latched(&self.info.interactor.name)
+    }
+
+    /// Latest type mismatch of each publisher that could not be connected to.
+    ///
+    /// Mismatches include field-level differences when the publisher sent its message
+    /// definition.
+    #[inline]
+    pub fn type_mismatches(&self) -> Vec<TypeMismatch> {
+        self.info
+            .interactor
+            .slave
+            .get_type_mismatches_of_subscription(&self.info.interactor.name)
+    }
+
+    /// Calls the callback whenever a publisher is rejected for sending a different type.
+    ///
+    /// Mismatches that were already reported are passed to the callback right away.
+    pub fn on_type_mismatch<F>(&self, callback: F)
+    where
+        F: Fn(&TypeMismatch) + Send + Sync + 'static,
+    {
+        self.info
+            .interactor
+            .slave
+            .add_type_mismatch_callback_to_subscription(
+                &self.info.interactor.name,
+                Arc::new(callback),
+            )
+    }
+
+    /// Waits until at least `count` publishers are connected.
+    ///
+    /// The timeout is measured with the node's clock, and waiting stops early on shutdown.
+    pub fn wait_for_publishers(&self, count: usize, timeout: Option<Duration>) -> Result<()> {
+        wait_for(&*self.clock, &self.shutdown_manager, timeout, || {
+            self.publisher_count() >= count
+        })
+    }
+
+    pub(crate) fn registration(&self) -> Weak<dyn Registration> {
+        Arc::downgrade(&self.info) as Weak<dyn Registration>
+    }
 }
 
 struct SubscriberInfo {
@@ -140,7 +273,7 @@ struct SubscriberInfo {
 }
 
 impl Interactor for SubscriberInfo {
-    fn unregister(&mut self) -> Response<()> {
+    fn unregister(&self) -> Response<()> {
         self.slave.remove_subscription(&self.name);
         self.master.unregister_subscriber(&self.name).map(|_| ())
     }
@@ -152,19 +285,31 @@ pub struct Service {
 }
 
 impl Service {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new<T, F>(
         master: Arc<Master>,
         slave: Arc<Slave>,
+        clock: Arc<dyn Clock>,
         hostname: &str,
         bind_address: &str,
         name: &str,
+        concurrency: ServiceConcurrency,
+        max_message_size: usize,
         handler: F,
     ) -> Result<Self>
     where
         T: ServicePair,
-        F: Fn(T::Request) -> ServiceResult<T::Response> + Send + Sync + 'static,
+        F: Fn(T::Request, ServiceEvent) -> ServiceResult<T::Response> + Send + Sync + 'static,
     {
-        let api = slave.add_service::<T, F>(hostname, bind_address, name, handler)?;
+        let api = slave.add_service::<T, F>(
+            hostname,
+            bind_address,
+            name,
+            clock,
+            concurrency,
+            max_message_size,
+            handler,
+        )?;
 
         let raii = Arc::new(InteractorRaii::new(ServiceInfo {
             master,
@@ -178,6 +323,10 @@ impl Service {
             .register_service(name, &raii.interactor.api)?;
         Ok(Self { _raii: raii })
     }
+
+    pub(crate) fn registration(&self) -> Weak<dyn Registration> {
+        Arc::downgrade(&self._raii) as Weak<dyn Registration>
+    }
 }
 
 struct ServiceInfo {
@@ -188,7 +337,7 @@ struct ServiceInfo {
 }
 
 impl Interactor for ServiceInfo {
-    fn unregister(&mut self) -> Response<()> {
+    fn unregister(&self) -> Response<()> {
         self.slave.remove_service(&self.name);
         self.master
             .unregister_service(&self.name, &self.api)
@@ -197,23 +346,121 @@ impl Interactor for ServiceInfo {
 }
 
 trait Interactor {
-    fn unregister(&mut self) -> Response<()>;
+    fn unregister(&self) -> Response<()>;
 }
 
 struct InteractorRaii<I: Interactor> {
     pub interactor: I,
+    unregistered: AtomicBool,
 }
 
 impl<I: Interactor> InteractorRaii<I> {
     pub fn new(interactor: I) -> InteractorRaii<I> {
-        Self { interactor }
+        Self {
+            interactor,
+            unregistered: AtomicBool::new(false),
+        }
+    }
+
+    fn unregister_once(&self) {
+        if self.unregistered.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        if let Err(e) = self.interactor.unregister() {
+            error!("Error while unloading: {:?}", e);
+        }
+    }
+}
+
+pub(crate) trait Registration: Send + Sync {
+    /// Unregisters from the master, unless that was already done.
+    fn unregister(&self);
+}
+
+impl<I: Interactor + Send + Sync> Registration for InteractorRaii<I> {
+    fn unregister(&self) {
+        self.unregister_once();
     }
 }
 
 impl<I: Interactor> Drop for InteractorRaii<I> {
     fn drop(&mut self) {
-        if let Err(e) = self.interactor.unregister() {
-            error!("Error while unloading: {:?}", e);
+        self.unregister_once();
+    }
+}
+
+/// Publishers, subscribers and services of a node, so they can be unregistered on shutdown.
+#[derive(Default)]
+pub(crate) struct Registrations {
+    items: Mutex<Vec<Weak<dyn Registration>>>,
+}
+
+impl Registrations {
+    pub fn add(&self, registration: Weak<dyn Registration>) {
+        let mut items = self.items.lock().expect(FAILED_TO_LOCK);
+        items.retain(|item| item.strong_count() > 0);
+        items.push(registration);
+    }
+
+    /// Unregisters everything that is still alive, even if users hold on to it.
+    pub fn unregister_all(&self) {
+        let items = std::mem::take(&mut *self.items.lock().expect(FAILED_TO_LOCK));
+        for item in items.iter().filter_map(Weak::upgrade) {
+            item.unregister();
+        }
+    }
+}
+
+/// Polls `condition` until it holds, the node shuts down, or the node's clock passes the timeout.
+pub(crate) fn wait_for<F>(
+    clock: &dyn Clock,
+    shutdown_manager: &ShutdownManager,
+    timeout: Option<Duration>,
+    condition: F,
+) -> Result<()>
+where
+    F: Fn() -> bool,
+{
+    let deadline = timeout.map(|timeout| clock.now() + RosDuration::from(timeout));
+    loop {
+        if condition() {
+            return Ok(());
+        }
+        if shutdown_manager.awaiting_shutdown() {
+            bail!(ErrorKind::ShuttingDown);
+        }
+        if let Some(ref deadline) = deadline {
+            if clock.now() >= *deadline {
+                bail!(ErrorKind::TimeoutError);
+            }
+        }
+        // Polls with wall time, so a paused simulated clock cannot block shutdown
+        sleep(Duration::from_millis(10));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::clock::RealClock;
+    use crate::api::error::Error;
+
+    #[test]
+    fn wait_for_stops_on_condition_timeout_and_shutdown() {
+        let clock = RealClock::default();
+        let manager = ShutdownManager::default();
+
+        wait_for(&clock, &manager, None, || true).unwrap();
+
+        match wait_for(&clock, &manager, Some(Duration::from_millis(30)), || false) {
+            Err(Error(ErrorKind::TimeoutError, _)) => {}
+            other => panic!("Expected timeout, got {:?}", other),
+        }
+
+        manager.shutdown();
+        match wait_for(&clock, &manager, None, || false) {
+            Err(Error(ErrorKind::ShuttingDown, _)) => {}
+            other => panic!("Expected shutdown, got {:?}", other),
         }
     }
 }
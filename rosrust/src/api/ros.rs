@@ -1,24 +1,41 @@
 use super::super::rosxmlrpc::Response;
 use super::clock::{Clock, Rate, RealClock, SimulatedClock};
+use super::console::{self, ConsoleFormat, ConsoleOutput, LogFile, LogRecord};
 use super::error::{ErrorKind, Result, ResultExt};
+use super::graph::{self, Graph, GraphChanges, GraphWatcher};
+use super::loggers::{self, LoggerLevels, ROOT_LOGGER};
 use super::master::{self, Master, Topic};
 use super::naming::{self, Resolver};
-use super::raii::{Publisher, Service, Subscriber};
+use super::node::RemoteNode;
+use super::raii::{self, Publisher, Registrations, Service, Subscriber};
 use super::resolve;
 use super::slave::Slave;
 use crate::api::clock::Delay;
-use crate::api::ShutdownManager;
+use crate::api::{ShutdownManager, ShutdownReason};
+use crate::msg::roscpp::{self, GetLoggers, GetLoggersRes, SetLoggerLevel, SetLoggerLevelRes};
 use crate::msg::rosgraph_msgs::{Clock as ClockMsg, Log};
 use crate::msg::std_msgs::Header;
-use crate::tcpros::{Client, Message, ServicePair, ServiceResult};
+use crate::tcpros::{
+    Client, Message, ServiceConcurrency, ServiceEvent, ServicePair, ServiceResult,
+    SingleSubscriberPublisher, TransportHints, DEFAULT_MAX_MESSAGE_SIZE,
+};
 use crate::time::{Duration, Time};
+use crate::util::FAILED_TO_LOCK;
+use crate::{RawMessage, RawMessageDescription};
+use crossbeam::channel::TryRecvError;
 use log::error;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use std::thread::sleep;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, sleep};
+use std::time::{SystemTime, UNIX_EPOCH};
 use xml_rpc;
 use yaml_rust::{Yaml, YamlLoader};
 
+/// Time given to queued `/rosout` messages to be sent during shutdown.
+const ROSOUT_FLUSH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
 pub struct Ros {
     master: Arc<Master>,
     slave: Arc<Slave>,
@@ -28,24 +45,56 @@ pub struct Ros {
     name: String,
     clock: Arc<Clock>,
     static_subs: Vec<Subscriber>,
+    static_services: Vec<Service>,
     logger: Option<Publisher<Log>>,
+    logger_levels: Arc<LoggerLevels>,
+    console_format: ConsoleFormat,
+    console_output: Mutex<ConsoleOutput>,
+    log_file: Option<Mutex<LogFile>>,
     shutdown_manager: Arc<ShutdownManager>,
+    registrations: Arc<Registrations>,
+    max_message_sizes: Mutex<HashMap<String, usize>>,
 }
 
 impl Ros {
+    /// Creates a node configured through the command line arguments and environment variables.
     pub fn new(name: &str) -> Result<Ros> {
-        let mut namespace = resolve::namespace();
+        RosBuilder::from_env(name).build()
+    }
+
+    /// Creates a builder for a node with explicit settings, ignoring the command line.
+    pub fn builder(name: &str) -> RosBuilder {
+        RosBuilder::new(name)
+    }
+
+    fn from_builder(builder: RosBuilder) -> Result<Ros> {
+        let mut namespace = builder.namespace;
         if !namespace.starts_with('/') {
             namespace = format!("/{}", namespace);
         }
-        let master_uri = resolve::master();
-        let hostname = resolve::hostname();
-        let name = resolve::name(name);
-        let mut ros = Ros::new_raw(&master_uri, &hostname, &namespace, &name)?;
-        for (src, dest) in resolve::mappings() {
+        let name = if builder.anonymous && !builder.name_remapped {
+            let millis = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|v| v.as_millis())
+                .unwrap_or(0);
+            naming::anonymous_name(&builder.name, &builder.hostname, std::process::id(), millis)
+        } else {
+            builder.name
+        };
+        let mut ros = Ros::new_raw(&builder.master_uri, &builder.hostname, &namespace, &name)?;
+        if builder.log_file {
+            ros.log_file = match LogFile::for_node(&ros.name) {
+                Ok(log_file) => Some(Mutex::new(log_file)),
+                Err(err) => {
+                    error!("Failed to open log file: {}", err);
+                    None
+                }
+            };
+        }
+        for (src, dest) in builder.mappings {
             ros.map(&src, &dest)?;
         }
-        for (src, dest) in resolve::params() {
+        for (src, dest) in builder.params {
             let data = YamlLoader::load_from_str(&dest)
                 .chain_err(|| ErrorKind::BadYamlData(dest.clone()))?
                 .into_iter()
@@ -75,6 +124,33 @@ impl Ros {
 
         ros.logger = Some(ros.publish("/rosout", 100)?);
 
+        let logger_levels = Arc::clone(&ros.logger_levels);
+        let get_loggers = ros.service::<GetLoggers, _>("~get_loggers", move |_| {
+            Ok(GetLoggersRes {
+                loggers: logger_levels
+                    .loggers()
+                    .into_iter()
+                    .map(|(name, level)| roscpp::Logger {
+                        name,
+                        level: loggers::level_name(level).into(),
+                    })
+                    .collect(),
+            })
+        })?;
+        ros.static_services.push(get_loggers);
+
+        let logger_levels = Arc::clone(&ros.logger_levels);
+        let set_logger_level =
+            ros.service::<SetLoggerLevel, _>("~set_logger_level", move |req| {
+                let level = loggers::level_from_name(&req.level)
+                    .ok_or_else(|| format!("Unknown logger level: {}", req.level))?;
+                logger_levels.set_level(&req.logger, level);
+                Ok(SetLoggerLevelRes {})
+            })?;
+        ros.static_services.push(set_logger_level);
+
+        ros.spawn_teardown();
+
         Ok(ros)
     }
 
@@ -119,11 +195,39 @@ impl Ros {
             name,
             clock: Arc::new(RealClock::default()),
             static_subs: Vec::new(),
+            static_services: Vec::new(),
             logger: None,
+            logger_levels: Arc::new(LoggerLevels::default()),
+            console_format: ConsoleFormat::from_env(),
+            console_output: Mutex::new(ConsoleOutput::default()),
+            log_file: None,
             shutdown_manager,
+            registrations: Arc::new(Registrations::default()),
+            max_message_sizes: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Tears the node down once shutdown is requested, for any reason.
+    fn spawn_teardown(&self) {
+        let shutdown_manager = Arc::clone(&self.shutdown_manager);
+        let registrations = Arc::clone(&self.registrations);
+        let logger = self.logger.clone();
+        let slave_shutdown = self.slave.shutdown_tx.clone();
+        thread::spawn(move || {
+            let reason = shutdown_manager.wait_for_request();
+            shutdown_manager.run_hooks(&reason);
+            if let Some(ref logger) = logger {
+                if !logger.flush(ROSOUT_FLUSH_TIMEOUT) {
+                    error!("Failed to flush /rosout before shutting down");
+                }
+            }
+            registrations.unregister_all();
+            // Fails if the slave API server has already stopped
+            let _ = slave_shutdown.send();
+            shutdown_manager.finish();
+        });
+    }
+
     fn map(&mut self, source: &str, destination: &str) -> Result<()> {
         self.resolver.map(source, destination).map_err(Into::into)
     }
@@ -138,6 +242,11 @@ impl Ros {
         &self.name
     }
 
+    /// Resolves a name relative to the node, applying command line remappings.
+    pub fn resolve_name(&self, name: &str) -> Result<String> {
+        Ok(self.resolver.translate(name)?)
+    }
+
     #[inline]
     pub fn hostname(&self) -> &str {
         &self.hostname
@@ -170,6 +279,24 @@ impl Ros {
         Rate::new(Arc::clone(&self.clock), Duration::from_nanos(nanos as i64))
     }
 
+    /// Reason of the shutdown, once it was requested.
+    pub fn shutdown_reason(&self) -> Option<ShutdownReason> {
+        self.shutdown_manager.reason()
+    }
+
+    /// Adds a hook that runs once shutdown is requested, before the node unregisters.
+    pub fn on_shutdown<F>(&self, hook: F)
+    where
+        F: FnOnce(&ShutdownReason) + Send + 'static,
+    {
+        self.shutdown_manager.on_shutdown(hook)
+    }
+
+    /// Blocks until the node is shut down and unregistered from the master.
+    pub fn wait_for_shutdown(&self) {
+        self.shutdown_manager.wait_for_shutdown()
+    }
+
     #[inline]
     pub fn is_ok(&self) -> bool {
         !self.shutdown_manager.awaiting_shutdown()
@@ -203,10 +330,101 @@ impl Ros {
             .map(|v| v.into_iter().map(Into::into).collect())
     }
 
+    pub fn nodes(&self) -> Response<Vec<String>> {
+        self.state().map(|state| graph::node_names(&state))
+    }
+
+    /// Builds a snapshot of the computation graph.
+    ///
+    /// Every node is asked for its publications, subscriptions and bus info. Nodes that
+    /// cannot be reached are still listed, with the reason in their `error` field.
+    pub fn graph(&self) -> Response<Graph> {
+        graph::fetch(&self.master, &self.name)
+    }
+
+    /// Polls the computation graph, calling back whenever it changes.
+    ///
+    /// The first snapshot is reported as a change from an empty graph. Polling stops when
+    /// the returned watcher is dropped, or when the node shuts down.
+    pub fn watch_graph<F>(&self, period: std::time::Duration, callback: F) -> GraphWatcher
+    where
+        F: FnMut(&Graph, &GraphChanges) + Send + 'static,
+    {
+        graph::watch(
+            Arc::clone(&self.master),
+            self.name.clone(),
+            Arc::clone(&self.shutdown_manager),
+            period,
+            callback,
+        )
+    }
+
+    pub fn remote_node(&self, name: &str) -> Result<RemoteNode> {
+        let name = self.resolver.translate(name)?;
+        let uri = self.master.lookup_node(&name)?;
+        Ok(RemoteNode::new(&uri, &self.name, &name)?)
+    }
+
+    pub fn service_uri(&self, service: &str) -> Result<String> {
+        let name = self.resolver.translate(service)?;
+        Ok(self.master.lookup_service(&name)?)
+    }
+
     pub fn client<T: ServicePair>(&self, service: &str) -> Result<Client<T>> {
         let name = self.resolver.translate(service)?;
         let uri = self.master.lookup_service(&name)?;
-        Ok(Client::new(&self.name, &uri, &name))
+        Ok(Client::with_max_message_size(
+            &self.name,
+            &uri,
+            &name,
+            self.max_message_size(&name),
+        ))
+    }
+
+    /// Reads the type of a service from the connection header of its server.
+    pub fn service_type(&self, service: &str) -> Result<String> {
+        Ok(self.client::<RawMessage>(service)?.probe()?.msg_type)
+    }
+
+    /// Creates a client that looks the service up again whenever connecting to it fails.
+    ///
+    /// Unlike `client`, this succeeds even if the service is not provided yet,
+    /// and keeps working when the server restarts on a different address.
+    pub fn resilient_client<T: ServicePair>(&self, service: &str) -> Result<Client<T>> {
+        let name = self.resolver.translate(service)?;
+        let master = Arc::clone(&self.master);
+        let lookup = {
+            let name = name.clone();
+            move || master.lookup_service(&name).ok()
+        };
+        let uri = lookup().unwrap_or_default();
+        Ok(
+            Client::with_max_message_size(&self.name, &uri, &name, self.max_message_size(&name))
+                .with_lookup(lookup),
+        )
+    }
+
+    /// Limits the size of messages received on a topic or service, in bytes.
+    ///
+    /// Applies to subscribers, services and clients created afterwards.
+    /// Larger messages are rejected instead of being buffered,
+    /// and the default limit is `DEFAULT_MAX_MESSAGE_SIZE`.
+    pub fn set_max_message_size(&self, name: &str, max_message_size: usize) -> Result<()> {
+        let name = self.resolver.translate(name)?;
+        self.max_message_sizes
+            .lock()
+            .expect(FAILED_TO_LOCK)
+            .insert(name, max_message_size);
+        Ok(())
+    }
+
+    fn max_message_size(&self, name: &str) -> usize {
+        self.max_message_sizes
+            .lock()
+            .expect(FAILED_TO_LOCK)
+            .get(name)
+            .cloned()
+            .unwrap_or(DEFAULT_MAX_MESSAGE_SIZE)
     }
 
     pub fn wait_for_service(
@@ -243,42 +461,241 @@ impl Ros {
     where
         T: ServicePair,
         F: Fn(T::Request) -> ServiceResult<T::Response> + Send + Sync + 'static,
+    {
+        self.service_with_events::<T, _>(
+            service,
+            ServiceConcurrency::Unbounded,
+            move |request, _| handler(request),
+        )
+    }
+
+    /// Advertises a service whose handler also gets details of each call.
+    ///
+    /// The concurrency limits how many calls get handled at the same time,
+    /// so stateful services can avoid being called re-entrantly.
+    pub fn service_with_events<T, F>(
+        &self,
+        service: &str,
+        concurrency: ServiceConcurrency,
+        handler: F,
+    ) -> Result<Service>
+    where
+        T: ServicePair,
+        F: Fn(T::Request, ServiceEvent) -> ServiceResult<T::Response> + Send + Sync + 'static,
     {
         let name = self.resolver.translate(service)?;
-        Service::new::<T, F>(
+        let service = Service::new::<T, F>(
             Arc::clone(&self.master),
             Arc::clone(&self.slave),
+            Arc::clone(&self.clock),
             &self.hostname,
             &self.bind_address,
             &name,
+            concurrency,
+            self.max_message_size(&name),
             handler,
+        )?;
+        self.registrations.add(service.registration());
+        Ok(service)
+    }
+
+    pub fn subscribe<T, F>(&self, topic: &str, queue_size: usize, callback: F) -> Result<Subscriber>
+    where
+        T: Message,
+        F: Fn(T) + Send + 'static,
+    {
+        self.subscribe_with_ids_and_headers(
+            topic,
+            queue_size,
+            move |data, _| callback(data),
+            |_| (),
         )
     }
 
-    pub fn subscribe<T, F>(
+    /// Subscribes with transport hints that get sent to every publisher of the topic.
+    pub fn subscribe_with_hints<T, F>(
         &self,
         topic: &str,
-        mut queue_size: usize,
+        queue_size: usize,
+        transport_hints: TransportHints,
         callback: F,
     ) -> Result<Subscriber>
     where
         T: Message,
-        F: Fn(T) -> () + Send + 'static,
+        F: Fn(T) + Send + 'static,
+    {
+        self.add_subscriber(
+            topic,
+            queue_size,
+            transport_hints,
+            move |data, _| callback(data),
+            |_| (),
+        )
+    }
+
+    pub fn subscribe_with_ids_and_headers<T, F, G>(
+        &self,
+        topic: &str,
+        queue_size: usize,
+        on_message: F,
+        on_connect: G,
+    ) -> Result<Subscriber>
+    where
+        T: Message,
+        F: Fn(T, &str) + Send + 'static,
+        G: Fn(HashMap<String, String>) + Send + 'static,
+    {
+        self.add_subscriber(
+            topic,
+            queue_size,
+            TransportHints::default(),
+            on_message,
+            on_connect,
+        )
+    }
+
+    fn add_subscriber<T, F, G>(
+        &self,
+        topic: &str,
+        mut queue_size: usize,
+        transport_hints: TransportHints,
+        on_message: F,
+        on_connect: G,
+    ) -> Result<Subscriber>
+    where
+        T: Message,
+        F: Fn(T, &str) + Send + 'static,
+        G: Fn(HashMap<String, String>) + Send + 'static,
     {
         if queue_size == 0 {
             queue_size = usize::max_value();
         }
         let name = self.resolver.translate(topic)?;
-        Subscriber::new::<T, F>(
+        let subscriber = Subscriber::new::<T, F, G>(
             Arc::clone(&self.master),
             Arc::clone(&self.slave),
+            Arc::clone(&self.clock),
+            Arc::clone(&self.shutdown_manager),
             &name,
             queue_size,
-            callback,
+            transport_hints,
+            self.max_message_size(&name),
+            on_message,
+            on_connect,
+        )?;
+        self.registrations.add(subscriber.registration());
+        Ok(subscriber)
+    }
+
+    /// Returns the first message received on a topic.
+    ///
+    /// An existing subscription of this node to the topic is observed without affecting its
+    /// callback. Otherwise a temporary subscription is made and dropped after the message.
+    /// The timeout is measured with the node's clock, and waiting stops early on shutdown.
+    pub fn wait_for_message<T>(
+        &self,
+        topic: &str,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<T>
+    where
+        T: Message,
+    {
+        let name = self.resolver.translate(topic)?;
+        if let Some(receiver) = self.slave.next_message_of_subscription::<T>(&name)? {
+            let message = RefCell::new(None);
+            raii::wait_for(&*self.clock, &self.shutdown_manager, timeout, || {
+                let received = match receiver.try_recv() {
+                    Ok(data) => data.map_err(Into::into),
+                    Err(TryRecvError::Empty) => return false,
+                    Err(TryRecvError::Disconnected) => Err(format!(
+                        "Subscription to '{}' ended before a message arrived",
+                        name
+                    )
+                    .into()),
+                };
+                message.replace(Some(received));
+                true
+            })?;
+            return message.into_inner().expect("Message was received");
+        }
+        let message = Arc::new(Mutex::new(None));
+        let _subscriber = {
+            let message = Arc::clone(&message);
+            self.subscribe::<T, _>(topic, 1, move |v| {
+                message.lock().expect(FAILED_TO_LOCK).get_or_insert(v);
+            })?
+        };
+        raii::wait_for(&*self.clock, &self.shutdown_manager, timeout, || {
+            message.lock().expect(FAILED_TO_LOCK).is_some()
+        })?;
+        let message = message.lock().expect(FAILED_TO_LOCK).take();
+        Ok(message.expect("Message was received"))
+    }
+
+    pub fn publish<T>(&self, topic: &str, queue_size: usize) -> Result<Publisher<T>>
+    where
+        T: Message,
+    {
+        self.publish_with_description(
+            topic,
+            queue_size,
+            RawMessageDescription::from_message::<T>(),
         )
     }
 
-    pub fn publish<T>(&self, topic: &str, mut queue_size: usize) -> Result<Publisher<T>>
+    /// Publishes with callbacks for subscribers connecting and disconnecting.
+    ///
+    /// Callbacks get a publisher that sends messages to that subscriber only,
+    /// which is useful for sending an initial state, or for only computing data when needed.
+    pub fn publish_with_callbacks<T, F, G>(
+        &self,
+        topic: &str,
+        queue_size: usize,
+        on_connect: F,
+        on_disconnect: G,
+    ) -> Result<Publisher<T>>
+    where
+        T: Message,
+        F: Fn(SingleSubscriberPublisher<T>) + Send + Sync + 'static,
+        G: Fn(SingleSubscriberPublisher<T>) + Send + Sync + 'static,
+    {
+        let publisher = self.publish::<T>(topic, queue_size)?;
+        publisher.add_status_callbacks(on_connect, on_disconnect);
+        Ok(publisher)
+    }
+
+    /// Publishes with latching, so the last message is sent to every new subscriber.
+    pub fn publish_latched<T>(&self, topic: &str, queue_size: usize) -> Result<Publisher<T>>
+    where
+        T: Message,
+    {
+        self.advertise(
+            topic,
+            queue_size,
+            true,
+            RawMessageDescription::from_message::<T>(),
+        )
+    }
+
+    pub fn publish_with_description<T>(
+        &self,
+        topic: &str,
+        queue_size: usize,
+        message_description: RawMessageDescription,
+    ) -> Result<Publisher<T>>
+    where
+        T: Message,
+    {
+        self.advertise(topic, queue_size, false, message_description)
+    }
+
+    fn advertise<T>(
+        &self,
+        topic: &str,
+        mut queue_size: usize,
+        latching: bool,
+        message_description: RawMessageDescription,
+    ) -> Result<Publisher<T>>
     where
         T: Message,
     {
@@ -286,55 +703,226 @@ impl Ros {
             queue_size = usize::max_value();
         }
         let name = self.resolver.translate(topic)?;
-        Publisher::new(
+        let publisher = Publisher::new(
             Arc::clone(&self.master),
             Arc::clone(&self.slave),
             Arc::clone(&self.clock),
+            Arc::clone(&self.shutdown_manager),
             &self.bind_address,
             &name,
             queue_size,
-        )
+            latching,
+            message_description,
+        )?;
+        self.registrations.add(publisher.registration());
+        Ok(publisher)
     }
 
-    fn log_to_terminal(&self, level: i8, msg: &str, file: &str, line: u32) {
-        use colored::{Color, Colorize};
+    fn log_to_terminal(&self, record: &LogRecord) {
+        let output = *self.console_output.lock().expect(FAILED_TO_LOCK);
+        console::write_to_terminal(&self.console_format, output, record);
+    }
 
-        let format_string =
-            |prefix, color| format!("[{} @ {}:{}]: {}", prefix, file, line, msg).color(color);
+    pub fn set_console_output(&self, output: ConsoleOutput) {
+        *self.console_output.lock().expect(FAILED_TO_LOCK) = output;
+    }
 
-        match level {
-            Log::DEBUG => println!("{}", format_string("DEBUG", Color::White)),
-            Log::INFO => println!("{}", format_string("INFO", Color::White)),
-            Log::WARN => eprintln!("{}", format_string("WARN", Color::Yellow)),
-            Log::ERROR => eprintln!("{}", format_string("ERROR", Color::Red)),
-            Log::FATAL => eprintln!("{}", format_string("FATAL", Color::Red)),
-            _ => {}
-        }
+    pub fn logger_level(&self, logger: &str) -> i8 {
+        self.logger_levels.level(logger)
+    }
+
+    pub fn set_logger_level(&self, logger: &str, level: i8) {
+        self.logger_levels.set_level(logger, level)
     }
 
     pub fn log(&self, level: i8, msg: String, file: &str, line: u32) {
-        self.log_to_terminal(level, &msg, file, line);
-        let logger = &match self.logger {
+        self.log_named(level, msg, ROOT_LOGGER, "", file, line)
+    }
+
+    /// Prints a record that passes the logger levels, without publishing or saving it.
+    pub(crate) fn log_named_to_terminal(
+        &self,
+        level: i8,
+        msg: &str,
+        logger: &str,
+        function: &str,
+        file: &str,
+        line: u32,
+    ) {
+        if !self.logger_levels.enabled(logger, level) {
+            return;
+        }
+        self.log_to_terminal(&LogRecord {
+            level,
+            msg,
+            node: &self.name,
+            logger,
+            function,
+            file,
+            line,
+            time: &self.now(),
+        });
+    }
+
+    pub fn log_named(
+        &self,
+        level: i8,
+        msg: String,
+        logger: &str,
+        function: &str,
+        file: &str,
+        line: u32,
+    ) {
+        if !self.logger_levels.enabled(logger, level) {
+            return;
+        }
+        let stamp = self.now();
+        let record = LogRecord {
+            level,
+            msg: &msg,
+            node: &self.name,
+            logger,
+            function,
+            file,
+            line,
+            time: &stamp,
+        };
+        self.log_to_terminal(&record);
+        if let Some(ref log_file) = self.log_file {
+            if let Err(err) = log_file.lock().expect(FAILED_TO_LOCK).write(&record) {
+                error!("Failed to write to log file: {}", err);
+            }
+        }
+        let publisher = &match self.logger {
             Some(ref v) => v,
             None => return,
         };
         let topics = self.slave.publications.get_topic_names();
         let message = Log {
-            header: Header::default(),
+            header: Header {
+                stamp,
+                ..Header::default()
+            },
             level,
             msg,
             name: self.name.clone(),
             line,
             file: file.into(),
-            function: String::default(),
+            function: function.into(),
             topics,
         };
-        if let Err(err) = logger.send(message) {
+        if let Err(err) = publisher.send(message) {
             error!("Logging error: {}", err);
         }
     }
 }
 
+/// Settings of a node, used to run several independent nodes in one process.
+///
+/// Every built node has its own slave API server and shutdown manager.
+#[derive(Clone, Debug)]
+pub struct RosBuilder {
+    name: String,
+    master_uri: String,
+    hostname: String,
+    namespace: String,
+    mappings: Vec<(String, String)>,
+    params: Vec<(String, String)>,
+    anonymous: bool,
+    name_remapped: bool,
+    log_file: bool,
+}
+
+impl RosBuilder {
+    /// Creates settings for a node in the root namespace, with a master on localhost.
+    ///
+    /// Such nodes are usually short lived or run inside tests, so they write no log file.
+    pub fn new(name: &str) -> RosBuilder {
+        RosBuilder {
+            name: name.into(),
+            master_uri: String::from("http://localhost:11311/"),
+            hostname: String::from("localhost"),
+            namespace: String::new(),
+            mappings: Vec::new(),
+            params: Vec::new(),
+            anonymous: false,
+            name_remapped: false,
+            log_file: false,
+        }
+    }
+
+    /// Reads the settings from the command line arguments and environment variables.
+    pub fn from_env(name: &str) -> RosBuilder {
+        let remapped_name = resolve::name(name);
+        RosBuilder {
+            name_remapped: remapped_name != name,
+            name: remapped_name,
+            master_uri: resolve::master(),
+            hostname: resolve::hostname(),
+            namespace: resolve::namespace(),
+            mappings: resolve::mappings(),
+            params: resolve::params(),
+            anonymous: false,
+            log_file: true,
+        }
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.into();
+        self.name_remapped = false;
+        self
+    }
+
+    /// Appends the hostname, process ID and time to the name, so multiple instances can run.
+    ///
+    /// This is ignored when the name was remapped with `__name:=` on the command line.
+    pub fn anonymous(mut self, anonymous: bool) -> Self {
+        self.anonymous = anonymous;
+        self
+    }
+
+    /// Writes messages to a log file in the log directory as well.
+    ///
+    /// This is enabled by default for nodes configured from the environment.
+    pub fn log_file(mut self, enabled: bool) -> Self {
+        self.log_file = enabled;
+        self
+    }
+
+    pub fn master_uri(mut self, master_uri: &str) -> Self {
+        self.master_uri = master_uri.into();
+        self
+    }
+
+    pub fn hostname(mut self, hostname: &str) -> Self {
+        self.hostname = hostname.into();
+        self
+    }
+
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.namespace = namespace.into();
+        self
+    }
+
+    /// Remaps a name, like `source:=destination` on the command line.
+    pub fn remap(mut self, source: &str, destination: &str) -> Self {
+        self.mappings.push((source.into(), destination.into()));
+        self
+    }
+
+    /// Sets a parameter to a YAML value after startup, like `_name:=value` on the command line.
+    ///
+    /// Names are resolved relative to the node, so `~name` is a private parameter.
+    pub fn param(mut self, name: &str, value: &str) -> Self {
+        self.params.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn build(self) -> Result<Ros> {
+        Ros::from_builder(self)
+    }
+}
+
 pub struct Parameter {
     master: Arc<Master>,
     name: String,
@@ -361,6 +949,15 @@ impl Parameter {
         self.master.set_param_any(&self.name, value).and(Ok(()))
     }
 
+    pub fn get_yaml(&self) -> Response<Yaml> {
+        self.get_raw().map(xmlrpc_to_yaml)
+    }
+
+    pub fn set_yaml(&self, value: Yaml) -> Result<()> {
+        self.set_raw(yaml_to_xmlrpc(value)?)?;
+        Ok(())
+    }
+
     pub fn delete(&self) -> Response<()> {
         self.master.delete_param(&self.name).and(Ok(()))
     }
@@ -397,6 +994,29 @@ fn yaml_to_xmlrpc(val: Yaml) -> Result<xml_rpc::Value> {
     })
 }
 
+fn xmlrpc_to_yaml(val: xml_rpc::Value) -> Yaml {
+    match val {
+        xml_rpc::Value::Int(v) => Yaml::Integer(i64::from(v)),
+        xml_rpc::Value::Bool(v) => Yaml::Boolean(v),
+        xml_rpc::Value::String(v) | xml_rpc::Value::DateTime(v) => Yaml::String(v),
+        xml_rpc::Value::Double(v) => Yaml::Real(format!("{:?}", v)),
+        xml_rpc::Value::Base64(v) => {
+            Yaml::Array(v.into_iter().map(|v| Yaml::Integer(i64::from(v))).collect())
+        }
+        xml_rpc::Value::Array(v) => Yaml::Array(v.into_iter().map(xmlrpc_to_yaml).collect()),
+        xml_rpc::Value::Struct(v) => {
+            let mut items = v.into_iter().collect::<Vec<_>>();
+            items.sort_by(|a, b| a.0.cmp(&b.0));
+            Yaml::Hash(
+                items
+                    .into_iter()
+                    .map(|(k, v)| (Yaml::String(k), xmlrpc_to_yaml(v)))
+                    .collect(),
+            )
+        }
+    }
+}
+
 fn yaml_to_string(val: Yaml) -> Result<String> {
     Ok(match val {
         Yaml::Real(v) | Yaml::String(v) => v,
@@ -415,8 +1035,12 @@ pub struct Spinner {
 
 impl Drop for Spinner {
     fn drop(&mut self) {
-        while !self.shutdown_manager.awaiting_shutdown() {
-            sleep(std::time::Duration::from_millis(100));
-        }
+        self.shutdown_manager.wait_for_shutdown();
+    }
+}
+
+impl Drop for Ros {
+    fn drop(&mut self) {
+        self.shutdown_manager.shutdown();
     }
 }
@@ -0,0 +1,190 @@
+use crate::util::FAILED_TO_LOCK;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+
+/// Cause of a node shutting down.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ShutdownReason {
+    /// Shutdown was requested through the API.
+    User,
+    /// The process received SIGINT.
+    Sigint,
+    /// The master or another node called `shutdown` on the slave API, with the given message.
+    Master(String),
+    /// Another node registered with the same name, so the master shut this one down.
+    DuplicateName(String),
+}
+
+impl ShutdownReason {
+    /// Classifies the message passed to the `shutdown` call of the slave API.
+    pub fn from_master_message(message: &str) -> Self {
+        // Sent by rosmaster to the old node when a node registers with the same name
+        if message.contains("registered with same name") {
+            ShutdownReason::DuplicateName(message.into())
+        } else {
+            ShutdownReason::Master(message.into())
+        }
+    }
+}
+
+type Hook = Box<dyn FnOnce(&ShutdownReason) + Send>;
+
+/// Tracks shutdown requests, and the teardown of the node that follows them.
+pub struct ShutdownManager {
+    should_shutdown: AtomicBool,
+    reason: Mutex<Option<ShutdownReason>>,
+    requested: Condvar,
+    hooks: Mutex<Vec<Hook>>,
+    finished: Mutex<bool>,
+    finished_signal: Condvar,
+}
+
+impl Default for ShutdownManager {
+    fn default() -> Self {
+        Self {
+            should_shutdown: AtomicBool::new(false),
+            reason: Mutex::new(None),
+            requested: Condvar::new(),
+            hooks: Mutex::new(Vec::new()),
+            finished: Mutex::new(false),
+            finished_signal: Condvar::new(),
+        }
+    }
+}
+
+impl ShutdownManager {
+    pub fn awaiting_shutdown(&self) -> bool {
+        self.should_shutdown.load(Ordering::Relaxed)
+    }
+
+    pub fn shutdown(&self) {
+        self.shutdown_with_reason(ShutdownReason::User)
+    }
+
+    /// Requests shutdown, keeping the reason of any earlier request.
+    pub fn shutdown_with_reason(&self, reason: ShutdownReason) {
+        let mut current = self.reason.lock().expect(FAILED_TO_LOCK);
+        if current.is_none() {
+            *current = Some(reason);
+        }
+        self.should_shutdown.store(true, Ordering::Relaxed);
+        self.requested.notify_all();
+    }
+
+    /// Reason of the first shutdown request, if there was one.
+    pub fn reason(&self) -> Option<ShutdownReason> {
+        self.reason.lock().expect(FAILED_TO_LOCK).clone()
+    }
+
+    /// Adds a hook that runs before the node unregisters from the master.
+    ///
+    /// If the node is already shut down, the hook runs immediately.
+    pub fn on_shutdown<F>(&self, hook: F)
+    where
+        F: FnOnce(&ShutdownReason) + Send + 'static,
+    {
+        // The lock is held while adding, so the hook can not be missed by a finishing teardown
+        let finished = self.finished.lock().expect(FAILED_TO_LOCK);
+        if !*finished {
+            self.hooks
+                .lock()
+                .expect(FAILED_TO_LOCK)
+                .push(Box::new(hook));
+            return;
+        }
+        drop(finished);
+        hook(&self.reason().unwrap_or(ShutdownReason::User));
+    }
+
+    /// Blocks until the teardown of the node has finished.
+    pub fn wait_for_shutdown(&self) {
+        let mut finished = self.finished.lock().expect(FAILED_TO_LOCK);
+        while !*finished {
+            finished = self.finished_signal.wait(finished).expect(FAILED_TO_LOCK);
+        }
+    }
+
+    pub(crate) fn wait_for_request(&self) -> ShutdownReason {
+        let mut reason = self.reason.lock().expect(FAILED_TO_LOCK);
+        loop {
+            if let Some(ref reason) = *reason {
+                return reason.clone();
+            }
+            reason = self.requested.wait(reason).expect(FAILED_TO_LOCK);
+        }
+    }
+
+    /// Runs the hooks in the order they were added.
+    pub(crate) fn run_hooks(&self, reason: &ShutdownReason) {
+        let hooks = std::mem::take(&mut *self.hooks.lock().expect(FAILED_TO_LOCK));
+        for hook in hooks {
+            hook(reason);
+        }
+    }
+
+    pub(crate) fn finish(&self) {
+        *self.finished.lock().expect(FAILED_TO_LOCK) = true;
+        // Hooks added while the teardown was running
+        self.run_hooks(&self.reason().unwrap_or(ShutdownReason::User));
+        self.finished_signal.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_first_reason() {
+        let manager = ShutdownManager::default();
+        assert!(!manager.awaiting_shutdown());
+        assert_eq!(None, manager.reason());
+        manager.shutdown_with_reason(ShutdownReason::from_master_message(
+            "new node registered with same name",
+        ));
+        manager.shutdown();
+        assert!(manager.awaiting_shutdown());
+        assert_eq!(
+            Some(ShutdownReason::DuplicateName(
+                "new node registered with same name".into()
+            )),
+            manager.reason()
+        );
+        assert_eq!(
+            ShutdownReason::Master("rosnode kill".into()),
+            ShutdownReason::from_master_message("rosnode kill")
+        );
+    }
+
+    #[test]
+    fn runs_hooks_once() {
+        use std::sync::Arc;
+
+        let manager = Arc::new(ShutdownManager::default());
+        let (tx, rx) = crossbeam::channel::unbounded();
+        let hook_tx = tx.clone();
+        manager.on_shutdown(move |reason| hook_tx.send(reason.clone()).unwrap());
+
+        let teardown = {
+            let manager = Arc::clone(&manager);
+            std::thread::spawn(move || {
+                let reason = manager.wait_for_request();
+                manager.run_hooks(&reason);
+                manager.finish();
+            })
+        };
+        manager.shutdown_with_reason(ShutdownReason::Sigint);
+        manager.wait_for_shutdown();
+        teardown.join().unwrap();
+        assert_eq!(
+            vec![ShutdownReason::Sigint],
+            rx.try_iter().collect::<Vec<_>>()
+        );
+
+        manager.on_shutdown(move |reason| tx.send(reason.clone()).unwrap());
+        assert_eq!(
+            vec![ShutdownReason::Sigint],
+            rx.try_iter().collect::<Vec<_>>()
+        );
+    }
+}
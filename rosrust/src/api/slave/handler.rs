@@ -1,9 +1,9 @@
 use super::publications::PublicationsTracker;
 use super::subscriptions::SubscriptionsTracker;
+use crate::api::{ShutdownManager, ShutdownReason};
 use crate::rosxmlrpc::{self, Response, ResponseError, Server};
 use crate::tcpros::Service;
-use crate::util::kill;
-use log::{error, info};
+use log::{info, warn};
 use nix::unistd::getpid;
 use std::collections::HashMap;
 use std::net::SocketAddr;
@@ -29,7 +29,7 @@ impl SlaveHandler {
         master_uri: &str,
         hostname: &str,
         name: &str,
-        shutdown_signal: kill::Sender,
+        shutdown_manager: Arc<ShutdownManager>,
     ) -> SlaveHandler {
         let mut server = Server::default();
 
@@ -58,14 +58,15 @@ impl SlaveHandler {
                 Some(Value::String(message)) => message,
                 _ => return Err(ResponseError::Client("Missing argument 'message'".into())),
             };
-            info!("Server is shutting down because: {}", message);
-            match shutdown_signal.send() {
-                Ok(()) => Ok(Value::Int(0)),
-                Err(err) => {
-                    error!("Shutdown error: {:?}", err);
-                    Err(ResponseError::Server("Failed to shut down".into()))
-                }
+            let reason = ShutdownReason::from_master_message(&message);
+            if let ShutdownReason::DuplicateName(..) = reason {
+                warn!("Shutting down, another node registered with our name");
+            } else {
+                info!("Server is shutting down because: {}", message);
             }
+            // The server itself is stopped by the teardown of the node
+            shutdown_manager.shutdown_with_reason(reason);
+            Ok(Value::Int(0))
         });
 
         server.register_value("getPid", "PID", |_args| Ok(Value::Int(getpid().into())));
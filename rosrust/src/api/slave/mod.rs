@@ -5,9 +5,14 @@ mod subscriptions;
 use self::handler::SlaveHandler;
 use super::error::{self, ErrorKind, Result};
 use crate::api::ShutdownManager;
-use crate::tcpros::{Message, PublisherStream, Service, ServicePair, ServiceResult};
+use crate::tcpros::{
+    Message, PublisherStream, Service, ServiceConcurrency, ServiceEvent, ServicePair,
+    ServiceResult, TransportHints, TypeMismatch, TypeMismatchCallback,
+};
 use crate::util::{kill, FAILED_TO_LOCK};
-use crossbeam::channel::TryRecvError;
+use crate::Clock;
+use crate::RawMessageDescription;
+use crossbeam::channel::{Receiver, TryRecvError};
 use log::error;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
@@ -36,7 +41,7 @@ impl Slave {
         use std::net::ToSocketAddrs;
 
         let (shutdown_tx, shutdown_rx) = kill::channel(kill::KillMode::Sync);
-        let handler = SlaveHandler::new(master_uri, hostname, name, shutdown_tx.clone());
+        let handler = SlaveHandler::new(master_uri, hostname, name, Arc::clone(&shutdown_manager));
         let publications = handler.publications.clone();
         let subscriptions = handler.subscriptions.clone();
         let services = Arc::clone(&handler.services);
@@ -88,16 +93,20 @@ impl Slave {
             .add_publishers(topic, &self.name, publishers)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn add_service<T, F>(
         &self,
         hostname: &str,
         bind_address: &str,
         service: &str,
+        clock: Arc<dyn Clock>,
+        concurrency: ServiceConcurrency,
+        max_message_size: usize,
         handler: F,
     ) -> SerdeResult<String>
     where
         T: ServicePair,
-        F: Fn(T::Request) -> ServiceResult<T::Response> + Send + Sync + 'static,
+        F: Fn(T::Request, ServiceEvent) -> ServiceResult<T::Response> + Send + Sync + 'static,
     {
         use std::collections::hash_map::Entry;
         match self
@@ -111,8 +120,17 @@ impl Slave {
                 Err(ErrorKind::Duplicate("service".into()).into())
             }
             Entry::Vacant(entry) => {
-                let service =
-                    Service::new::<T, _>(hostname, bind_address, 0, service, &self.name, handler)?;
+                let service = Service::new::<T, _>(
+                    hostname,
+                    bind_address,
+                    0,
+                    service,
+                    &self.name,
+                    clock,
+                    concurrency,
+                    max_message_size,
+                    handler,
+                )?;
                 let api = service.api.clone();
                 entry.insert(service);
                 Ok(api)
@@ -131,11 +149,13 @@ impl Slave {
         hostname: &str,
         topic: &str,
         queue_size: usize,
+        message_description: &RawMessageDescription,
     ) -> error::tcpros::Result<PublisherStream<T>>
     where
         T: Message,
     {
-        self.publications.add(hostname, topic, queue_size)
+        self.publications
+            .add(hostname, topic, queue_size, &self.name, message_description)
     }
 
     #[inline]
@@ -144,13 +164,29 @@ impl Slave {
     }
 
     #[inline]
-    pub fn add_subscription<T, F>(&self, topic: &str, queue_size: usize, callback: F) -> Result<()>
+    pub fn add_subscription<T, F, G>(
+        &self,
+        topic: &str,
+        queue_size: usize,
+        transport_hints: TransportHints,
+        max_message_size: usize,
+        on_message: F,
+        on_connect: G,
+    ) -> Result<()>
     where
         T: Message,
-        F: Fn(T) -> () + Send + 'static,
+        F: Fn(T, &str) + Send + 'static,
+        G: Fn(HashMap<String, String>) + Send + 'static,
     {
-        self.subscriptions
-            .add(&self.name, topic, queue_size, callback)
+        self.subscriptions.add(
+            &self.name,
+            topic,
+            queue_size,
+            transport_hints,
+            max_message_size,
+            on_message,
+            on_connect,
+        )
     }
 
     #[inline]
@@ -162,4 +198,32 @@ impl Slave {
     pub fn get_publisher_count_of_subscription(&self, topic: &str) -> usize {
         self.subscriptions.publisher_count(topic)
     }
+
+    #[inline]
+    pub fn next_message_of_subscription<T: Message>(
+        &self,
+        topic: &str,
+    ) -> Result<Option<Receiver<error::tcpros::Result<T>>>> {
+        self.subscriptions.next_message(topic)
+    }
+
+    #[inline]
+    pub fn is_subscription_latched(&self, topic: &str) -> bool {
+        self.subscriptions.is_latched(topic)
+    }
+
+    #[inline]
+    pub fn get_type_mismatches_of_subscription(&self, topic: &str) -> Vec<TypeMismatch> {
+        self.subscriptions.type_mismatches(topic)
+    }
+
+    #[inline]
+    pub fn add_type_mismatch_callback_to_subscription(
+        &self,
+        topic: &str,
+        callback: TypeMismatchCallback,
+    ) {
+        self.subscriptions
+            .add_type_mismatch_callback(topic, callback)
+    }
 }
@@ -1,7 +1,8 @@
 use crate::api::error::{self, ErrorKind, Result};
-use crate::tcpros::{Subscriber, Topic};
+use crate::tcpros::{Subscriber, Topic, TransportHints, TypeMismatch, TypeMismatchCallback};
 use crate::util::FAILED_TO_LOCK;
 use crate::Message;
+use crossbeam::channel::Receiver;
 use log::error;
 use std::collections::{BTreeSet, HashMap};
 use std::iter::FromIterator;
@@ -46,10 +47,21 @@ impl SubscriptionsTracker {
             .collect()
     }
 
-    pub fn add<T, F>(&self, name: &str, topic: &str, queue_size: usize, callback: F) -> Result<()>
+    #[allow(clippy::too_many_arguments)]
+    pub fn add<T, F, G>(
+        &self,
+        name: &str,
+        topic: &str,
+        queue_size: usize,
+        transport_hints: TransportHints,
+        max_message_size: usize,
+        on_message: F,
+        on_connect: G,
+    ) -> Result<()>
     where
         T: Message,
-        F: Fn(T) -> () + Send + 'static,
+        F: Fn(T, &str) + Send + 'static,
+        G: Fn(HashMap<String, String>) + Send + 'static,
     {
         use std::collections::hash_map::Entry;
         match self
@@ -63,7 +75,15 @@ impl SubscriptionsTracker {
                 Err(ErrorKind::Duplicate("subscription".into()).into())
             }
             Entry::Vacant(entry) => {
-                let subscriber = Subscriber::new::<T, F>(name, topic, queue_size, callback);
+                let subscriber = Subscriber::new::<T, F, G>(
+                    name,
+                    topic,
+                    queue_size,
+                    transport_hints,
+                    max_message_size,
+                    on_message,
+                    on_connect,
+                );
                 entry.insert(subscriber);
                 Ok(())
             }
@@ -83,6 +103,53 @@ impl SubscriptionsTracker {
             .get(topic)
             .map_or(0, Subscriber::publisher_count)
     }
+
+    /// Observes the next message of an existing subscription, if there is one.
+    pub fn next_message<T: Message>(
+        &self,
+        topic: &str,
+    ) -> Result<Option<Receiver<error::tcpros::Result<T>>>> {
+        let mapping = self.mapping.lock().expect(FAILED_TO_LOCK);
+        let subscriber = match mapping.get(topic) {
+            Some(subscriber) => subscriber,
+            None => return Ok(None),
+        };
+        let msg_type = &subscriber.get_topic().msg_type;
+        if *msg_type != T::msg_type() {
+            bail!(
+                "Cannot wait for '{}' messages on the '{}' subscription to '{}'",
+                T::msg_type(),
+                msg_type,
+                topic
+            );
+        }
+        Ok(Some(subscriber.next_message()))
+    }
+
+    #[inline]
+    pub fn is_latched(&self, topic: &str) -> bool {
+        self.mapping
+            .lock()
+            .expect(FAILED_TO_LOCK)
+            .get(topic)
+            .is_some_and(Subscriber::is_latched)
+    }
+
+    #[inline]
+    pub fn type_mismatches(&self, topic: &str) -> Vec<TypeMismatch> {
+        self.mapping
+            .lock()
+            .expect(FAILED_TO_LOCK)
+            .get(topic)
+            .map_or_else(Vec::new, Subscriber::type_mismatches)
+    }
+
+    #[inline]
+    pub fn add_type_mismatch_callback(&self, topic: &str, callback: TypeMismatchCallback) {
+        if let Some(subscriber) = self.mapping.lock().expect(FAILED_TO_LOCK).get(topic) {
+            subscriber.add_type_mismatch_callback(callback);
+        }
+    }
 }
 
 fn connect_to_publisher(
@@ -94,7 +161,8 @@ fn connect_to_publisher(
     if subscriber.is_connected_to(publisher) {
         return Ok(());
     }
-    let (protocol, hostname, port) = request_topic(publisher, caller_id, topic)?;
+    let protocols = subscriber.get_transport_hints().protocols();
+    let (protocol, hostname, port) = request_topic(publisher, caller_id, topic, &protocols)?;
     if protocol != "TCPROS" {
         bail!(ErrorKind::CommunicationIssue(format!(
             "Publisher responded with a non-TCPROS protocol: {}",
@@ -110,6 +178,7 @@ fn request_topic(
     publisher_uri: &str,
     caller_id: &str,
     topic: &str,
+    protocols: &[Vec<String>],
 ) -> error::rosxmlrpc::Result<(String, String, i32)> {
     use crate::rosxmlrpc::error::ResultExt;
     let (_code, _message, protocols): (i32, String, (String, String, i32)) = xml_rpc::Client::new()
@@ -119,7 +188,7 @@ fn request_topic(
                 .parse()
                 .chain_err(|| error::rosxmlrpc::ErrorKind::BadUri(publisher_uri.into()))?,
             "requestTopic",
-            &(caller_id, topic, [["TCPROS"]]),
+            (caller_id, topic, protocols),
         )
         .chain_err(|| error::rosxmlrpc::ErrorKind::TopicConnectionError(topic.to_owned()))?
         .map_err(|_| "error")?;
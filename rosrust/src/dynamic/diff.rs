@@ -0,0 +1,196 @@
+use super::{join_path, DynamicMsg, FieldCase, FieldInfo, Msg};
+use std::fmt;
+
+/// Single difference between two versions of a message definition.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldDifference {
+    /// Field that only exists in the other definition.
+    Added { path: String, datatype: String },
+    /// Field that only exists in this definition.
+    Removed { path: String, datatype: String },
+    /// Field that exists in both definitions with different types.
+    Changed {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+    /// Fields that exist in both definitions, but in a different order.
+    Reordered { path: String },
+}
+
+impl fmt::Display for FieldDifference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldDifference::Added { path, datatype } => {
+                write!(f, "added field '{}' of type '{}'", path, datatype)
+            }
+            FieldDifference::Removed { path, datatype } => {
+                write!(f, "removed field '{}' of type '{}'", path, datatype)
+            }
+            FieldDifference::Changed {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "field '{}' changed type from '{}' to '{}'",
+                path, expected, actual
+            ),
+            FieldDifference::Reordered { path } if path.is_empty() => {
+                write!(f, "fields were reordered")
+            }
+            FieldDifference::Reordered { path } => {
+                write!(f, "fields of '{}' were reordered", path)
+            }
+        }
+    }
+}
+
+impl DynamicMsg {
+    /// Lists the field-level differences between this message and another version of it.
+    ///
+    /// Nested messages of the same type are compared field by field, so changes deep
+    /// inside a message are reported with their full path.
+    pub fn diff(&self, other: &DynamicMsg) -> Vec<FieldDifference> {
+        let mut differences = vec![];
+        diff_messages(self, &self.msg, other, &other.msg, "", &mut differences);
+        differences
+    }
+}
+
+fn diff_messages(
+    left: &DynamicMsg,
+    left_msg: &Msg,
+    right: &DynamicMsg,
+    right_msg: &Msg,
+    path: &str,
+    differences: &mut Vec<FieldDifference>,
+) {
+    let find = |msg: &'_ Msg, name: &str| -> Option<FieldInfo> {
+        msg.fields.iter().find(|field| field.name == name).cloned()
+    };
+    let mut shared_left = vec![];
+    for field in &left_msg.fields {
+        let field_path = join_path(path, &field.name);
+        match find(right_msg, &field.name) {
+            None => differences.push(FieldDifference::Removed {
+                path: field_path,
+                datatype: describe(left_msg, field),
+            }),
+            Some(other) => {
+                shared_left.push(field.name.clone());
+                let expected = describe(left_msg, field);
+                let actual = describe(right_msg, &other);
+                if expected != actual {
+                    differences.push(FieldDifference::Changed {
+                        path: field_path,
+                        expected,
+                        actual,
+                    });
+                    continue;
+                }
+                if field.datatype.is_builtin() {
+                    continue;
+                }
+                if let (Ok(left_dependency), Ok(right_dependency)) = (
+                    left.dependency(left_msg, &field.datatype),
+                    right.dependency(right_msg, &other.datatype),
+                ) {
+                    diff_messages(
+                        left,
+                        left_dependency,
+                        right,
+                        right_dependency,
+                        &field_path,
+                        differences,
+                    );
+                }
+            }
+        }
+    }
+    let mut shared_right = vec![];
+    for field in &right_msg.fields {
+        if find(left_msg, &field.name).is_none() {
+            differences.push(FieldDifference::Added {
+                path: join_path(path, &field.name),
+                datatype: describe(right_msg, field),
+            });
+        } else {
+            shared_right.push(field.name.clone());
+        }
+    }
+    if shared_left != shared_right {
+        differences.push(FieldDifference::Reordered { path: path.into() });
+    }
+}
+
+/// Full type of a field, as it would be written in a message definition.
+fn describe(msg: &Msg, field: &FieldInfo) -> String {
+    let datatype = match field.datatype.struct_path(&msg.package) {
+        Some((package, name)) => format!("{}/{}", package, name),
+        None => field.datatype.builtin_name().into(),
+    };
+    match field.case {
+        FieldCase::Unit => datatype,
+        FieldCase::Vector => format!("{}[]", datatype),
+        FieldCase::Array(count) => format!("{}[{}]", datatype, count),
+        FieldCase::Const(ref value) => format!("{}={}", datatype, value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_changes_in_nested_messages() {
+        let expected = DynamicMsg::new(
+            "foo/Bar",
+            "Baz baz\nint32 count\nuint8 MODE=1\nstring name\n\
+             ================================================================================\n\
+             MSG: foo/Baz\nfloat64 x\nfloat64 y\n",
+        )
+        .unwrap();
+        let actual = DynamicMsg::new(
+            "foo/Bar",
+            "Baz baz\nint64 count\nuint8 MODE=2\nstring[] tags\n\
+             ================================================================================\n\
+             MSG: foo/Baz\nfloat64 y\nfloat64 x\nfloat64 z\n",
+        )
+        .unwrap();
+        let differences = expected.diff(&actual);
+        assert_eq!(
+            vec![
+                FieldDifference::Added {
+                    path: "baz.z".into(),
+                    datatype: "float64".into(),
+                },
+                FieldDifference::Reordered { path: "baz".into() },
+                FieldDifference::Changed {
+                    path: "count".into(),
+                    expected: "int32".into(),
+                    actual: "int64".into(),
+                },
+                FieldDifference::Changed {
+                    path: "MODE".into(),
+                    expected: "uint8=1".into(),
+                    actual: "uint8=2".into(),
+                },
+                FieldDifference::Removed {
+                    path: "name".into(),
+                    datatype: "string".into(),
+                },
+                FieldDifference::Added {
+                    path: "tags".into(),
+                    datatype: "string[]".into(),
+                },
+            ],
+            differences
+        );
+        assert_eq!(
+            "field 'count' changed type from 'int32' to 'int64'",
+            differences[2].to_string()
+        );
+        assert!(expected.diff(&expected).is_empty());
+    }
+}
@@ -0,0 +1,25 @@
+#![allow(deprecated)]
+error_chain! {
+    foreign_links {
+        Io(::std::io::Error);
+        Yaml(::yaml_rust::ScanError);
+    }
+    errors {
+        BadMessageDefinition(details: String) {
+            description("Bad message definition")
+            display("Bad message definition: {}", details)
+        }
+        MissingDependency(msg_type: String) {
+            description("Message definition is missing a dependency")
+            display("Message definition is missing dependency '{}'", msg_type)
+        }
+        MessageNotFound(msg_type: String) {
+            description("Could not find message definition")
+            display("Could not find definition of '{}' in any search path", msg_type)
+        }
+        BadValue(field: String, details: String) {
+            description("Value does not match message definition")
+            display("Bad value for field '{}': {}", field, details)
+        }
+    }
+}
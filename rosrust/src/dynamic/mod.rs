@@ -0,0 +1,667 @@
+//! Runtime introspection of messages, based on their textual definitions.
+//!
+//! This allows handling messages whose types are not known at compile time, like
+//! the ones passing through command line tools. Message definitions are received
+//! in the `message_definition` connection header field, or loaded from the
+//! message search paths.
+
+pub use self::diff::FieldDifference;
+pub use self::msg::{DataType, FieldCase, FieldInfo, Msg};
+pub use self::search::{find_definition, find_service_definitions};
+pub use self::value::{MessageValue, Value};
+pub use self::yaml::{to_yaml, to_yaml_string};
+
+use self::error::{ErrorKind, Result, ResultExt};
+use crate::rosmsg::RosMsg;
+use crate::time::{Duration, Time};
+use crate::{RawMessage, RawMessageDescription};
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+
+mod diff;
+pub mod error;
+mod msg;
+mod search;
+mod value;
+mod yaml;
+
+/// Message type, with all of its dependencies, parsed from a message definition.
+#[derive(Clone, Debug)]
+pub struct DynamicMsg {
+    msg: Msg,
+    dependencies: HashMap<(String, String), Msg>,
+    definition: String,
+}
+
+impl DynamicMsg {
+    /// Parses the full message definition, as sent in the connection header.
+    pub fn new(message_type: &str, message_definition: &str) -> Result<Self> {
+        let (package, name) = split_type(message_type)?;
+        let (source, sections) = split_definition(message_definition)?;
+        let msg = Msg::new(package, name, &source)?;
+        let mut dependencies = HashMap::new();
+        for (section_type, section_source) in sections {
+            let (package, name) = split_type(&section_type)?;
+            let dependency = Msg::new(package, name, &section_source)?;
+            dependencies.insert((package.into(), name.into()), dependency);
+        }
+        let dynamic_msg = Self {
+            msg,
+            dependencies,
+            definition: message_definition.into(),
+        };
+        dynamic_msg.check_dependencies(&dynamic_msg.msg, &mut BTreeSet::new())?;
+        Ok(dynamic_msg)
+    }
+
+    /// Parses the message description received from a publisher's connection header.
+    pub fn from_headers(headers: &HashMap<String, String>) -> Result<Self> {
+        let message_type = headers.get("type").ok_or_else(|| {
+            ErrorKind::BadMessageDefinition("Connection header is missing the type".into())
+        })?;
+        let definition = headers.get("message_definition").ok_or_else(|| {
+            ErrorKind::BadMessageDefinition("Connection header is missing the definition".into())
+        })?;
+        Self::new(message_type, definition)
+    }
+
+    /// Loads the message definition from the message search paths.
+    pub fn find(message_type: &str) -> Result<Self> {
+        Self::new(message_type, &find_definition(message_type)?)
+    }
+
+    #[inline]
+    pub fn msg(&self) -> &Msg {
+        &self.msg
+    }
+
+    #[inline]
+    pub fn definition(&self) -> &str {
+        &self.definition
+    }
+
+    #[inline]
+    pub fn msg_type(&self) -> String {
+        self.msg.get_type()
+    }
+
+    /// Returns the message type that a field of the given message refers to.
+    pub fn dependency(&self, parent: &Msg, datatype: &DataType) -> Result<&Msg> {
+        let path = datatype.struct_path(&parent.package).ok_or_else(|| {
+            ErrorKind::BadMessageDefinition(format!(
+                "Field type '{}' is not a message",
+                datatype.builtin_name()
+            ))
+        })?;
+        self.dependencies
+            .get(&path)
+            .ok_or_else(|| ErrorKind::MissingDependency(format!("{}/{}", path.0, path.1)).into())
+    }
+
+    pub fn md5sum(&self) -> Result<String> {
+        self.calculate_md5(&self.msg, &mut HashMap::new())
+    }
+
+    pub fn description(&self) -> Result<RawMessageDescription> {
+        Ok(RawMessageDescription {
+            msg_definition: self.definition.clone(),
+            md5sum: self.md5sum()?,
+            msg_type: self.msg_type(),
+        })
+    }
+
+    pub fn decode<R: io::Read>(&self, r: R) -> Result<Value> {
+        let mut input = Input {
+            inner: r,
+            remaining: None,
+        };
+        self.decode_message(&self.msg, &mut input)
+            .map(Value::Message)
+    }
+
+    pub fn encode<W: io::Write>(&self, value: &Value, mut w: W) -> Result<()> {
+        self.encode_message(&self.msg, value, "", &mut w)
+    }
+
+    /// Decodes a whole message, failing if any bytes are left over.
+    ///
+    /// Since the size of the data is known, vector lengths that cannot fit into it
+    /// are rejected before decoding any items.
+    pub fn decode_raw(&self, message: &RawMessage) -> Result<Value> {
+        let mut input = Input {
+            inner: &message.0[..],
+            remaining: Some(message.0.len()),
+        };
+        let value = self.decode_message(&self.msg, &mut input)?;
+        match input.remaining {
+            Some(0) | None => Ok(Value::Message(value)),
+            Some(left) => bail!(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} bytes left over after decoding the message", left),
+            )),
+        }
+    }
+
+    pub fn encode_raw(&self, value: &Value) -> Result<RawMessage> {
+        let mut data = vec![];
+        self.encode(value, &mut data)?;
+        Ok(RawMessage(data))
+    }
+
+    /// Creates a message with every field set to zero, empty or its default.
+    pub fn default_value(&self) -> Value {
+        self.default_message(&self.msg)
+    }
+
+    fn check_dependencies<'a>(
+        &'a self,
+        msg: &'a Msg,
+        parents: &mut BTreeSet<(&'a str, &'a str)>,
+    ) -> Result<()> {
+        if !parents.insert((&msg.package, &msg.name)) {
+            bail!(ErrorKind::BadMessageDefinition(format!(
+                "Message '{}' depends on itself",
+                msg.get_type()
+            )));
+        }
+        for field in &msg.fields {
+            if field.datatype.is_builtin() {
+                continue;
+            }
+            let dependency = self.dependency(msg, &field.datatype)?;
+            self.check_dependencies(dependency, parents)?;
+        }
+        parents.remove(&(&msg.package[..], &msg.name[..]));
+        Ok(())
+    }
+
+    fn calculate_md5(
+        &self,
+        msg: &Msg,
+        hashes: &mut HashMap<(String, String), String>,
+    ) -> Result<String> {
+        use md5::{Digest, Md5};
+
+        for field in &msg.fields {
+            if let Some(path) = field.datatype.struct_path(&msg.package) {
+                if !hashes.contains_key(&path) {
+                    let dependency = self.dependency(msg, &field.datatype)?;
+                    let hash = self.calculate_md5(dependency, hashes)?;
+                    hashes.insert(path, hash);
+                }
+            }
+        }
+        let representation = msg
+            .get_md5_representation(hashes)
+            .ok_or_else(|| ErrorKind::MissingDependency(msg.get_type()))?;
+        let mut hasher = Md5::new();
+        hasher.input(&representation);
+        Ok(hex::encode(hasher.result().as_slice()))
+    }
+
+    fn default_message(&self, msg: &Msg) -> Value {
+        Value::Message(
+            msg.fields
+                .iter()
+                .filter(|field| !field.is_constant())
+                .map(|field| {
+                    let value = match field.case {
+                        FieldCase::Vector => Value::Array(vec![]),
+                        FieldCase::Array(count) => Value::Array(
+                            (0..count)
+                                .map(|_| self.default_field(msg, &field.datatype))
+                                .collect(),
+                        ),
+                        _ => self.default_field(msg, &field.datatype),
+                    };
+                    (field.name.clone(), value)
+                })
+                .collect(),
+        )
+    }
+
+    fn default_field(&self, parent: &Msg, datatype: &DataType) -> Value {
+        match *datatype {
+            DataType::Bool => Value::Bool(false),
+            DataType::I8(_) => Value::I8(0),
+            DataType::I16 => Value::I16(0),
+            DataType::I32 => Value::I32(0),
+            DataType::I64 => Value::I64(0),
+            DataType::U8(_) => Value::U8(0),
+            DataType::U16 => Value::U16(0),
+            DataType::U32 => Value::U32(0),
+            DataType::U64 => Value::U64(0),
+            DataType::F32 => Value::F32(0.0),
+            DataType::F64 => Value::F64(0.0),
+            DataType::String => Value::String(String::new()),
+            DataType::Time => Value::Time(Time::new()),
+            DataType::Duration => Value::Duration(Duration::new()),
+            DataType::LocalStruct(..) | DataType::RemoteStruct(..) => {
+                match self.dependency(parent, datatype) {
+                    Ok(msg) => self.default_message(msg),
+                    // Dependencies are validated at construction
+                    Err(_) => Value::Message(vec![]),
+                }
+            }
+        }
+    }
+
+    fn decode_message<R: io::Read>(&self, msg: &Msg, r: &mut Input<R>) -> Result<MessageValue> {
+        let mut fields = vec![];
+        for field in &msg.fields {
+            let value = match field.case {
+                FieldCase::Const(..) => continue,
+                FieldCase::Unit => self.decode_field(msg, &field.datatype, r)?,
+                FieldCase::Vector => {
+                    let count = u32::decode(&mut *r)? as usize;
+                    self.check_count(msg, &field.datatype, count, r.remaining)?;
+                    self.decode_array(msg, &field.datatype, count, r)?
+                }
+                FieldCase::Array(count) => self.decode_array(msg, &field.datatype, count, r)?,
+            };
+            fields.push((field.name.clone(), value));
+        }
+        Ok(fields)
+    }
+
+    fn decode_array<R: io::Read>(
+        &self,
+        parent: &Msg,
+        datatype: &DataType,
+        count: usize,
+        r: &mut Input<R>,
+    ) -> Result<Value> {
+        let mut items = vec![];
+        for _ in 0..count {
+            items.push(self.decode_field(parent, datatype, r)?);
+        }
+        Ok(Value::Array(items))
+    }
+
+    /// Rejects vector lengths declared by the data that cannot be legitimate.
+    ///
+    /// Items that take up no bytes cannot be bounded by the size of the data, so their
+    /// number is limited instead.
+    fn check_count(
+        &self,
+        parent: &Msg,
+        datatype: &DataType,
+        count: usize,
+        remaining: Option<usize>,
+    ) -> Result<()> {
+        let item_size = self.min_size(parent, datatype)?;
+        if item_size == 0 {
+            if count > MAX_EMPTY_ITEMS {
+                bail!(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{} items of an empty type exceed the limit of {}",
+                        count, MAX_EMPTY_ITEMS
+                    ),
+                ));
+            }
+        } else if let Some(remaining) = remaining {
+            if count.saturating_mul(item_size) > remaining {
+                bail!(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "{} items need at least {} bytes each, but only {} bytes are left",
+                        count, item_size, remaining
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Smallest number of bytes that a value of the given type takes up on the wire.
+    fn min_size(&self, parent: &Msg, datatype: &DataType) -> Result<usize> {
+        Ok(match *datatype {
+            DataType::Bool | DataType::I8(_) | DataType::U8(_) => 1,
+            DataType::I16 | DataType::U16 => 2,
+            DataType::I32 | DataType::U32 | DataType::F32 | DataType::String => 4,
+            DataType::I64 | DataType::U64 | DataType::F64 | DataType::Time | DataType::Duration => {
+                8
+            }
+            DataType::LocalStruct(..) | DataType::RemoteStruct(..) => {
+                let msg = self.dependency(parent, datatype)?;
+                let mut size = 0usize;
+                for field in &msg.fields {
+                    let field_size = match field.case {
+                        FieldCase::Const(..) => 0,
+                        FieldCase::Unit => self.min_size(msg, &field.datatype)?,
+                        FieldCase::Vector => 4,
+                        FieldCase::Array(count) => {
+                            count.saturating_mul(self.min_size(msg, &field.datatype)?)
+                        }
+                    };
+                    size = size.saturating_add(field_size);
+                }
+                size
+            }
+        })
+    }
+
+    fn decode_field<R: io::Read>(
+        &self,
+        parent: &Msg,
+        datatype: &DataType,
+        r: &mut Input<R>,
+    ) -> Result<Value> {
+        Ok(match *datatype {
+            DataType::Bool => Value::Bool(RosMsg::decode(&mut *r)?),
+            DataType::I8(_) => Value::I8(RosMsg::decode(&mut *r)?),
+            DataType::I16 => Value::I16(RosMsg::decode(&mut *r)?),
+            DataType::I32 => Value::I32(RosMsg::decode(&mut *r)?),
+            DataType::I64 => Value::I64(RosMsg::decode(&mut *r)?),
+            DataType::U8(_) => Value::U8(RosMsg::decode(&mut *r)?),
+            DataType::U16 => Value::U16(RosMsg::decode(&mut *r)?),
+            DataType::U32 => Value::U32(RosMsg::decode(&mut *r)?),
+            DataType::U64 => Value::U64(RosMsg::decode(&mut *r)?),
+            DataType::F32 => Value::F32(RosMsg::decode(&mut *r)?),
+            DataType::F64 => Value::F64(RosMsg::decode(&mut *r)?),
+            DataType::String => Value::String(RosMsg::decode(&mut *r)?),
+            DataType::Time => Value::Time(RosMsg::decode(&mut *r)?),
+            DataType::Duration => Value::Duration(RosMsg::decode(&mut *r)?),
+            DataType::LocalStruct(..) | DataType::RemoteStruct(..) => {
+                let msg = self.dependency(parent, datatype)?;
+                Value::Message(self.decode_message(msg, r)?)
+            }
+        })
+    }
+
+    fn encode_message<W: io::Write>(
+        &self,
+        msg: &Msg,
+        value: &Value,
+        path: &str,
+        w: &mut W,
+    ) -> Result<()> {
+        let fields = match *value {
+            Value::Message(ref fields) => fields,
+            _ => bail!(ErrorKind::BadValue(
+                path.into(),
+                format!("expected '{}' message", msg.get_type())
+            )),
+        };
+        for field in &msg.fields {
+            let field_path = join_path(path, &field.name);
+            let value = match field.case {
+                FieldCase::Const(..) => continue,
+                _ => fields
+                    .iter()
+                    .find(|v| v.0 == field.name)
+                    .map(|v| &v.1)
+                    .ok_or_else(|| ErrorKind::BadValue(field_path.clone(), "missing".into()))?,
+            };
+            match field.case {
+                FieldCase::Unit => {
+                    self.encode_field(msg, &field.datatype, value, &field_path, w)?;
+                }
+                FieldCase::Vector | FieldCase::Array(..) => {
+                    let items = match *value {
+                        Value::Array(ref items) => items,
+                        _ => bail!(ErrorKind::BadValue(field_path, "expected array".into())),
+                    };
+                    if let FieldCase::Array(count) = field.case {
+                        if items.len() != count {
+                            bail!(ErrorKind::BadValue(
+                                field_path,
+                                format!("expected {} items, got {}", count, items.len())
+                            ));
+                        }
+                    } else {
+                        (items.len() as u32).encode(&mut *w)?;
+                    }
+                    for (index, item) in items.iter().enumerate() {
+                        let item_path = join_path(&field_path, &index.to_string());
+                        self.encode_field(msg, &field.datatype, item, &item_path, w)?;
+                    }
+                }
+                FieldCase::Const(..) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn encode_field<W: io::Write>(
+        &self,
+        parent: &Msg,
+        datatype: &DataType,
+        value: &Value,
+        path: &str,
+        w: &mut W,
+    ) -> Result<()> {
+        match (datatype, value) {
+            (DataType::Bool, Value::Bool(v)) => v.encode(w)?,
+            (DataType::I8(_), Value::I8(v)) => v.encode(w)?,
+            (DataType::I16, Value::I16(v)) => v.encode(w)?,
+            (DataType::I32, Value::I32(v)) => v.encode(w)?,
+            (DataType::I64, Value::I64(v)) => v.encode(w)?,
+            (DataType::U8(_), Value::U8(v)) => v.encode(w)?,
+            (DataType::U16, Value::U16(v)) => v.encode(w)?,
+            (DataType::U32, Value::U32(v)) => v.encode(w)?,
+            (DataType::U64, Value::U64(v)) => v.encode(w)?,
+            (DataType::F32, Value::F32(v)) => v.encode(w)?,
+            (DataType::F64, Value::F64(v)) => v.encode(w)?,
+            (DataType::String, Value::String(v)) => v.encode(w)?,
+            (DataType::Time, Value::Time(v)) => v.encode(w)?,
+            (DataType::Duration, Value::Duration(v)) => v.encode(w)?,
+            (DataType::LocalStruct(..), _) | (DataType::RemoteStruct(..), _) => {
+                let msg = self.dependency(parent, datatype)?;
+                self.encode_message(msg, value, path, w)?;
+            }
+            _ => bail!(ErrorKind::BadValue(
+                path.into(),
+                format!("expected {}", datatype.builtin_name())
+            )),
+        }
+        Ok(())
+    }
+}
+
+fn join_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.into()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn split_type(message_type: &str) -> Result<(&str, &str)> {
+    let mut parts = message_type.splitn(2, '/');
+    match (parts.next(), parts.next()) {
+        (Some(package), Some(name)) if !package.is_empty() && !name.is_empty() => {
+            Ok((package, name))
+        }
+        _ => bail!(ErrorKind::BadMessageDefinition(format!(
+            "Bad message type '{}'",
+            message_type
+        ))),
+    }
+}
+
+type Section = (String, String);
+
+/// Splits a full message definition into the main source and the sources of dependencies.
+fn split_definition(definition: &str) -> Result<(String, Vec<Section>)> {
+    let mut blocks = vec![String::new()];
+    for line in definition.lines() {
+        let trimmed = line.trim();
+        if !trimmed.is_empty() && trimmed.chars().all(|v| v == '=') {
+            blocks.push(String::new());
+            continue;
+        }
+        if let Some(block) = blocks.last_mut() {
+            block.push_str(line);
+            block.push('\n');
+        }
+    }
+    let mut blocks = blocks.into_iter();
+    let source = blocks.next().unwrap_or_default();
+    let sections = blocks
+        .map(|block| {
+            let block = block.trim_start();
+            let mut lines = block.splitn(2, '\n');
+            let header = lines.next().unwrap_or("");
+            if !header.starts_with("MSG:") {
+                bail!(ErrorKind::BadMessageDefinition(format!(
+                    "Expected 'MSG:' line, got '{}'",
+                    header
+                )));
+            }
+            Ok((
+                header["MSG:".len()..].trim().into(),
+                lines.next().unwrap_or("").into(),
+            ))
+        })
+        .collect::<Result<_>>()
+        .chain_err(|| ErrorKind::BadMessageDefinition("Failed to split sections".into()))?;
+    Ok((source, sections))
+}
+
+/// Largest number of items in a vector whose type takes up no bytes on the wire.
+const MAX_EMPTY_ITEMS: usize = 1 << 16;
+
+/// Reader that keeps track of the bytes left, when the size of the data is known.
+struct Input<R> {
+    inner: R,
+    remaining: Option<usize>,
+}
+
+impl<R: io::Read> io::Read for Input<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        if let Some(ref mut remaining) = self.remaining {
+            *remaining = remaining.saturating_sub(count);
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::rosgraph_msgs::Log;
+    use crate::Message;
+
+    static FAILED_TO_PARSE: &str = "Failed to parse message definition";
+    static FAILED_TO_DECODE: &str = "Failed to decode message";
+    static FAILED_TO_ENCODE: &str = "Failed to encode message";
+
+    #[test]
+    fn md5sum_matches_generated_messages() {
+        let msg = DynamicMsg::new(&Log::msg_type(), &Log::msg_definition()).expect(FAILED_TO_PARSE);
+        assert_eq!(Log::md5sum(), msg.md5sum().unwrap());
+        assert_eq!(
+            RawMessageDescription::from_message::<Log>(),
+            msg.description().unwrap()
+        );
+    }
+
+    #[test]
+    fn decodes_and_encodes_generated_messages() {
+        let mut log = Log::default();
+        log.header.seq = 7;
+        log.header.stamp = Time { sec: 5, nsec: 6 };
+        log.level = Log::WARN;
+        log.name = "/node".into();
+        log.msg = "message".into();
+        log.line = 42;
+        log.topics = vec!["/a".into(), "/b".into()];
+        let data = log.encode_vec().unwrap();
+
+        let msg = DynamicMsg::new(&Log::msg_type(), &Log::msg_definition()).expect(FAILED_TO_PARSE);
+        let value = msg.decode(&data[4..]).expect(FAILED_TO_DECODE);
+        assert_eq!(Some(&Value::U32(7)), value.get("header.seq"));
+        assert_eq!(
+            Some(&Value::Time(Time { sec: 5, nsec: 6 })),
+            value.get("header.stamp")
+        );
+        assert_eq!(Some(&Value::I8(Log::WARN)), value.get("level"));
+        assert_eq!(Some(&Value::String("/b".into())), value.get("topics.1"));
+
+        let encoded = msg.encode_raw(&value).expect(FAILED_TO_ENCODE);
+        assert_eq!(&data[4..], &encoded.0[..]);
+    }
+
+    #[test]
+    fn decode_rejects_impossible_lengths_and_leftovers() {
+        fn io_error_kind(err: error::Error) -> io::ErrorKind {
+            match err.kind() {
+                ErrorKind::Io(err) => err.kind(),
+                kind => panic!("Unexpected error: {:?}", kind),
+            }
+        }
+
+        let msg = DynamicMsg::new("foo/Bar", "int32[] a").expect(FAILED_TO_PARSE);
+        let message = RawMessage(vec![0xff, 0xff, 0xff, 0xff, 1, 0, 0, 0]);
+        let err = msg.decode_raw(&message).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, io_error_kind(err));
+        let message = RawMessage(vec![1, 0, 0, 0, 1, 0, 0, 0, 9]);
+        let err = msg.decode_raw(&message).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, io_error_kind(err));
+
+        let msg =
+            DynamicMsg::new("foo/Bar", "Empty[] a\n====\nMSG: foo/Empty\n").expect(FAILED_TO_PARSE);
+        let value = msg
+            .decode_raw(&RawMessage(vec![3, 0, 0, 0]))
+            .expect(FAILED_TO_DECODE);
+        assert_eq!(Some(&Value::Message(vec![])), value.get("a.2"));
+        let message = RawMessage(vec![0xff, 0xff, 0xff, 0xff]);
+        let err = msg.decode_raw(&message).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, io_error_kind(err));
+        let err = msg.decode(&message.0[..]).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, io_error_kind(err));
+    }
+
+    #[test]
+    fn encode_reports_bad_fields() {
+        let msg = DynamicMsg::new("foo/Bar", "int32 a\nstring[2] b").expect(FAILED_TO_PARSE);
+        let value = Value::Message(vec![
+            ("a".into(), Value::I32(1)),
+            ("b".into(), Value::Array(vec![Value::String("x".into())])),
+        ]);
+        match msg.encode_raw(&value).unwrap_err().kind() {
+            ErrorKind::BadValue(field, _) => assert_eq!("b", field),
+            kind => panic!("Unexpected error: {:?}", kind),
+        }
+        let value = Value::Message(vec![("a".into(), Value::I64(1))]);
+        match msg.encode_raw(&value).unwrap_err().kind() {
+            ErrorKind::BadValue(field, _) => assert_eq!("a", field),
+            kind => panic!("Unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn default_value_fills_all_fields() {
+        let msg = DynamicMsg::new(
+            "foo/Bar",
+            "Baz[] many\nBaz[2] two\nint8 X=3\n\
+             ================================================================================\n\
+             MSG: foo/Baz\nbool flag\n",
+        )
+        .expect(FAILED_TO_PARSE);
+        let baz = Value::Message(vec![("flag".into(), Value::Bool(false))]);
+        assert_eq!(
+            Value::Message(vec![
+                ("many".into(), Value::Array(vec![])),
+                ("two".into(), Value::Array(vec![baz.clone(), baz])),
+            ]),
+            msg.default_value()
+        );
+    }
+
+    #[test]
+    fn rejects_missing_and_circular_dependencies() {
+        match DynamicMsg::new("foo/Bar", "Baz baz").unwrap_err().kind() {
+            ErrorKind::MissingDependency(name) => assert_eq!("foo/Baz", name),
+            kind => panic!("Unexpected error: {:?}", kind),
+        }
+        assert!(DynamicMsg::new(
+            "foo/Bar",
+            "Baz baz\n====\nMSG: foo/Baz\nBar bar\n====\nMSG: foo/Bar\nBaz baz\n",
+        )
+        .is_err());
+    }
+}
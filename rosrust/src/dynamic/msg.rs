@@ -0,0 +1,363 @@
+use super::error::{ErrorKind, Result, ResultExt};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Msg {
+    pub package: String,
+    pub name: String,
+    pub fields: Vec<FieldInfo>,
+    pub source: String,
+}
+
+impl Msg {
+    pub fn new(package: &str, name: &str, source: &str) -> Result<Msg> {
+        let fields = match_lines(source)
+            .chain_err(|| ErrorKind::BadMessageDefinition(format!("{}/{}", package, name)))?;
+        Ok(Msg {
+            package: package.to_owned(),
+            name: name.to_owned(),
+            fields,
+            source: source.trim().into(),
+        })
+    }
+
+    pub fn get_type(&self) -> String {
+        format!("{}/{}", self.package, self.name)
+    }
+
+    pub fn dependencies(&self) -> Vec<(String, String)> {
+        self.fields
+            .iter()
+            .filter_map(|field| field.datatype.struct_path(&self.package))
+            .collect()
+    }
+
+    pub fn get_md5_representation(
+        &self,
+        hashes: &HashMap<(String, String), String>,
+    ) -> Option<String> {
+        let constants = self
+            .fields
+            .iter()
+            .filter(|v| v.is_constant())
+            .map(|v| v.md5_string(&self.package, hashes))
+            .collect::<Option<Vec<String>>>()?;
+        let fields = self
+            .fields
+            .iter()
+            .filter(|v| !v.is_constant())
+            .map(|v| v.md5_string(&self.package, hashes))
+            .collect::<Option<Vec<String>>>()?;
+        let representation = constants
+            .into_iter()
+            .chain(fields)
+            .collect::<Vec<_>>()
+            .join("\n");
+        Some(representation)
+    }
+}
+
+fn match_line(data: &str) -> Option<Result<FieldInfo>> {
+    let trimmed = data.trim();
+    if trimmed.starts_with("string") && trimmed.contains('=') {
+        let mut parts = trimmed["string".len()..].splitn(2, '=');
+        let name = parts.next().unwrap_or("");
+        let value = parts.next().unwrap_or("");
+        if name.starts_with(char::is_whitespace) && is_field_name(name.trim()) {
+            return Some(FieldInfo::new(
+                "string",
+                name.trim(),
+                FieldCase::Const(value.trim().into()),
+            ));
+        }
+    }
+    let data = strip_useless(data);
+    if data.is_empty() {
+        return None;
+    }
+    let mut parts = data.splitn(2, char::is_whitespace);
+    let field_type = parts.next().unwrap_or("");
+    let rest = parts.next().unwrap_or("").trim();
+    if rest.contains('=') {
+        let mut parts = rest.splitn(2, '=');
+        let name = parts.next().unwrap_or("").trim();
+        let value = parts.next().unwrap_or("").trim();
+        if is_field_name(name) && !field_type.contains('[') {
+            return Some(FieldInfo::new(
+                field_type,
+                name,
+                FieldCase::Const(value.into()),
+            ));
+        }
+    } else if is_field_name(rest) {
+        if let Some(bracket) = field_type.find('[') {
+            let count = field_type[bracket..]
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .trim();
+            let field_type = &field_type[..bracket];
+            if count.is_empty() {
+                return Some(FieldInfo::new(field_type, rest, FieldCase::Vector));
+            }
+            if let Ok(count) = count.parse() {
+                return Some(FieldInfo::new(field_type, rest, FieldCase::Array(count)));
+            }
+        } else {
+            return Some(FieldInfo::new(field_type, rest, FieldCase::Unit));
+        }
+    }
+    Some(Err(format!("Unsupported content of line: {}", data).into()))
+}
+
+fn is_field_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() => {}
+        _ => return false,
+    }
+    chars.all(|v| v.is_ascii_alphanumeric() || v == '_')
+}
+
+#[inline]
+fn strip_useless(data: &str) -> &str {
+    data.split('#').next().unwrap_or("").trim()
+}
+
+#[inline]
+fn match_lines(data: &str) -> Result<Vec<FieldInfo>> {
+    data.split('\n')
+        .filter_map(match_line)
+        .collect::<Result<_>>()
+        .chain_err(|| "Failed to parse line in data string")
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldCase {
+    Unit,
+    Vector,
+    Array(usize),
+    Const(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldInfo {
+    pub datatype: DataType,
+    pub name: String,
+    pub case: FieldCase,
+}
+
+impl FieldInfo {
+    pub fn is_constant(&self) -> bool {
+        matches!(self.case, FieldCase::Const(..))
+    }
+
+    fn md5_string(
+        &self,
+        package: &str,
+        hashes: &HashMap<(String, String), String>,
+    ) -> Option<String> {
+        let datatype = self.datatype.md5_string(package, hashes)?;
+        Some(match (self.datatype.is_builtin(), &self.case) {
+            (_, FieldCase::Const(v)) => format!("{} {}={}", datatype, self.name, v),
+            (false, _) | (_, FieldCase::Unit) => format!("{} {}", datatype, self.name),
+            (true, FieldCase::Vector) => format!("{}[] {}", datatype, self.name),
+            (true, FieldCase::Array(l)) => format!("{}[{}] {}", datatype, l, self.name),
+        })
+    }
+
+    fn new(datatype: &str, name: &str, case: FieldCase) -> Result<FieldInfo> {
+        Ok(FieldInfo {
+            datatype: parse_datatype(datatype)
+                .ok_or_else(|| format!("Unsupported datatype: {}", datatype))?,
+            name: name.to_owned(),
+            case,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DataType {
+    Bool,
+    I8(bool),
+    I16,
+    I32,
+    I64,
+    U8(bool),
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    String,
+    Time,
+    Duration,
+    LocalStruct(String),
+    RemoteStruct(String, String),
+}
+
+impl DataType {
+    pub fn is_builtin(&self) -> bool {
+        !matches!(
+            *self,
+            DataType::LocalStruct(_) | DataType::RemoteStruct(_, _)
+        )
+    }
+
+    /// Package and name of the message type, resolved relative to the given package.
+    pub fn struct_path(&self, package: &str) -> Option<(String, String)> {
+        match *self {
+            DataType::LocalStruct(ref name) => Some((package.to_owned(), name.clone())),
+            DataType::RemoteStruct(ref pkg, ref name) => Some((pkg.clone(), name.clone())),
+            _ => None,
+        }
+    }
+
+    fn md5_string(
+        &self,
+        package: &str,
+        hashes: &HashMap<(String, String), String>,
+    ) -> Option<String> {
+        if let Some(path) = self.struct_path(package) {
+            return hashes.get(&path).cloned();
+        }
+        Some(self.builtin_name().into())
+    }
+
+    pub fn builtin_name(&self) -> &'static str {
+        match *self {
+            DataType::Bool => "bool",
+            DataType::I8(true) => "int8",
+            DataType::I8(false) => "byte",
+            DataType::I16 => "int16",
+            DataType::I32 => "int32",
+            DataType::I64 => "int64",
+            DataType::U8(true) => "uint8",
+            DataType::U8(false) => "char",
+            DataType::U16 => "uint16",
+            DataType::U32 => "uint32",
+            DataType::U64 => "uint64",
+            DataType::F32 => "float32",
+            DataType::F64 => "float64",
+            DataType::String => "string",
+            DataType::Time => "time",
+            DataType::Duration => "duration",
+            DataType::LocalStruct(_) | DataType::RemoteStruct(_, _) => "",
+        }
+    }
+}
+
+fn parse_datatype(datatype: &str) -> Option<DataType> {
+    match datatype {
+        "bool" => Some(DataType::Bool),
+        "int8" => Some(DataType::I8(true)),
+        "byte" => Some(DataType::I8(false)),
+        "int16" => Some(DataType::I16),
+        "int32" => Some(DataType::I32),
+        "int64" => Some(DataType::I64),
+        "uint8" => Some(DataType::U8(true)),
+        "char" => Some(DataType::U8(false)),
+        "uint16" => Some(DataType::U16),
+        "uint32" => Some(DataType::U32),
+        "uint64" => Some(DataType::U64),
+        "float32" => Some(DataType::F32),
+        "float64" => Some(DataType::F64),
+        "string" => Some(DataType::String),
+        "time" => Some(DataType::Time),
+        "duration" => Some(DataType::Duration),
+        "Header" => Some(DataType::RemoteStruct("std_msgs".into(), "Header".into())),
+        _ => {
+            let parts = datatype.split('/').collect::<Vec<_>>();
+            if parts.iter().any(|v| v.is_empty()) {
+                return None;
+            }
+            match parts.len() {
+                2 => Some(DataType::RemoteStruct(
+                    parts[0].to_owned(),
+                    parts[1].to_owned(),
+                )),
+                1 => Some(DataType::LocalStruct(parts[0].to_owned())),
+                _ => None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static FAILED_TO_PARSE: &str = "Failed to parse message";
+
+    #[test]
+    fn parses_all_kinds_of_lines() {
+        let msg = Msg::new(
+            "foo",
+            "Bar",
+            "# comment\n\
+             Header header # trailing comment\n\
+             uint8[] data\n\
+             float64[9] covariance\n\
+             Baz baz\n\
+             other_pkg/Qux[] quxes\n\
+             int32 X = -3 # constant\n\
+             string NAME = has # in it\n",
+        )
+        .expect(FAILED_TO_PARSE);
+        assert_eq!(
+            vec![
+                FieldInfo {
+                    datatype: DataType::RemoteStruct("std_msgs".into(), "Header".into()),
+                    name: "header".into(),
+                    case: FieldCase::Unit,
+                },
+                FieldInfo {
+                    datatype: DataType::U8(true),
+                    name: "data".into(),
+                    case: FieldCase::Vector,
+                },
+                FieldInfo {
+                    datatype: DataType::F64,
+                    name: "covariance".into(),
+                    case: FieldCase::Array(9),
+                },
+                FieldInfo {
+                    datatype: DataType::LocalStruct("Baz".into()),
+                    name: "baz".into(),
+                    case: FieldCase::Unit,
+                },
+                FieldInfo {
+                    datatype: DataType::RemoteStruct("other_pkg".into(), "Qux".into()),
+                    name: "quxes".into(),
+                    case: FieldCase::Vector,
+                },
+                FieldInfo {
+                    datatype: DataType::I32,
+                    name: "X".into(),
+                    case: FieldCase::Const("-3".into()),
+                },
+                FieldInfo {
+                    datatype: DataType::String,
+                    name: "NAME".into(),
+                    case: FieldCase::Const("has # in it".into()),
+                },
+            ],
+            msg.fields
+        );
+        assert_eq!(
+            vec![
+                ("std_msgs".into(), "Header".into()),
+                ("foo".into(), "Baz".into()),
+                ("other_pkg".into(), "Qux".into()),
+            ],
+            msg.dependencies()
+        );
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        assert!(Msg::new("foo", "Bar", "int32").is_err());
+        assert!(Msg::new("foo", "Bar", "int32 1abc").is_err());
+        assert!(Msg::new("foo", "Bar", "int32[x] abc").is_err());
+        assert!(Msg::new("foo", "Bar", "a//b abc").is_err());
+    }
+}
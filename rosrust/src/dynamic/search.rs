@@ -0,0 +1,112 @@
+use super::error::{ErrorKind, Result, ResultExt};
+use super::msg::Msg;
+use super::{split_definition, split_type};
+use crate::msg::{rosgraph_msgs, std_msgs};
+use crate::Message;
+use std::collections::{HashSet, VecDeque};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Builds the full definition of a message type from the message search paths.
+///
+/// Messages are searched for in `CMAKE_PREFIX_PATH`, `ROS_PACKAGE_PATH` and `ROSRUST_MSG_PATH`,
+/// in the same manner as during message generation.
+pub fn find_definition(message_type: &str) -> Result<String> {
+    let (package, name) = split_type(message_type)?;
+    let source = find_source(package, name, "msg")?;
+    generate_definition(package, &source)
+}
+
+/// Builds the full definitions of a service type's request and response messages.
+pub fn find_service_definitions(service_type: &str) -> Result<(String, String)> {
+    let (package, name) = split_type(service_type)?;
+    let source = find_source(package, name, "srv")?;
+    let mut request = String::new();
+    let mut response = String::new();
+    let mut target = &mut request;
+    for line in source.lines() {
+        if line.trim() == "---" {
+            target = &mut response;
+            continue;
+        }
+        target.push_str(line);
+        target.push('\n');
+    }
+    Ok((
+        generate_definition(package, &request)?,
+        generate_definition(package, &response)?,
+    ))
+}
+
+fn generate_definition(package: &str, source: &str) -> Result<String> {
+    let message = Msg::new(package, "", source)?;
+    let mut handled_messages = HashSet::<(String, String)>::new();
+    let mut result = message.source.clone();
+    let mut pending = message.dependencies().into_iter().collect::<VecDeque<_>>();
+    while let Some(value) = pending.pop_front() {
+        if handled_messages.contains(&value) {
+            continue;
+        }
+        handled_messages.insert(value.clone());
+        let message = Msg::new(&value.0, &value.1, &find_source(&value.0, &value.1, "msg")?)?;
+        result += "\n\n========================================";
+        result += "========================================";
+        result += &format!("\nMSG: {}/{}\n", value.0, value.1);
+        pending.extend(message.dependencies());
+        result += &message.source;
+    }
+    result += "\n";
+    Ok(result)
+}
+
+fn find_source(package: &str, name: &str, extension: &str) -> Result<String> {
+    let file_name = format!("{}.{}", name, extension);
+    for path in search_paths() {
+        let candidate = path.join(package).join(extension).join(&file_name);
+        if candidate.is_file() {
+            return fs::read_to_string(&candidate)
+                .chain_err(|| format!("Failed to read file {}", candidate.display()));
+        }
+    }
+    if extension == "msg" {
+        if let Some(definition) = in_memory_definition(package, name) {
+            return Ok(split_definition(&definition)?.0);
+        }
+    }
+    bail!(ErrorKind::MessageNotFound(format!("{}/{}", package, name)))
+}
+
+fn in_memory_definition(package: &str, name: &str) -> Option<String> {
+    match (package, name) {
+        ("rosgraph_msgs", "Clock") => Some(rosgraph_msgs::Clock::msg_definition()),
+        ("rosgraph_msgs", "Log") => Some(rosgraph_msgs::Log::msg_definition()),
+        ("std_msgs", "Header") => Some(std_msgs::Header::msg_definition()),
+        _ => None,
+    }
+}
+
+fn search_paths() -> Vec<PathBuf> {
+    let cmake_paths = env::var("CMAKE_PREFIX_PATH").unwrap_or_default();
+    let package_paths = env::var("ROS_PACKAGE_PATH").unwrap_or_default();
+    let extra_paths = env::var("ROSRUST_MSG_PATH").unwrap_or_default();
+    let cmake_paths = cmake_paths
+        .split(':')
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from);
+    let package_paths = package_paths
+        .split(':')
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from);
+    let extra_paths = extra_paths
+        .split(':')
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from);
+    cmake_paths
+        .clone()
+        .map(|v| v.join("share"))
+        .chain(cmake_paths.map(|v| v.join("..").join("src")))
+        .chain(package_paths.flat_map(|v| vec![v.join(".."), v]))
+        .chain(extra_paths)
+        .collect()
+}
@@ -0,0 +1,84 @@
+use crate::time::{Duration, Time};
+
+/// Dynamically typed value of a message field.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    String(String),
+    Time(Time),
+    Duration(Duration),
+    Array(Vec<Value>),
+    Message(MessageValue),
+}
+
+/// Fields of a message, in the order of the message definition.
+pub type MessageValue = Vec<(String, Value)>;
+
+impl Value {
+    /// Looks up a field by a dot separated path, like `header.stamp`.
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        path.split('.')
+            .filter(|v| !v.is_empty())
+            .try_fold(self, |value, name| match *value {
+                Value::Message(ref fields) => fields
+                    .iter()
+                    .find(|field| field.0 == name)
+                    .map(|field| &field.1),
+                Value::Array(ref items) => items.get(name.parse::<usize>().ok()?),
+                _ => None,
+            })
+    }
+
+    /// Converts numeric values into a floating point number.
+    pub fn as_f64(&self) -> Option<f64> {
+        Some(match *self {
+            Value::I8(v) => f64::from(v),
+            Value::I16(v) => f64::from(v),
+            Value::I32(v) => f64::from(v),
+            Value::I64(v) => v as f64,
+            Value::U8(v) => f64::from(v),
+            Value::U16(v) => f64::from(v),
+            Value::U32(v) => f64::from(v),
+            Value::U64(v) => v as f64,
+            Value::F32(v) => f64::from(v),
+            Value::F64(v) => v,
+            Value::Time(ref v) => v.seconds(),
+            Value::Duration(ref v) => v.seconds(),
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_follows_paths() {
+        let value = Value::Message(vec![
+            (
+                "header".into(),
+                Value::Message(vec![("seq".into(), Value::U32(4))]),
+            ),
+            (
+                "items".into(),
+                Value::Array(vec![Value::I8(1), Value::I8(2)]),
+            ),
+        ]);
+        assert_eq!(Some(&Value::U32(4)), value.get("header.seq"));
+        assert_eq!(Some(&Value::I8(2)), value.get("items.1"));
+        assert_eq!(Some(&value), value.get(""));
+        assert_eq!(None, value.get("header.stamp"));
+        assert_eq!(None, value.get("items.2"));
+    }
+}
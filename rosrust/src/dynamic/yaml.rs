@@ -0,0 +1,315 @@
+use super::error::{ErrorKind, Result};
+use super::msg::{DataType, FieldCase, Msg};
+use super::value::Value;
+use super::{join_path, DynamicMsg};
+use crate::time::{Duration, Time};
+use std::convert::TryFrom;
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+/// Converts a value into YAML, matching the output of `rostopic echo`.
+pub fn to_yaml(value: &Value) -> Yaml {
+    match *value {
+        Value::Bool(v) => Yaml::Boolean(v),
+        Value::I8(v) => Yaml::Integer(v.into()),
+        Value::I16(v) => Yaml::Integer(v.into()),
+        Value::I32(v) => Yaml::Integer(v.into()),
+        Value::I64(v) => Yaml::Integer(v),
+        Value::U8(v) => Yaml::Integer(v.into()),
+        Value::U16(v) => Yaml::Integer(v.into()),
+        Value::U32(v) => Yaml::Integer(v.into()),
+        Value::U64(v) => match i64::try_from(v) {
+            Ok(v) => Yaml::Integer(v),
+            Err(_) => Yaml::Real(v.to_string()),
+        },
+        Value::F32(v) => float_to_yaml(v.into()),
+        Value::F64(v) => float_to_yaml(v),
+        Value::String(ref v) => Yaml::String(v.clone()),
+        Value::Time(ref v) => time_to_yaml(v.sec.into(), v.nsec.into()),
+        Value::Duration(ref v) => time_to_yaml(v.sec.into(), v.nsec.into()),
+        Value::Array(ref items) => Yaml::Array(items.iter().map(to_yaml).collect()),
+        Value::Message(ref fields) => Yaml::Hash(
+            fields
+                .iter()
+                .map(|(name, value)| (Yaml::String(name.clone()), to_yaml(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Converts a value into a YAML document, without the leading document separator.
+pub fn to_yaml_string(value: &Value) -> String {
+    let mut output = String::new();
+    {
+        let mut emitter = YamlEmitter::new(&mut output);
+        // Writing into a string can not fail
+        let _ = emitter.dump(&to_yaml(value));
+    }
+    output.trim_start_matches("---").trim_start().into()
+}
+
+fn float_to_yaml(v: f64) -> Yaml {
+    Yaml::Real(if v.is_nan() {
+        ".nan".into()
+    } else if v.is_infinite() {
+        if v > 0.0 { ".inf" } else { "-.inf" }.into()
+    } else {
+        format!("{:?}", v)
+    })
+}
+
+fn time_to_yaml(sec: i64, nsec: i64) -> Yaml {
+    let mut hash = Hash::new();
+    hash.insert(Yaml::String("secs".into()), Yaml::Integer(sec));
+    hash.insert(Yaml::String("nsecs".into()), Yaml::Integer(nsec));
+    Yaml::Hash(hash)
+}
+
+impl DynamicMsg {
+    /// Parses the message from YAML arguments, in the style of `rostopic pub`.
+    ///
+    /// A single mapping sets fields by name, while a sequence of values or multiple
+    /// arguments set fields in order. Omitted fields are set to their defaults.
+    pub fn from_yaml_args<S: AsRef<str>>(&self, args: &[S]) -> Result<Value> {
+        let mut documents = vec![];
+        for arg in args {
+            documents.extend(YamlLoader::load_from_str(arg.as_ref())?);
+        }
+        let yaml = match documents.len() {
+            0 => Yaml::Null,
+            1 => documents.remove(0),
+            _ => Yaml::Array(documents),
+        };
+        self.from_yaml(&yaml)
+    }
+
+    pub fn from_yaml(&self, yaml: &Yaml) -> Result<Value> {
+        self.message_from_yaml(self.msg(), yaml, "")
+    }
+
+    fn message_from_yaml(&self, msg: &Msg, yaml: &Yaml, path: &str) -> Result<Value> {
+        let fields = msg
+            .fields
+            .iter()
+            .filter(|field| !field.is_constant())
+            .collect::<Vec<_>>();
+        let mut values = match self.default_message(msg) {
+            Value::Message(values) => values,
+            _ => unreachable!(),
+        };
+        match *yaml {
+            Yaml::Null => {}
+            Yaml::Hash(ref hash) => {
+                for (key, item) in hash {
+                    let name = key.as_str().ok_or_else(|| {
+                        ErrorKind::BadValue(path.into(), "field names must be strings".into())
+                    })?;
+                    let index = fields
+                        .iter()
+                        .position(|field| field.name == name)
+                        .ok_or_else(|| {
+                            ErrorKind::BadValue(join_path(path, name), "no such field".into())
+                        })?;
+                    let field_path = join_path(path, name);
+                    values[index].1 = self.field_from_yaml(msg, index, item, &field_path)?;
+                }
+            }
+            Yaml::Array(ref items) => {
+                if items.len() > fields.len() {
+                    bail!(ErrorKind::BadValue(
+                        path.into(),
+                        format!("got {} values for {} fields", items.len(), fields.len())
+                    ));
+                }
+                for (index, item) in items.iter().enumerate() {
+                    let field_path = join_path(path, &fields[index].name);
+                    values[index].1 = self.field_from_yaml(msg, index, item, &field_path)?;
+                }
+            }
+            ref item if fields.len() == 1 => {
+                let field_path = join_path(path, &fields[0].name);
+                values[0].1 = self.field_from_yaml(msg, 0, item, &field_path)?;
+            }
+            _ => bail!(ErrorKind::BadValue(path.into(), "expected mapping".into())),
+        }
+        Ok(Value::Message(values))
+    }
+
+    fn field_from_yaml(&self, msg: &Msg, index: usize, yaml: &Yaml, path: &str) -> Result<Value> {
+        let field = msg
+            .fields
+            .iter()
+            .filter(|field| !field.is_constant())
+            .nth(index)
+            .ok_or_else(|| ErrorKind::BadValue(path.into(), "no such field".into()))?;
+        let count = match field.case {
+            FieldCase::Unit | FieldCase::Const(..) => {
+                return self.item_from_yaml(msg, &field.datatype, yaml, path);
+            }
+            FieldCase::Vector => None,
+            FieldCase::Array(count) => Some(count),
+        };
+        let items = match *yaml {
+            Yaml::Array(ref items) => items,
+            _ => bail!(ErrorKind::BadValue(path.into(), "expected sequence".into())),
+        };
+        if let Some(count) = count {
+            if count != items.len() {
+                bail!(ErrorKind::BadValue(
+                    path.into(),
+                    format!("expected {} items, got {}", count, items.len())
+                ));
+            }
+        }
+        items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                self.item_from_yaml(
+                    msg,
+                    &field.datatype,
+                    item,
+                    &join_path(path, &index.to_string()),
+                )
+            })
+            .collect::<Result<_>>()
+            .map(Value::Array)
+    }
+
+    fn item_from_yaml(
+        &self,
+        parent: &Msg,
+        datatype: &DataType,
+        yaml: &Yaml,
+        path: &str,
+    ) -> Result<Value> {
+        let bad_value =
+            || ErrorKind::BadValue(path.into(), format!("expected {}", datatype.builtin_name()));
+        Ok(match *datatype {
+            DataType::Bool => Value::Bool(match *yaml {
+                Yaml::Boolean(v) => v,
+                Yaml::Integer(v) => v != 0,
+                _ => bail!(bad_value()),
+            }),
+            DataType::I8(_) => Value::I8(integer_from_yaml(yaml).ok_or_else(bad_value)?),
+            DataType::I16 => Value::I16(integer_from_yaml(yaml).ok_or_else(bad_value)?),
+            DataType::I32 => Value::I32(integer_from_yaml(yaml).ok_or_else(bad_value)?),
+            DataType::I64 => Value::I64(integer_from_yaml(yaml).ok_or_else(bad_value)?),
+            DataType::U8(_) => Value::U8(integer_from_yaml(yaml).ok_or_else(bad_value)?),
+            DataType::U16 => Value::U16(integer_from_yaml(yaml).ok_or_else(bad_value)?),
+            DataType::U32 => Value::U32(integer_from_yaml(yaml).ok_or_else(bad_value)?),
+            DataType::U64 => Value::U64(match *yaml {
+                Yaml::Real(ref v) => v.parse().map_err(|_| bad_value())?,
+                _ => integer_from_yaml(yaml).ok_or_else(bad_value)?,
+            }),
+            DataType::F32 => Value::F32(float_from_yaml(yaml).ok_or_else(bad_value)? as f32),
+            DataType::F64 => Value::F64(float_from_yaml(yaml).ok_or_else(bad_value)?),
+            DataType::String => Value::String(match *yaml {
+                Yaml::String(ref v) | Yaml::Real(ref v) => v.clone(),
+                Yaml::Integer(v) => v.to_string(),
+                Yaml::Boolean(v) => v.to_string(),
+                _ => bail!(bad_value()),
+            }),
+            DataType::Time => {
+                let nanos = nanos_from_yaml(yaml).ok_or_else(bad_value)?;
+                if nanos < 0 {
+                    bail!(bad_value());
+                }
+                Value::Time(Time::from_nanos(nanos))
+            }
+            DataType::Duration => Value::Duration(Duration::from_nanos(
+                nanos_from_yaml(yaml).ok_or_else(bad_value)?,
+            )),
+            DataType::LocalStruct(..) | DataType::RemoteStruct(..) => {
+                let msg = self.dependency(parent, datatype)?;
+                self.message_from_yaml(msg, yaml, path)?
+            }
+        })
+    }
+}
+
+fn integer_from_yaml<T: TryFrom<i64>>(yaml: &Yaml) -> Option<T> {
+    match *yaml {
+        Yaml::Integer(v) => T::try_from(v).ok(),
+        _ => None,
+    }
+}
+
+fn float_from_yaml(yaml: &Yaml) -> Option<f64> {
+    match *yaml {
+        Yaml::Integer(v) => Some(v as f64),
+        _ => yaml.as_f64(),
+    }
+}
+
+fn nanos_from_yaml(yaml: &Yaml) -> Option<i64> {
+    match *yaml {
+        Yaml::Hash(ref hash) => {
+            let secs = hash.get(&Yaml::String("secs".into()));
+            let nsecs = hash.get(&Yaml::String("nsecs".into()));
+            let secs = secs.map_or(Some(0), |v| v.as_i64())?;
+            let nsecs = nsecs.map_or(Some(0), |v| v.as_i64())?;
+            Some(secs * 1_000_000_000 + nsecs)
+        }
+        _ => float_from_yaml(yaml).map(|v| (v * 1e9) as i64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static FAILED_TO_PARSE: &str = "Failed to parse message definition";
+
+    fn example() -> DynamicMsg {
+        DynamicMsg::new(
+            "foo/Bar",
+            "Header header\nfloat64[2] values\nstring text\n\
+             ================================================================================\n\
+             MSG: std_msgs/Header\nuint32 seq\ntime stamp\nstring frame_id\n",
+        )
+        .expect(FAILED_TO_PARSE)
+    }
+
+    #[test]
+    fn converts_to_yaml_text() {
+        let msg = example();
+        let value = msg
+            .from_yaml_args(&["{header: {stamp: 1.5}, values: [1, 2.5], text: hi}"])
+            .unwrap();
+        assert_eq!(
+            "header:\n  seq: 0\n  stamp:\n    secs: 1\n    nsecs: 500000000\n  frame_id: \"\"\n\
+             values:\n  - 1.0\n  - 2.5\ntext: hi",
+            to_yaml_string(&value)
+        );
+    }
+
+    #[test]
+    fn parses_positional_arguments() {
+        let msg = example();
+        let value = msg
+            .from_yaml_args(&["{seq: 3, frame_id: map}", "[0, 1]", "'42'"])
+            .unwrap();
+        assert_eq!(Some(&Value::U32(3)), value.get("header.seq"));
+        assert_eq!(
+            Some(&Value::String("map".into())),
+            value.get("header.frame_id")
+        );
+        assert_eq!(Some(&Value::F64(1.0)), value.get("values.1"));
+        assert_eq!(Some(&Value::String("42".into())), value.get("text"));
+    }
+
+    #[test]
+    fn reports_bad_values() {
+        let msg = example();
+        let check = |arg: &str, expected: &str| match msg.from_yaml_args(&[arg]).unwrap_err().kind()
+        {
+            ErrorKind::BadValue(field, _) => assert_eq!(expected, field),
+            kind => panic!("Unexpected error: {:?}", kind),
+        };
+        check("{values: [1]}", "values");
+        check("{header: {seq: -1}}", "header.seq");
+        check("{header: {stamp: abc}}", "header.stamp");
+        check("{other: 1}", "other");
+    }
+}
@@ -4,23 +4,36 @@
 extern crate error_chain;
 
 pub use crate::api::raii::{Publisher, Service, Subscriber};
-pub use crate::api::{error, Clock, Parameter};
+pub use crate::api::{
+    error, Clock, NodeHandle, Parameter, Ros, RosBuilder, ShutdownManager, ShutdownReason,
+};
+pub use crate::logger::RosLogger;
+pub use crate::raw_message::{RawMessage, RawMessageDescription};
 #[doc(hidden)]
 pub use crate::rosmsg::RosMsg;
 pub use crate::singleton::*;
-pub use crate::tcpros::{Client, ClientResponse, Message, ServicePair};
+pub use crate::tcpros::{
+    Client, ClientResponse, ClientTimeouts, Message, RetryPolicy, ServiceConcurrency, ServiceEvent,
+    ServicePair, ServiceProbe, SingleSubscriberPublisher, Transport, TransportHints, TypeMismatch,
+    DEFAULT_MAX_MESSAGE_SIZE,
+};
 pub use crate::time::{Duration, Time};
 #[doc(hidden)]
 pub use rosrust_codegen::*;
 
 pub mod api;
+pub mod dynamic;
 mod log_macros;
+mod logger;
 #[doc(hidden)]
 pub mod msg;
+mod raw_message;
 #[doc(hidden)]
 pub mod rosmsg;
 mod rosxmlrpc;
 pub mod singleton;
 mod tcpros;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod time;
 mod util;
@@ -1,9 +1,17 @@
 #[macro_export]
 macro_rules! ros_log {
-    ($level:expr, $($arg:tt)+) => {
+    ($level:expr, $($arg:tt)+) => {{
+        fn __rosrust_log_function() {}
         let msg = format!($($arg)*);
-        $crate::log($level, msg, file!(), line!());
-    }
+        $crate::log_named(
+            $level,
+            msg,
+            &$crate::api::logger_name(module_path!()),
+            $crate::api::function_name(__rosrust_log_function),
+            file!(),
+            line!(),
+        );
+    }}
 }
 
 #[macro_export]
@@ -40,3 +48,233 @@ macro_rules! ros_fatal {
         $crate::ros_log!($crate::msg::rosgraph_msgs::Log::FATAL, $($arg)*);
     }
 }
+
+#[macro_export]
+macro_rules! ros_log_named {
+    ($level:expr, $name:expr, $($arg:tt)+) => {{
+        fn __rosrust_log_function() {}
+        let msg = format!($($arg)*);
+        $crate::log_named(
+            $level,
+            msg,
+            &$crate::api::named_logger_name(module_path!(), $name),
+            $crate::api::function_name(__rosrust_log_function),
+            file!(),
+            line!(),
+        );
+    }}
+}
+
+#[macro_export]
+macro_rules! ros_log_once {
+    ($level:expr, $($arg:tt)+) => {{
+        static LOGGED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+        if !LOGGED.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            $crate::ros_log!($level, $($arg)*);
+        }
+    }}
+}
+
+#[macro_export]
+macro_rules! ros_log_cond {
+    ($level:expr, $cond:expr, $($arg:tt)+) => {{
+        if $cond {
+            $crate::ros_log!($level, $($arg)*);
+        }
+    }}
+}
+
+#[macro_export]
+macro_rules! ros_log_throttle {
+    ($level:expr, $period:expr, $($arg:tt)+) => {{
+        static LAST_HIT: $crate::api::LastHit = $crate::api::LastHit::new();
+        if LAST_HIT.throttle($crate::now(), $period) {
+            $crate::ros_log!($level, $($arg)*);
+        }
+    }}
+}
+
+#[macro_export]
+macro_rules! ros_log_delayed_throttle {
+    ($level:expr, $period:expr, $($arg:tt)+) => {{
+        static LAST_HIT: $crate::api::LastHit = $crate::api::LastHit::new();
+        if LAST_HIT.delayed_throttle($crate::now(), $period) {
+            $crate::ros_log!($level, $($arg)*);
+        }
+    }}
+}
+
+#[macro_export]
+macro_rules! ros_debug_named {
+    ($name:expr, $($arg:tt)*) => {
+        $crate::ros_log_named!($crate::msg::rosgraph_msgs::Log::DEBUG, $name, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_info_named {
+    ($name:expr, $($arg:tt)*) => {
+        $crate::ros_log_named!($crate::msg::rosgraph_msgs::Log::INFO, $name, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_warn_named {
+    ($name:expr, $($arg:tt)*) => {
+        $crate::ros_log_named!($crate::msg::rosgraph_msgs::Log::WARN, $name, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_err_named {
+    ($name:expr, $($arg:tt)*) => {
+        $crate::ros_log_named!($crate::msg::rosgraph_msgs::Log::ERROR, $name, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_fatal_named {
+    ($name:expr, $($arg:tt)*) => {
+        $crate::ros_log_named!($crate::msg::rosgraph_msgs::Log::FATAL, $name, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_debug_once {
+    ($($arg:tt)*) => {
+        $crate::ros_log_once!($crate::msg::rosgraph_msgs::Log::DEBUG, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_info_once {
+    ($($arg:tt)*) => {
+        $crate::ros_log_once!($crate::msg::rosgraph_msgs::Log::INFO, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_warn_once {
+    ($($arg:tt)*) => {
+        $crate::ros_log_once!($crate::msg::rosgraph_msgs::Log::WARN, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_err_once {
+    ($($arg:tt)*) => {
+        $crate::ros_log_once!($crate::msg::rosgraph_msgs::Log::ERROR, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_fatal_once {
+    ($($arg:tt)*) => {
+        $crate::ros_log_once!($crate::msg::rosgraph_msgs::Log::FATAL, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_debug_cond {
+    ($cond:expr, $($arg:tt)*) => {
+        $crate::ros_log_cond!($crate::msg::rosgraph_msgs::Log::DEBUG, $cond, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_info_cond {
+    ($cond:expr, $($arg:tt)*) => {
+        $crate::ros_log_cond!($crate::msg::rosgraph_msgs::Log::INFO, $cond, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_warn_cond {
+    ($cond:expr, $($arg:tt)*) => {
+        $crate::ros_log_cond!($crate::msg::rosgraph_msgs::Log::WARN, $cond, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_err_cond {
+    ($cond:expr, $($arg:tt)*) => {
+        $crate::ros_log_cond!($crate::msg::rosgraph_msgs::Log::ERROR, $cond, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_fatal_cond {
+    ($cond:expr, $($arg:tt)*) => {
+        $crate::ros_log_cond!($crate::msg::rosgraph_msgs::Log::FATAL, $cond, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_debug_throttle {
+    ($period:expr, $($arg:tt)*) => {
+        $crate::ros_log_throttle!($crate::msg::rosgraph_msgs::Log::DEBUG, $period, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_info_throttle {
+    ($period:expr, $($arg:tt)*) => {
+        $crate::ros_log_throttle!($crate::msg::rosgraph_msgs::Log::INFO, $period, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_warn_throttle {
+    ($period:expr, $($arg:tt)*) => {
+        $crate::ros_log_throttle!($crate::msg::rosgraph_msgs::Log::WARN, $period, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_err_throttle {
+    ($period:expr, $($arg:tt)*) => {
+        $crate::ros_log_throttle!($crate::msg::rosgraph_msgs::Log::ERROR, $period, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_fatal_throttle {
+    ($period:expr, $($arg:tt)*) => {
+        $crate::ros_log_throttle!($crate::msg::rosgraph_msgs::Log::FATAL, $period, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_debug_delayed_throttle {
+    ($period:expr, $($arg:tt)*) => {
+        $crate::ros_log_delayed_throttle!($crate::msg::rosgraph_msgs::Log::DEBUG, $period, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_info_delayed_throttle {
+    ($period:expr, $($arg:tt)*) => {
+        $crate::ros_log_delayed_throttle!($crate::msg::rosgraph_msgs::Log::INFO, $period, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_warn_delayed_throttle {
+    ($period:expr, $($arg:tt)*) => {
+        $crate::ros_log_delayed_throttle!($crate::msg::rosgraph_msgs::Log::WARN, $period, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_err_delayed_throttle {
+    ($period:expr, $($arg:tt)*) => {
+        $crate::ros_log_delayed_throttle!($crate::msg::rosgraph_msgs::Log::ERROR, $period, $($arg)*);
+    }
+}
+
+#[macro_export]
+macro_rules! ros_fatal_delayed_throttle {
+    ($period:expr, $($arg:tt)*) => {
+        $crate::ros_log_delayed_throttle!($crate::msg::rosgraph_msgs::Log::FATAL, $period, $($arg)*);
+    }
+}
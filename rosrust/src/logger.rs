@@ -0,0 +1,97 @@
+use crate::api::logger_name;
+use crate::error::Result;
+use crate::msg::rosgraph_msgs::Log;
+use crate::singleton::{try_log, try_log_to_terminal};
+use log::{Level, LevelFilter, Metadata, Record};
+use std::cell::Cell;
+
+/// Forwards records of the `log` crate to `/rosout` and the terminal.
+///
+/// Records logged before the node is initialized are only printed to the terminal.
+pub struct RosLogger;
+
+impl RosLogger {
+    /// Installs the logger globally, forwarding records up to the given level.
+    pub fn install(level: LevelFilter) -> Result<()> {
+        log::set_logger(&LOGGER).map_err(|err| format!("Failed to install logger: {}", err))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+static LOGGER: RosLogger = RosLogger;
+
+thread_local! {
+    static FORWARDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Clears the forwarding flag once forwarding ends, even if it panics.
+struct ForwardingGuard;
+
+impl Drop for ForwardingGuard {
+    fn drop(&mut self) {
+        FORWARDING.with(|v| v.set(false));
+    }
+}
+
+impl log::Log for RosLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let level = level_to_ros(record.level());
+        let msg = format!("{}", record.args());
+        let logger = logger_name(record.target());
+        let function = record.module_path().unwrap_or_else(|| record.target());
+        let file = record.file().unwrap_or("");
+        let line = record.line().unwrap_or(0);
+        // Publishing to /rosout can log errors itself, which must not be forwarded again,
+        // so they are only printed
+        if FORWARDING.with(|v| v.replace(true)) {
+            try_log_to_terminal(level, &msg, &logger, function, file, line);
+            return;
+        }
+        let _guard = ForwardingGuard;
+        try_log(level, msg, &logger, function, file, line);
+    }
+
+    fn flush(&self) {}
+}
+
+fn level_to_ros(level: Level) -> i8 {
+    match level {
+        Level::Error => Log::ERROR,
+        Level::Warn => Log::WARN,
+        Level::Info => Log::INFO,
+        Level::Debug | Level::Trace => Log::DEBUG,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_levels() {
+        assert_eq!(Log::ERROR, level_to_ros(Level::Error));
+        assert_eq!(Log::WARN, level_to_ros(Level::Warn));
+        assert_eq!(Log::INFO, level_to_ros(Level::Info));
+        assert_eq!(Log::DEBUG, level_to_ros(Level::Debug));
+        assert_eq!(Log::DEBUG, level_to_ros(Level::Trace));
+    }
+
+    #[test]
+    fn guard_clears_forwarding_flag_on_panic() {
+        let result = std::panic::catch_unwind(|| {
+            FORWARDING.with(|v| v.set(true));
+            let _guard = ForwardingGuard;
+            panic!("Failed while forwarding");
+        });
+        assert!(result.is_err());
+        assert!(!FORWARDING.with(Cell::get));
+    }
+}
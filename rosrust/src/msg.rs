@@ -1 +1,7 @@
-crate::rosmsg_include!(rosgraph_msgs / Clock, rosgraph_msgs / Log, INTERNAL);
+crate::rosmsg_include!(
+    rosgraph_msgs / Clock,
+    rosgraph_msgs / Log,
+    roscpp / GetLoggers,
+    roscpp / SetLoggerLevel,
+    INTERNAL
+);
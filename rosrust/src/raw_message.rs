@@ -0,0 +1,107 @@
+use crate::rosmsg::RosMsg;
+use crate::tcpros::{Message, ServicePair};
+use std::collections::HashMap;
+use std::io;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RawMessage(pub Vec<u8>);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawMessageDescription {
+    pub msg_definition: String,
+    pub md5sum: String,
+    pub msg_type: String,
+}
+
+impl RawMessageDescription {
+    pub fn from_message<T: Message>() -> Self {
+        Self {
+            msg_definition: T::msg_definition(),
+            md5sum: T::md5sum(),
+            msg_type: T::msg_type(),
+        }
+    }
+
+    pub fn from_headers(headers: &HashMap<String, String>) -> Option<Self> {
+        Some(Self {
+            msg_definition: headers
+                .get("message_definition")
+                .cloned()
+                .unwrap_or_default(),
+            md5sum: headers.get("md5sum")?.clone(),
+            msg_type: headers.get("type")?.clone(),
+        })
+    }
+}
+
+impl Message for RawMessage {
+    #[inline]
+    fn msg_definition() -> String {
+        "*".into()
+    }
+
+    #[inline]
+    fn md5sum() -> String {
+        "*".into()
+    }
+
+    #[inline]
+    fn msg_type() -> String {
+        "*".into()
+    }
+}
+
+impl ServicePair for RawMessage {
+    type Request = RawMessage;
+    type Response = RawMessage;
+}
+
+impl RosMsg for RawMessage {
+    #[inline]
+    fn encode<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(&self.0)
+    }
+
+    #[inline]
+    fn decode<R: io::Read>(mut r: R) -> io::Result<Self> {
+        let mut data = vec![];
+        r.read_to_end(&mut data)?;
+        Ok(Self(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_payload_verbatim() {
+        let message = RawMessage(vec![3, 0, 0, 0, 1, 2, 3]);
+        assert_eq!(
+            message.encode_vec().unwrap(),
+            vec![7, 0, 0, 0, 3, 0, 0, 0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn decodes_whole_payload() {
+        let message = RawMessage::decode_slice(&[7, 0, 0, 0, 3, 0, 0, 0, 1, 2, 3]).unwrap();
+        assert_eq!(message, RawMessage(vec![3, 0, 0, 0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn reads_description_from_headers() {
+        let mut headers = HashMap::new();
+        headers.insert("md5sum".into(), "abc".into());
+        assert_eq!(None, RawMessageDescription::from_headers(&headers));
+        headers.insert("type".into(), "std_msgs/String".into());
+        assert_eq!(
+            Some(RawMessageDescription {
+                msg_definition: String::new(),
+                md5sum: "abc".into(),
+                msg_type: "std_msgs/String".into(),
+            }),
+            RawMessageDescription::from_headers(&headers)
+        );
+    }
+}
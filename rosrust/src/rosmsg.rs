@@ -2,7 +2,7 @@ use crate::time::{Duration, Time};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std;
 use std::collections::HashMap;
-use std::io;
+use std::io::{self, Read};
 
 pub trait RosMsg: std::marker::Sized {
     fn encode<W: io::Write>(&self, w: W) -> io::Result<()>;
@@ -30,8 +30,21 @@ pub trait RosMsg: std::marker::Sized {
         reader.set_position(4);
         Self::decode(&mut reader)
     }
+
+    /// Lets vectors of numeric primitives be copied to and from the wire as raw bytes.
+    #[doc(hidden)]
+    #[inline]
+    fn bulk_copyable() -> Option<BulkCopyable> {
+        None
+    }
 }
 
+/// Marks a type as plain little endian data, valid for any bit pattern.
+///
+/// Only this module can create it, so no other type can claim to be bulk copyable.
+#[doc(hidden)]
+pub struct BulkCopyable(());
+
 impl RosMsg for bool {
     #[inline]
     fn encode<W: io::Write>(&self, mut w: W) -> io::Result<()> {
@@ -54,6 +67,11 @@ impl RosMsg for u8 {
     fn decode<R: io::Read>(mut r: R) -> io::Result<Self> {
         r.read_u8()
     }
+
+    #[inline]
+    fn bulk_copyable() -> Option<BulkCopyable> {
+        Some(BulkCopyable(()))
+    }
 }
 
 impl RosMsg for i8 {
@@ -66,6 +84,11 @@ impl RosMsg for i8 {
     fn decode<R: io::Read>(mut r: R) -> io::Result<Self> {
         r.read_i8()
     }
+
+    #[inline]
+    fn bulk_copyable() -> Option<BulkCopyable> {
+        Some(BulkCopyable(()))
+    }
 }
 
 impl RosMsg for u16 {
@@ -78,6 +101,11 @@ impl RosMsg for u16 {
     fn decode<R: io::Read>(mut r: R) -> io::Result<Self> {
         r.read_u16::<LittleEndian>()
     }
+
+    #[inline]
+    fn bulk_copyable() -> Option<BulkCopyable> {
+        Some(BulkCopyable(()))
+    }
 }
 
 impl RosMsg for i16 {
@@ -90,6 +118,11 @@ impl RosMsg for i16 {
     fn decode<R: io::Read>(mut r: R) -> io::Result<Self> {
         r.read_i16::<LittleEndian>()
     }
+
+    #[inline]
+    fn bulk_copyable() -> Option<BulkCopyable> {
+        Some(BulkCopyable(()))
+    }
 }
 
 impl RosMsg for u32 {
@@ -102,6 +135,11 @@ impl RosMsg for u32 {
     fn decode<R: io::Read>(mut r: R) -> io::Result<Self> {
         r.read_u32::<LittleEndian>()
     }
+
+    #[inline]
+    fn bulk_copyable() -> Option<BulkCopyable> {
+        Some(BulkCopyable(()))
+    }
 }
 
 impl RosMsg for i32 {
@@ -114,6 +152,11 @@ impl RosMsg for i32 {
     fn decode<R: io::Read>(mut r: R) -> io::Result<Self> {
         r.read_i32::<LittleEndian>()
     }
+
+    #[inline]
+    fn bulk_copyable() -> Option<BulkCopyable> {
+        Some(BulkCopyable(()))
+    }
 }
 
 impl RosMsg for u64 {
@@ -126,6 +169,11 @@ impl RosMsg for u64 {
     fn decode<R: io::Read>(mut r: R) -> io::Result<Self> {
         r.read_u64::<LittleEndian>()
     }
+
+    #[inline]
+    fn bulk_copyable() -> Option<BulkCopyable> {
+        Some(BulkCopyable(()))
+    }
 }
 
 impl RosMsg for i64 {
@@ -138,6 +186,11 @@ impl RosMsg for i64 {
     fn decode<R: io::Read>(mut r: R) -> io::Result<Self> {
         r.read_i64::<LittleEndian>()
     }
+
+    #[inline]
+    fn bulk_copyable() -> Option<BulkCopyable> {
+        Some(BulkCopyable(()))
+    }
 }
 
 impl RosMsg for f32 {
@@ -150,6 +203,11 @@ impl RosMsg for f32 {
     fn decode<R: io::Read>(mut r: R) -> io::Result<Self> {
         r.read_f32::<LittleEndian>()
     }
+
+    #[inline]
+    fn bulk_copyable() -> Option<BulkCopyable> {
+        Some(BulkCopyable(()))
+    }
 }
 
 impl RosMsg for f64 {
@@ -162,6 +220,11 @@ impl RosMsg for f64 {
     fn decode<R: io::Read>(mut r: R) -> io::Result<Self> {
         r.read_f64::<LittleEndian>()
     }
+
+    #[inline]
+    fn bulk_copyable() -> Option<BulkCopyable> {
+        Some(BulkCopyable(()))
+    }
 }
 
 #[inline]
@@ -169,9 +232,18 @@ pub fn encode_fixed_slice<W: io::Write, T: RosMsg>(data: &[T], mut w: W) -> io::
     data.iter().try_for_each(|v| v.encode(w.by_ref()))
 }
 
+/// Decodes `len` consecutive items.
+///
+/// Lengths come from untrusted data, so only a bounded amount of memory is reserved
+/// up front, and the vector grows as items are successfully decoded.
 #[inline]
 pub fn decode_fixed_vec<R: io::Read, T: RosMsg>(len: u32, mut r: R) -> io::Result<Vec<T>> {
-    (0..len).map(move |_| T::decode(r.by_ref())).collect()
+    let len = len as usize;
+    let mut data = Vec::with_capacity(initial_capacity::<T>(len));
+    for _ in 0..len {
+        data.push(T::decode(r.by_ref())?);
+    }
+    Ok(data)
 }
 
 #[inline]
@@ -193,13 +265,17 @@ pub fn encode_variable_primitive_slice<W: io::Write, T: RosMsg>(
     data: &[T],
     mut w: W,
 ) -> io::Result<()> {
+    if T::bulk_copyable().is_none() {
+        return encode_variable_slice(data, w);
+    }
     (data.len() as u32).encode(w.by_ref())?;
     let ptr = data.as_ptr() as *const u8;
 
     // Because both wire and system are little endian, we simply copy
     // the in-memory slice to the buffer directly.
-    w.write(unsafe { std::slice::from_raw_parts(ptr, data.len() * std::mem::size_of::<T>()) })
-        .map(|_| ())
+    // SAFETY: `T` is a numeric primitive without padding, so all the slice's bytes
+    // are initialized, and they stay borrowed for as long as the byte view lives.
+    w.write_all(unsafe { std::slice::from_raw_parts(ptr, std::mem::size_of_val(data)) })
 }
 
 #[inline]
@@ -213,32 +289,41 @@ pub fn encode_variable_primitive_slice<W: io::Write, T: RosMsg>(
 
 /// Fast vector decoding when platform endiannes matches wire
 /// endiannes (little).
+///
+/// Numbers are copied straight into the vector, which only grows as data arrives,
+/// so a bogus length prefix results in an error instead of a huge allocation.
+/// Other types, like `bool`, are decoded one by one, since not every byte is valid for them.
 #[inline]
 #[cfg(target_endian = "little")]
 pub fn decode_variable_primitive_vec<R: io::Read, T: RosMsg>(mut r: R) -> io::Result<Vec<T>> {
     let num_elements = u32::decode(r.by_ref())? as usize;
-    let num_bytes = num_elements * std::mem::size_of::<T>();
-
-    // Allocate the memory w/o initializing because we will later fill
-    // all the memory.
-    let mut buf = Vec::<T>::with_capacity(num_elements);
-
-    let buf_ptr = buf.as_mut_ptr();
-
-    // Fill the Vec to full capacity with the stream data.
-    let mut read_buf = unsafe { std::slice::from_raw_parts_mut(buf_ptr as *mut u8, num_bytes) };
-    r.read_exact(&mut read_buf)?;
-
-    // Do not drop the memory
-    std::mem::forget(buf);
-
-    // Return a new, completely full Vec using the now initialized memory.
-    Ok(unsafe { Vec::from_raw_parts(buf_ptr, num_elements, num_elements) })
+    if T::bulk_copyable().is_none() {
+        return decode_fixed_vec(num_elements as u32, r);
+    }
+    let item_size = std::mem::size_of::<T>();
+
+    let mut data = Vec::<T>::with_capacity(initial_capacity::<T>(num_elements));
+    while data.len() < num_elements {
+        // Grow by the amount already received, so memory stays proportional to the data
+        let chunk = (num_elements - data.len()).min(data.capacity().max(data.len()).max(1));
+        data.reserve(chunk);
+        let start = data.len();
+        // SAFETY: `reserve` made room for `chunk` more items past `start`, and zeroing them
+        // first makes them valid numbers before they are exposed as a byte slice or the
+        // length covers them. Any bit pattern read afterwards is also a valid number.
+        unsafe {
+            let ptr = data.as_mut_ptr().add(start) as *mut u8;
+            std::ptr::write_bytes(ptr, 0, chunk * item_size);
+            r.read_exact(std::slice::from_raw_parts_mut(ptr, chunk * item_size))?;
+            data.set_len(start + chunk);
+        }
+    }
+    Ok(data)
 }
 
 #[inline]
 #[cfg(target_endian = "big")]
-pub fn decode_variable_primitive_vec<R: io::Read, T: RosMsg>(mut r: R) -> io::Result<Vec<T>> {
+pub fn decode_variable_primitive_vec<R: io::Read, T: RosMsg>(r: R) -> io::Result<Vec<T>> {
     decode_variable_vec(r)
 }
 
@@ -254,10 +339,10 @@ impl RosMsg for String {
     }
 
     #[inline]
-    fn decode<R: io::Read>(r: R) -> io::Result<Self> {
-        decode_variable_vec::<R, u8>(r).and_then(|v| {
-            String::from_utf8(v).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
-        })
+    fn decode<R: io::Read>(mut r: R) -> io::Result<Self> {
+        let len = u32::decode(r.by_ref())? as usize;
+        String::from_utf8(read_bytes(len, r)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
     }
 }
 
@@ -280,11 +365,14 @@ where
 
     #[inline]
     fn decode<R: io::Read>(mut r: R) -> io::Result<Self> {
-        let data_size = u64::from(read_data_size(r.by_ref())?);
-        let mut limited_r = r.take(data_size);
+        let data_size = read_data_size(r.by_ref())? as usize;
+        let data = read_bytes(data_size, r)?;
+        let mut rows = data.as_slice();
         let mut output = HashMap::<String, String, Hasher>::default();
-        // TODO: ensure we break only on EOF
-        while let Ok(item) = String::decode(&mut limited_r) {
+        // Every row must fit within the declared data size, so running out of data
+        // anywhere but at a row boundary is an error.
+        while !rows.is_empty() {
+            let item = String::decode(&mut rows)?;
             let parts = item.splitn(2, '=').collect::<Vec<&str>>();
             match *parts.as_slice() {
                 [key, value] => output.insert(key.into(), value.into()),
@@ -334,6 +422,28 @@ impl RosMsg for Duration {
     }
 }
 
+/// Upper bound on memory reserved ahead of decoding, as lengths are untrusted.
+const MAX_PREALLOCATED_BYTES: usize = 64 * 1024;
+
+#[inline]
+fn initial_capacity<T>(len: usize) -> usize {
+    len.min(MAX_PREALLOCATED_BYTES / std::mem::size_of::<T>().max(1))
+}
+
+/// Reads exactly `len` bytes, failing with `UnexpectedEof` if the data ends sooner.
+#[inline]
+fn read_bytes<R: io::Read>(len: usize, r: R) -> io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(initial_capacity::<u8>(len));
+    r.take(len as u64).read_to_end(&mut data)?;
+    if data.len() != len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Data ended before the declared length",
+        ));
+    }
+    Ok(data)
+}
+
 #[inline]
 fn read_data_size<R: io::Read>(r: R) -> io::Result<u32> {
     u32::decode(r)
@@ -343,3 +453,159 @@ fn read_data_size<R: io::Read>(r: R) -> io::Result<u32> {
 fn write_data_size<W: io::Write>(value: u32, w: W) -> io::Result<()> {
     value.encode(w)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::rosgraph_msgs::Log;
+    use proptest::prelude::*;
+
+    fn decode_all<T: RosMsg>(data: &[u8]) -> io::Result<T> {
+        let mut cursor = io::Cursor::new(data);
+        T::decode(&mut cursor)
+    }
+
+    fn sample_log(name: String, msg: String, line: u32, topics: Vec<String>) -> Log {
+        let mut log = Log {
+            level: Log::WARN,
+            name,
+            msg,
+            line,
+            topics,
+            ..Default::default()
+        };
+        log.header.seq = line;
+        log.header.stamp = Time { sec: line, nsec: 7 };
+        log.header.frame_id = "frame".into();
+        log
+    }
+
+    #[test]
+    fn rejects_lengths_beyond_available_data() {
+        let huge = [0xff, 0xff, 0xff, 0xff, 1, 2, 3];
+        let err = decode_all::<String>(&huge).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+        let err = decode_variable_vec::<_, Time>(&huge[..]).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+        let err = decode_variable_primitive_vec::<_, f64>(&huge[..]).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+        let err = decode_fixed_vec::<_, u64>(u32::max_value(), &huge[4..]).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+    }
+
+    #[test]
+    fn decodes_primitive_vectors_without_invalid_values() {
+        let data = [3, 0, 0, 0, 0, 1, 2];
+        let values = decode_variable_primitive_vec::<_, bool>(&data[..]).unwrap();
+        assert_eq!(vec![false, true, true], values);
+    }
+
+    #[test]
+    fn decodes_primitive_vectors_larger_than_preallocation() {
+        let values = (0..100_000u32).collect::<Vec<_>>();
+        let mut data = Vec::new();
+        encode_variable_primitive_slice(&values, &mut data).unwrap();
+        assert_eq!(
+            values,
+            decode_variable_primitive_vec::<_, u32>(&data[..]).unwrap()
+        );
+        data.pop();
+        let err = decode_variable_primitive_vec::<_, u32>(&data[..]).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+    }
+
+    #[test]
+    fn copies_only_numeric_primitive_vectors_as_bytes() {
+        let values = vec![String::from("ab"), String::from("c")];
+        let mut bulk = Vec::new();
+        encode_variable_primitive_slice(&values, &mut bulk).unwrap();
+        let mut itemwise = Vec::new();
+        encode_variable_slice(&values, &mut itemwise).unwrap();
+        assert_eq!(itemwise, bulk);
+        assert_eq!(
+            values,
+            decode_variable_primitive_vec::<_, String>(&bulk[..]).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_header_rows() {
+        // Declares 11 bytes of rows, but the only row claims 8 bytes with 7 available
+        let data = [11, 0, 0, 0, 8, 0, 0, 0, 97, 98, 99, 61, 49, 50, 51];
+        let err = decode_all::<HashMap<String, String>>(&data).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+
+        // Declares more rows than the stream contains
+        let data = [15, 0, 0, 0, 7, 0, 0, 0, 97, 98, 99, 61, 49, 50, 51];
+        let err = decode_all::<HashMap<String, String>>(&data).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips_headers(fields in prop::collection::hash_map("[a-z_]{1,12}", ".*", 0..8)) {
+            let mut data = Vec::new();
+            fields.encode(&mut data).unwrap();
+            let decoded: HashMap<String, String> = decode_all(&data).unwrap();
+            prop_assert_eq!(fields, decoded);
+        }
+
+        #[test]
+        fn round_trips_primitive_vectors(
+            floats in prop::collection::vec(any::<f64>(), 0..64),
+            bools in prop::collection::vec(any::<bool>(), 0..64),
+        ) {
+            let mut data = Vec::new();
+            encode_variable_primitive_slice(&floats, &mut data).unwrap();
+            encode_variable_primitive_slice(&bools, &mut data).unwrap();
+            let mut reader = data.as_slice();
+            let decoded_floats = decode_variable_primitive_vec::<_, f64>(&mut reader).unwrap();
+            let decoded_bools = decode_variable_primitive_vec::<_, bool>(&mut reader).unwrap();
+            prop_assert_eq!(
+                floats.iter().map(|v| v.to_bits()).collect::<Vec<_>>(),
+                decoded_floats.iter().map(|v| v.to_bits()).collect::<Vec<_>>()
+            );
+            prop_assert_eq!(bools, decoded_bools);
+            prop_assert!(reader.is_empty());
+        }
+
+        #[test]
+        fn round_trips_messages(
+            name in ".*",
+            msg in ".*",
+            line in any::<u32>(),
+            topics in prop::collection::vec(".*", 0..8),
+        ) {
+            let log = sample_log(name, msg, line, topics);
+            let data = log.encode_vec().unwrap();
+            let decoded = Log::decode_slice(&data).unwrap();
+            prop_assert_eq!(&log.msg, &decoded.msg);
+            prop_assert_eq!(&log.topics, &decoded.topics);
+            prop_assert_eq!(data, decoded.encode_vec().unwrap());
+        }
+
+        #[test]
+        fn random_bytes_do_not_panic_decoders(data in prop::collection::vec(any::<u8>(), 0..256)) {
+            let _ = decode_all::<HashMap<String, String>>(&data);
+            let _ = decode_all::<String>(&data);
+            let _ = decode_variable_vec::<_, String>(data.as_slice());
+            let _ = decode_all::<Log>(&data);
+            let _ = decode_variable_primitive_vec::<_, f64>(data.as_slice());
+            let _ = decode_variable_primitive_vec::<_, bool>(data.as_slice());
+        }
+
+        #[test]
+        fn corrupted_messages_do_not_panic_decoders(
+            index in any::<prop::sample::Index>(),
+            value in any::<u8>(),
+            cut in any::<prop::sample::Index>(),
+        ) {
+            let log = sample_log("/node".into(), "message".into(), 42, vec!["/rosout".into()]);
+            let mut data = log.encode_vec().unwrap();
+            let position = index.index(data.len());
+            data[position] = value;
+            data.truncate(cut.index(data.len() + 1));
+            let _ = decode_all::<Log>(&data);
+        }
+    }
+}
@@ -1,18 +1,31 @@
+use crate::api::console::{self, ConsoleFormat, ConsoleOutput, LogRecord};
 use crate::api::raii::{Publisher, Service, Subscriber};
 use crate::api::resolve::get_unused_args;
-use crate::api::{Delay, Parameter, Rate, Ros, SystemState, Topic};
+use crate::api::{
+    Clock, Delay, Graph, GraphChanges, GraphWatcher, NodeHandle, Parameter, Rate, RealClock,
+    RemoteNode, Ros, RosBuilder, ShutdownReason, SystemState, Topic,
+};
 use crate::error::{ErrorKind, Result};
+use crate::logger::RosLogger;
 use crate::rosxmlrpc::Response;
-use crate::tcpros::{Client, Message, ServicePair, ServiceResult};
+use crate::tcpros::{
+    Client, Message, ServiceConcurrency, ServiceEvent, ServicePair, ServiceResult,
+    SingleSubscriberPublisher, TransportHints,
+};
 use crate::time::{Duration, Time};
 use crate::util::FAILED_TO_LOCK;
+use crate::RawMessageDescription;
 use crossbeam::sync::ShardedLock;
 use ctrlc;
 use lazy_static::lazy_static;
+use log::LevelFilter;
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time;
 
 lazy_static! {
-    static ref ROS: ShardedLock<Option<Ros>> = ShardedLock::new(None);
+    static ref ROS: ShardedLock<Option<Arc<Ros>>> = ShardedLock::new(None);
+    static ref CONSOLE_FORMAT: ConsoleFormat = ConsoleFormat::from_env();
 }
 
 #[inline]
@@ -20,24 +33,56 @@ pub fn init(name: &str) {
     try_init(name).expect("ROS init failed!");
 }
 
+/// Initializes the node, forwarding records of the `log` crate to `/rosout`.
+#[inline]
+pub fn init_with_logger(name: &str, level: LevelFilter) {
+    try_init(name).expect("ROS init failed!");
+    install_logger(level).expect("ROS logger installation failed!");
+}
+
 #[inline]
 pub fn try_init(name: &str) -> Result<()> {
     try_init_with_options(name, true)
 }
 
+#[inline]
 pub fn try_init_with_options(name: &str, capture_sigint: bool) -> Result<()> {
+    try_init_with(
+        name,
+        InitOptions {
+            no_sigint_handler: !capture_sigint,
+            ..InitOptions::default()
+        },
+    )
+}
+
+/// Options for initializing the node.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InitOptions {
+    /// Appends the hostname, process ID and time to the name, so multiple instances can run.
+    pub anonymous_name: bool,
+    /// Leaves handling of SIGINT to the application.
+    pub no_sigint_handler: bool,
+    /// Skips writing messages to a log file, like for short lived command line tools.
+    pub no_log_file: bool,
+}
+
+pub fn try_init_with(name: &str, options: InitOptions) -> Result<()> {
     let mut ros = ROS.write().expect(FAILED_TO_LOCK);
     if ros.is_some() {
         bail!(ErrorKind::MultipleInitialization);
     }
-    let client = Ros::new(name)?;
-    if capture_sigint {
+    let client = RosBuilder::from_env(name)
+        .anonymous(options.anonymous_name)
+        .log_file(!options.no_log_file)
+        .build()?;
+    if !options.no_sigint_handler {
         let shutdown_sender = client.shutdown_sender();
         ctrlc::set_handler(move || {
-            shutdown_sender.shutdown();
+            shutdown_sender.shutdown_with_reason(ShutdownReason::Sigint);
         })?;
     }
-    *ros = Some(client);
+    *ros = Some(Arc::new(client));
     Ok(())
 }
 
@@ -69,6 +114,11 @@ pub fn name() -> String {
     ros!().name().into()
 }
 
+#[inline]
+pub fn resolve_name(name: &str) -> Result<String> {
+    ros!().resolve_name(name)
+}
+
 #[inline]
 pub fn hostname() -> String {
     ros!().hostname().into()
@@ -110,6 +160,28 @@ pub fn shutdown() {
     ros!().shutdown_sender().shutdown()
 }
 
+#[inline]
+pub fn shutdown_reason() -> Option<ShutdownReason> {
+    ros!().shutdown_reason()
+}
+
+/// Adds a hook that runs once shutdown is requested, before the node unregisters.
+#[inline]
+pub fn on_shutdown<F>(hook: F)
+where
+    F: FnOnce(&ShutdownReason) + Send + 'static,
+{
+    ros!().on_shutdown(hook)
+}
+
+/// Blocks until the node is shut down and unregistered from the master.
+#[inline]
+pub fn wait_for_shutdown() {
+    // The lock must not be held while waiting
+    let shutdown_manager = { ros!().shutdown_sender() };
+    shutdown_manager.wait_for_shutdown()
+}
+
 #[inline]
 pub fn param(name: &str) -> Option<Parameter> {
     ros!().param(name)
@@ -130,11 +202,55 @@ pub fn topics() -> Response<Vec<Topic>> {
     ros!().topics()
 }
 
+#[inline]
+pub fn nodes() -> Response<Vec<String>> {
+    ros!().nodes()
+}
+
+#[inline]
+pub fn graph() -> Response<Graph> {
+    ros!().graph()
+}
+
+#[inline]
+pub fn watch_graph<F>(period: time::Duration, callback: F) -> GraphWatcher
+where
+    F: FnMut(&Graph, &GraphChanges) + Send + 'static,
+{
+    ros!().watch_graph(period, callback)
+}
+
+#[inline]
+pub fn remote_node(name: &str) -> Result<RemoteNode> {
+    ros!().remote_node(name)
+}
+
+#[inline]
+pub fn service_uri(service: &str) -> Result<String> {
+    ros!().service_uri(service)
+}
+
+/// Creates a handle in a namespace relative to the node's namespace, or `~` for the private one.
+#[inline]
+pub fn node_handle(namespace: &str) -> Result<NodeHandle> {
+    NodeHandle::new(Arc::clone(ros!()), namespace)
+}
+
 #[inline]
 pub fn client<T: ServicePair>(service: &str) -> Result<Client<T>> {
     ros!().client::<T>(service)
 }
 
+#[inline]
+pub fn service_type(service: &str) -> Result<String> {
+    ros!().service_type(service)
+}
+
+#[inline]
+pub fn resilient_client<T: ServicePair>(service: &str) -> Result<Client<T>> {
+    ros!().resilient_client::<T>(service)
+}
+
 #[inline]
 pub fn wait_for_service(service: &str, timeout: Option<time::Duration>) -> Result<()> {
     ros!().wait_for_service(service, timeout)
@@ -149,15 +265,72 @@ where
     ros!().service::<T, F>(service, handler)
 }
 
+#[inline]
+pub fn set_max_message_size(name: &str, max_message_size: usize) -> Result<()> {
+    ros!().set_max_message_size(name, max_message_size)
+}
+
+#[inline]
+pub fn service_with_events<T, F>(
+    service: &str,
+    concurrency: ServiceConcurrency,
+    handler: F,
+) -> Result<Service>
+where
+    T: ServicePair,
+    F: Fn(T::Request, ServiceEvent) -> ServiceResult<T::Response> + Send + Sync + 'static,
+{
+    ros!().service_with_events::<T, F>(service, concurrency, handler)
+}
+
 #[inline]
 pub fn subscribe<T, F>(topic: &str, queue_size: usize, callback: F) -> Result<Subscriber>
 where
     T: Message,
-    F: Fn(T) -> () + Send + 'static,
+    F: Fn(T) + Send + 'static,
 {
     ros!().subscribe::<T, F>(topic, queue_size, callback)
 }
 
+#[inline]
+pub fn subscribe_with_hints<T, F>(
+    topic: &str,
+    queue_size: usize,
+    transport_hints: TransportHints,
+    callback: F,
+) -> Result<Subscriber>
+where
+    T: Message,
+    F: Fn(T) + Send + 'static,
+{
+    ros!().subscribe_with_hints::<T, F>(topic, queue_size, transport_hints, callback)
+}
+
+#[inline]
+pub fn subscribe_with_ids_and_headers<T, F, G>(
+    topic: &str,
+    queue_size: usize,
+    on_message: F,
+    on_connect: G,
+) -> Result<Subscriber>
+where
+    T: Message,
+    F: Fn(T, &str) + Send + 'static,
+    G: Fn(HashMap<String, String>) + Send + 'static,
+{
+    ros!().subscribe_with_ids_and_headers::<T, F, G>(topic, queue_size, on_message, on_connect)
+}
+
+#[inline]
+pub fn wait_for_message<T>(topic: &str, timeout: Option<time::Duration>) -> Result<T>
+where
+    T: Message,
+{
+    // The lock must not be held while waiting
+    let ros = { Arc::clone(ros!()) };
+    ros.wait_for_message::<T>(topic, timeout)
+}
+
 #[inline]
 pub fn publish<T>(topic: &str, queue_size: usize) -> Result<Publisher<T>>
 where
@@ -166,9 +339,114 @@ where
     ros!().publish::<T>(topic, queue_size)
 }
 
+#[inline]
+pub fn publish_with_callbacks<T, F, G>(
+    topic: &str,
+    queue_size: usize,
+    on_connect: F,
+    on_disconnect: G,
+) -> Result<Publisher<T>>
+where
+    T: Message,
+    F: Fn(SingleSubscriberPublisher<T>) + Send + Sync + 'static,
+    G: Fn(SingleSubscriberPublisher<T>) + Send + Sync + 'static,
+{
+    ros!().publish_with_callbacks::<T, F, G>(topic, queue_size, on_connect, on_disconnect)
+}
+
+#[inline]
+pub fn publish_latched<T>(topic: &str, queue_size: usize) -> Result<Publisher<T>>
+where
+    T: Message,
+{
+    ros!().publish_latched::<T>(topic, queue_size)
+}
+
+#[inline]
+pub fn publish_with_description<T>(
+    topic: &str,
+    queue_size: usize,
+    message_description: RawMessageDescription,
+) -> Result<Publisher<T>>
+where
+    T: Message,
+{
+    ros!().publish_with_description::<T>(topic, queue_size, message_description)
+}
+
 #[inline]
 pub fn log(level: i8, msg: String, file: &str, line: u32) {
     ros!().log(level, msg, file, line)
 }
 
+#[inline]
+pub fn log_named(level: i8, msg: String, logger: &str, function: &str, file: &str, line: u32) {
+    ros!().log_named(level, msg, logger, function, file, line)
+}
+
+#[inline]
+pub fn logger_level(logger: &str) -> i8 {
+    ros!().logger_level(logger)
+}
+
+#[inline]
+pub fn set_console_output(output: ConsoleOutput) {
+    ros!().set_console_output(output)
+}
+
+#[inline]
+pub fn set_logger_level(logger: &str, level: i8) {
+    ros!().set_logger_level(logger, level)
+}
+
+/// Forwards records of the `log` crate to `/rosout`, up to the given level.
+#[inline]
+pub fn install_logger(level: LevelFilter) -> Result<()> {
+    RosLogger::install(level)
+}
+
+/// Logs through the node if it is available, and only to the terminal otherwise.
+///
+/// The lock is not waited for, since records can be emitted during initialization.
+pub(crate) fn try_log(level: i8, msg: String, logger: &str, function: &str, file: &str, line: u32) {
+    if let Ok(ros) = ROS.try_read() {
+        if let Some(ref ros) = *ros {
+            return ros.log_named(level, msg, logger, function, file, line);
+        }
+    }
+    log_to_terminal(level, &msg, logger, function, file, line)
+}
+
+/// Prints a record through the node if it is available, without publishing or saving it.
+pub(crate) fn try_log_to_terminal(
+    level: i8,
+    msg: &str,
+    logger: &str,
+    function: &str,
+    file: &str,
+    line: u32,
+) {
+    if let Ok(ros) = ROS.try_read() {
+        if let Some(ref ros) = *ros {
+            return ros.log_named_to_terminal(level, msg, logger, function, file, line);
+        }
+    }
+    log_to_terminal(level, msg, logger, function, file, line)
+}
+
+/// Prints a record to the terminal only, with the console format from the environment.
+fn log_to_terminal(level: i8, msg: &str, logger: &str, function: &str, file: &str, line: u32) {
+    let record = LogRecord {
+        level,
+        msg,
+        node: "",
+        logger,
+        function,
+        file,
+        line,
+        time: &RealClock::default().now(),
+    };
+    console::write_to_terminal(&CONSOLE_FORMAT, ConsoleOutput::default(), &record)
+}
+
 static UNINITIALIZED: &str = "ROS uninitialized. Please run ros::init(name) first!";
@@ -1,28 +1,251 @@
-use super::error::{ErrorKind, Result, ResultExt};
+use super::error::{Error, ErrorKind, Result, ResultExt};
+use super::frame::{self, DEFAULT_MAX_MESSAGE_SIZE};
 use super::header::{decode, encode};
 use super::{ServicePair, ServiceResult};
 use crate::rosmsg::RosMsg;
-use byteorder::{LittleEndian, ReadBytesExt};
+use crate::util::FAILED_TO_LOCK;
+use byteorder::ReadBytesExt;
+use crossbeam::channel::{bounded, Receiver, RecvTimeoutError};
 use log::error;
 use net2::TcpStreamExt;
 use std;
 use std::collections::HashMap;
 use std::io;
 use std::io::{Read, Write};
-use std::net::TcpStream;
-use std::sync::Arc;
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
+
+/// Timeouts of the stages of a service call, where `None` waits indefinitely.
+///
+/// The header and response timeouts limit the whole stage, however the server spreads out its data.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClientTimeouts {
+    connect: Option<Duration>,
+    header: Option<Duration>,
+    response: Option<Duration>,
+}
+
+impl ClientTimeouts {
+    /// Applies the same timeout to every stage.
+    pub fn all(timeout: Duration) -> Self {
+        Self {
+            connect: Some(timeout),
+            header: Some(timeout),
+            response: Some(timeout),
+        }
+    }
+
+    /// Limits each connection attempt.
+    pub fn connect(mut self, timeout: Option<Duration>) -> Self {
+        self.connect = timeout;
+        self
+    }
+
+    /// Limits the exchange of connection headers.
+    pub fn header(mut self, timeout: Option<Duration>) -> Self {
+        self.header = timeout;
+        self
+    }
+
+    /// Limits sending the request and waiting for the response.
+    pub fn response(mut self, timeout: Option<Duration>) -> Self {
+        self.response = timeout;
+        self
+    }
+}
+
+/// Controls how often, and how quickly, connecting to a service gets retried.
+///
+/// The delay between attempts starts at `initial_delay` and doubles after every attempt,
+/// up to `max_delay` if one is set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    attempts: usize,
+    initial_delay: Duration,
+    max_delay: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(15, Duration::from_millis(1))
+    }
+}
+
+impl RetryPolicy {
+    /// Makes up to `attempts` connection attempts, with no upper limit on the delay.
+    pub fn new(attempts: usize, initial_delay: Duration) -> Self {
+        Self {
+            attempts,
+            initial_delay,
+            max_delay: None,
+        }
+    }
+
+    /// Tries connecting only once.
+    pub fn no_retries() -> Self {
+        Self::new(1, Duration::from_millis(0))
+    }
+
+    /// Stops the delay from doubling beyond `max_delay`.
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = Some(max_delay);
+        self
+    }
+}
+
+/// Shared between a pending call and its response, to interrupt the call on demand.
+#[derive(Default)]
+struct Cancellation {
+    cancelled: AtomicBool,
+    stream: Mutex<Option<TcpStream>>,
+}
+
+impl Cancellation {
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        // Shutting down the socket unblocks any pending reads and writes
+        if let Some(ref stream) = *self.stream.lock().expect(FAILED_TO_LOCK) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn track(&self, stream: &TcpStream) -> Result<()> {
+        let mut tracked = self.stream.lock().expect(FAILED_TO_LOCK);
+        if self.is_cancelled() {
+            bail!(ErrorKind::ServiceCallCancelled);
+        }
+        *tracked = Some(stream.try_clone()?);
+        Ok(())
+    }
+
+    fn sleep(&self, duration: Duration) -> Result<()> {
+        let step = Duration::from_millis(10);
+        let mut remaining = duration;
+        while remaining > Duration::from_millis(0) {
+            if self.is_cancelled() {
+                bail!(ErrorKind::ServiceCallCancelled);
+            }
+            let current = remaining.min(step);
+            thread::sleep(current);
+            remaining -= current;
+        }
+        Ok(())
+    }
+}
+
+/// Stream whose reads and writes fail once a deadline passes.
+struct Deadline<'a> {
+    stream: &'a mut TcpStream,
+    deadline: Option<Instant>,
+}
+
+impl<'a> Deadline<'a> {
+    fn new(stream: &'a mut TcpStream, timeout: Option<Duration>) -> Self {
+        Self {
+            stream,
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+        }
+    }
+
+    /// Shrinks the socket timeouts to the time left until the deadline.
+    fn prepare(&mut self) -> io::Result<()> {
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => return Ok(()),
+        };
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining == Duration::from_millis(0) {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "Deadline passed"));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.set_write_timeout(Some(remaining))
+    }
+}
+
+impl<'a> Read for Deadline<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.prepare()?;
+        self.stream.read(buf)
+    }
+}
+
+impl<'a> Write for Deadline<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.prepare()?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Connection header of a service server, as reported to a probe.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServiceProbe {
+    pub caller_id: String,
+    pub md5sum: String,
+    pub msg_type: String,
+    /// Whether the server's checksum matches the one of the probing client.
+    pub matches: bool,
+}
+
+impl ServiceProbe {
+    fn from_headers(mut headers: HashMap<String, String>, md5sum: &str) -> Result<Self> {
+        let mut take = |field: &str| {
+            headers
+                .remove(field)
+                .ok_or_else(|| ErrorKind::HeaderMissingField(field.into()))
+        };
+        let caller_id = take("callerid")?;
+        let server_md5sum = take("md5sum")?;
+        let msg_type = take("type")?;
+        // Raw clients and servers accept any checksum
+        let matches = md5sum == "*" || server_md5sum == "*" || server_md5sum == md5sum;
+        Ok(Self {
+            caller_id,
+            md5sum: server_md5sum,
+            msg_type,
+            matches,
+        })
+    }
+}
 
 pub struct ClientResponse<T> {
-    handle: thread::JoinHandle<Result<ServiceResult<T>>>,
+    response: Receiver<Result<ServiceResult<T>>>,
+    cancellation: Arc<Cancellation>,
 }
 
 impl<T> ClientResponse<T> {
     pub fn read(self) -> Result<ServiceResult<T>> {
-        self.handle
-            .join()
+        self.response
+            .recv()
             .unwrap_or_else(|_| Err(ErrorKind::ServiceResponseUnknown.into()))
     }
+
+    /// Waits for the response up to the timeout, cancelling the call if it runs out.
+    pub fn read_timeout(self, timeout: Duration) -> Result<ServiceResult<T>> {
+        match self.response.recv_timeout(timeout) {
+            Ok(response) => response,
+            Err(RecvTimeoutError::Timeout) => {
+                self.cancellation.cancel();
+                Err(ErrorKind::ServiceCallTimeout("waiting for response".into()).into())
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(ErrorKind::ServiceResponseUnknown.into()),
+        }
+    }
+
+    /// Interrupts the call, making it fail with a cancellation error.
+    pub fn cancel(&self) {
+        self.cancellation.cancel();
+    }
 }
 
 impl<T: Send + 'static> ClientResponse<T> {
@@ -34,10 +257,74 @@ impl<T: Send + 'static> ClientResponse<T> {
     }
 }
 
+type UriLookup = Arc<dyn Fn() -> Option<String> + Send + Sync>;
+
+#[derive(Clone)]
 struct ClientInfo {
     caller_id: String,
-    uri: String,
+    uri: Arc<Mutex<String>>,
     service: String,
+    max_message_size: usize,
+    timeouts: ClientTimeouts,
+    retry_policy: RetryPolicy,
+    lookup: Option<UriLookup>,
+    availability_wait: Option<Duration>,
+}
+
+impl ClientInfo {
+    fn uri(&self) -> String {
+        self.uri.lock().expect(FAILED_TO_LOCK).clone()
+    }
+
+    /// Looks the service up again, waiting for it to appear if configured to.
+    fn lookup(&self, cancellation: &Cancellation) -> Result<Option<String>> {
+        let lookup = match self.lookup {
+            Some(ref lookup) => lookup,
+            None => return Ok(Some(self.uri())),
+        };
+        let start = Instant::now();
+        loop {
+            if let Some(uri) = lookup() {
+                *self.uri.lock().expect(FAILED_TO_LOCK) = uri.clone();
+                return Ok(Some(uri));
+            }
+            match self.availability_wait {
+                Some(wait) if start.elapsed() < wait => {
+                    cancellation.sleep(Duration::from_millis(100))?
+                }
+                _ => return Ok(None),
+            }
+        }
+    }
+
+    fn connect(&self, timeout: Option<Duration>, cancellation: &Cancellation) -> Result<TcpStream> {
+        let uri = self.uri();
+        if self.lookup.is_none() {
+            return self.connect_to(&uri, &self.retry_policy, timeout, cancellation);
+        }
+        // The server might have moved, so only try the known address once before looking it up
+        let result = self.connect_to(&uri, &RetryPolicy::no_retries(), timeout, cancellation);
+        if result.is_ok() || cancellation.is_cancelled() {
+            return result;
+        }
+        match self.lookup(cancellation)? {
+            Some(uri) => self.connect_to(&uri, &self.retry_policy, timeout, cancellation),
+            None => bail!(ErrorKind::ServiceConnectionFail(self.service.clone(), uri)),
+        }
+    }
+
+    fn connect_to(
+        &self,
+        uri: &str,
+        retry_policy: &RetryPolicy,
+        timeout: Option<Duration>,
+        cancellation: &Cancellation,
+    ) -> Result<TcpStream> {
+        let trimmed_uri = uri.trim_start_matches("rosrpc://");
+        connect_with_retries(trimmed_uri, retry_policy, timeout, cancellation)
+            .map_err(|err| classify_error(err, "connecting", cancellation))
+            .chain_err(|| ErrorKind::ServiceConnectionFail(self.service.clone(), uri.into()))
+    }
 }
 
 #[derive(Clone)]
@@ -46,72 +333,274 @@ pub struct Client<T: ServicePair> {
     phantom: std::marker::PhantomData<T>,
 }
 
-fn connect_to_tcp_with_multiple_attempts(uri: &str, attempts: usize) -> io::Result<TcpStream> {
-    let mut err = io::Error::new(
-        io::ErrorKind::Other,
-        "Tried to connect via TCP with 0 connection attempts",
-    );
-    let mut repeat_delay_ms = 1;
-    for _ in 0..attempts {
-        let stream_result = TcpStream::connect(uri).and_then(|stream| {
-            stream.set_linger(None)?;
-            Ok(stream)
-        });
-        match stream_result {
-            Ok(stream) => {
-                return Ok(stream);
+const CONNECT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+fn connect_with_retries(
+    uri: &str,
+    retry_policy: &RetryPolicy,
+    timeout: Option<Duration>,
+    cancellation: &Cancellation,
+) -> Result<TcpStream> {
+    let mut err: Error =
+        io::Error::other("Tried to connect via TCP with 0 connection attempts").into();
+    let mut delay = retry_policy.initial_delay;
+    for attempt in 0..retry_policy.attempts {
+        if attempt > 0 {
+            cancellation.sleep(delay)?;
+            delay *= 2;
+            if let Some(max_delay) = retry_policy.max_delay {
+                delay = delay.min(max_delay);
             }
-            Err(error) => err = error,
         }
-        std::thread::sleep(std::time::Duration::from_millis(repeat_delay_ms));
-        repeat_delay_ms *= 2;
+        if cancellation.is_cancelled() {
+            bail!(ErrorKind::ServiceCallCancelled);
+        }
+        match connect(uri, timeout, cancellation) {
+            Ok(stream) => return Ok(stream),
+            Err(error) => err = error.into(),
+        }
     }
     Err(err)
 }
 
+fn connect(
+    uri: &str,
+    timeout: Option<Duration>,
+    cancellation: &Cancellation,
+) -> io::Result<TcpStream> {
+    let address = uri
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Service URI has no address"))?;
+    let stream = match timeout {
+        Some(timeout) => TcpStream::connect_timeout(&address, timeout)?,
+        // Waiting in slices keeps an unlimited connection attempt responsive to cancellation
+        None => loop {
+            match TcpStream::connect_timeout(&address, CONNECT_POLL_INTERVAL) {
+                Err(ref err) if is_timeout(err) && !cancellation.is_cancelled() => continue,
+                result => break result?,
+            }
+        },
+    };
+    stream.set_linger(None)?;
+    Ok(stream)
+}
+
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// Replaces errors caused by timeouts or cancellation with errors saying so.
+fn classify_error(err: Error, stage: &str, cancellation: &Cancellation) -> Error {
+    if cancellation.is_cancelled() {
+        return ErrorKind::ServiceCallCancelled.into();
+    }
+    let timed_out = match *err.kind() {
+        ErrorKind::Io(ref err) => is_timeout(err),
+        _ => false,
+    };
+    if timed_out {
+        return Error::with_chain(err, ErrorKind::ServiceCallTimeout(stage.into()));
+    }
+    err
+}
+
 impl<T: ServicePair> Client<T> {
     pub fn new(caller_id: &str, uri: &str, service: &str) -> Client<T> {
+        Self::with_max_message_size(caller_id, uri, service, DEFAULT_MAX_MESSAGE_SIZE)
+    }
+
+    /// Creates a client that rejects responses larger than `max_message_size` bytes.
+    pub fn with_max_message_size(
+        caller_id: &str,
+        uri: &str,
+        service: &str,
+        max_message_size: usize,
+    ) -> Client<T> {
         Client {
             info: std::sync::Arc::new(ClientInfo {
                 caller_id: String::from(caller_id),
-                uri: String::from(uri),
+                uri: Arc::new(Mutex::new(String::from(uri))),
                 service: String::from(service),
+                max_message_size,
+                timeouts: ClientTimeouts::default(),
+                retry_policy: RetryPolicy::default(),
+                lookup: None,
+                availability_wait: None,
             }),
             phantom: std::marker::PhantomData,
         }
     }
 
+    /// Sets the timeouts used by calls that do not provide their own.
+    pub fn with_timeouts(mut self, timeouts: ClientTimeouts) -> Self {
+        Arc::make_mut(&mut self.info).timeouts = timeouts;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        Arc::make_mut(&mut self.info).retry_policy = retry_policy;
+        self
+    }
+
+    /// Makes the client look up the service's URI again whenever connecting fails,
+    /// following servers that restart on a different address.
+    ///
+    /// The lookup returns `None` if the service is not currently provided.
+    pub fn with_lookup<F>(mut self, lookup: F) -> Self
+    where
+        F: Fn() -> Option<String> + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.info).lookup = Some(Arc::new(lookup));
+        self
+    }
+
+    /// Makes calls wait up to the given time for the service to be provided again,
+    /// instead of failing right away. Only applies to clients with a lookup.
+    pub fn with_availability_wait(mut self, wait: Duration) -> Self {
+        Arc::make_mut(&mut self.info).availability_wait = Some(wait);
+        self
+    }
+
+    /// URI of the service server currently used by the client.
+    pub fn uri(&self) -> String {
+        self.info.uri()
+    }
+
+    /// Checks if the service is provided by any node, updating the URI if it moved.
+    ///
+    /// Clients without a lookup assume that their service exists.
+    pub fn exists(&self) -> bool {
+        match self.info.lookup {
+            Some(ref lookup) => match lookup() {
+                Some(uri) => {
+                    *self.info.uri.lock().expect(FAILED_TO_LOCK) = uri;
+                    true
+                }
+                None => false,
+            },
+            None => true,
+        }
+    }
+
+    /// Checks if the service server accepts connections, with a single connection attempt.
+    pub fn is_available(&self) -> bool {
+        let timeout = self
+            .info
+            .timeouts
+            .connect
+            .or_else(|| Some(Duration::from_secs(1)));
+        self.exists()
+            && self
+                .info
+                .connect_to(
+                    &self.uri(),
+                    &RetryPolicy::no_retries(),
+                    timeout,
+                    &Cancellation::default(),
+                )
+                .is_ok()
+    }
+
     pub fn req(&self, args: &T::Request) -> Result<ServiceResult<T::Response>> {
-        Self::request_body(
-            args,
-            &self.info.uri,
-            &self.info.caller_id,
-            &self.info.service,
-        )
+        self.req_with_timeouts(args, &self.info.timeouts)
+    }
+
+    pub fn req_with_timeouts(
+        &self,
+        args: &T::Request,
+        timeouts: &ClientTimeouts,
+    ) -> Result<ServiceResult<T::Response>> {
+        Self::request_body(args, &self.info, timeouts, &Cancellation::default())
     }
 
     pub fn req_async(&self, args: T::Request) -> ClientResponse<T::Response> {
+        self.req_async_with_timeouts(args, self.info.timeouts.clone())
+    }
+
+    pub fn req_async_with_timeouts(
+        &self,
+        args: T::Request,
+        timeouts: ClientTimeouts,
+    ) -> ClientResponse<T::Response> {
         let info = Arc::clone(&self.info);
+        let cancellation = Arc::new(Cancellation::default());
+        let (tx, rx) = bounded(1);
+        {
+            let cancellation = Arc::clone(&cancellation);
+            thread::spawn(move || {
+                // The response might have been dropped without being read
+                let _ = tx.send(Self::request_body(&args, &info, &timeouts, &cancellation));
+            });
+        }
         ClientResponse {
-            handle: thread::spawn(move || {
-                Self::request_body(&args, &info.uri, &info.caller_id, &info.service)
-            }),
+            response: rx,
+            cancellation,
         }
     }
 
+    /// Asks the service server for its connection header, without performing a request.
+    pub fn probe_headers(&self) -> Result<HashMap<String, String>> {
+        let info = &self.info;
+        let cancellation = Cancellation::default();
+        let mut stream = info.connect(info.timeouts.connect, &cancellation)?;
+        let mut stream = Deadline::new(&mut stream, info.timeouts.header);
+        let mut fields = HashMap::<String, String>::new();
+        fields.insert(String::from("callerid"), info.caller_id.clone());
+        fields.insert(String::from("service"), info.service.clone());
+        fields.insert(String::from("md5sum"), T::md5sum());
+        fields.insert(String::from("probe"), String::from("1"));
+        encode(&mut stream, &fields)
+            .and_then(|_| decode(&mut stream))
+            .map_err(|err| classify_error(err.into(), "exchanging headers", &cancellation))
+    }
+
+    /// Probes the service server, reporting its type and whether its checksum matches `T`.
+    ///
+    /// No request gets sent, so this is safe to use before calling a service.
+    pub fn probe(&self) -> Result<ServiceProbe> {
+        ServiceProbe::from_headers(self.probe_headers()?, &T::md5sum())
+    }
+
     fn request_body(
         args: &T::Request,
-        uri: &str,
-        caller_id: &str,
-        service: &str,
+        info: &ClientInfo,
+        timeouts: &ClientTimeouts,
+        cancellation: &Cancellation,
     ) -> Result<ServiceResult<T::Response>> {
-        let trimmed_uri = uri.trim_start_matches("rosrpc://");
-        let mut stream = connect_to_tcp_with_multiple_attempts(trimmed_uri, 15)
-            .chain_err(|| ErrorKind::ServiceConnectionFail(service.into(), uri.into()))?;
+        let ClientInfo {
+            ref caller_id,
+            ref service,
+            max_message_size,
+            ..
+        } = *info;
+        let mut stream = info.connect(timeouts.connect, cancellation)?;
+        cancellation.track(&stream)?;
 
         // Service request starts by exchanging connection headers
-        exchange_headers::<T, _>(&mut stream, caller_id, service)?;
+        exchange_headers::<T, _>(
+            &mut Deadline::new(&mut stream, timeouts.header),
+            caller_id,
+            service,
+        )
+        .map_err(|err| classify_error(err, "exchanging headers", cancellation))?;
+
+        Self::exchange_messages(
+            args,
+            &mut Deadline::new(&mut stream, timeouts.response),
+            max_message_size,
+        )
+        .map_err(|err| classify_error(err, "waiting for response", cancellation))
+    }
 
+    fn exchange_messages<S: Read + Write>(
+        args: &T::Request,
+        mut stream: &mut S,
+        max_message_size: usize,
+    ) -> Result<ServiceResult<T::Response>> {
         let mut writer = io::Cursor::new(Vec::with_capacity(128));
         // skip the first 4 bytes that will contain the message length
         writer.set_position(4);
@@ -127,15 +616,18 @@ impl<T: ServicePair> Client<T> {
         stream.write_all(&writer.into_inner())?;
 
         // Service responds with a boolean byte, signalling success
-        let success = read_verification_byte(&mut stream)
-            .chain_err(|| ErrorKind::ServiceResponseInterruption)?;
+        let success = read_verification_byte(&mut stream).map_err(|err| {
+            if is_timeout(&err) {
+                err.into()
+            } else {
+                Error::with_chain(err, ErrorKind::ServiceResponseInterruption)
+            }
+        })?;
         Ok(if success {
             // Decode response as response type upon success
-
-            // TODO: validate response length
-            let _length = stream.read_u32::<LittleEndian>();
-
-            let data = RosMsg::decode(&mut stream)?;
+            let length = frame::read_length(&mut stream, max_message_size)?;
+            let body = frame::read_body(&mut stream, length)?;
+            let data = frame::decode_body(&body)?;
 
             let mut dump = vec![];
             if let Err(err) = stream.read_to_end(&mut dump) {
@@ -145,7 +637,10 @@ impl<T: ServicePair> Client<T> {
             Ok(data)
         } else {
             // Decode response as string upon failure
-            let data = RosMsg::decode(&mut stream)?;
+            let length = frame::read_length(&mut stream, max_message_size)?;
+            let body = frame::read_body(&mut stream, length)?;
+            let data = String::from_utf8(body)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
 
             let mut dump = vec![];
             if let Err(err) = stream.read_to_end(&mut dump) {
@@ -196,3 +691,137 @@ where
     write_request::<T, U>(stream, caller_id, service)?;
     read_response::<T, U>(stream)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RawMessage;
+    use std::net::TcpListener;
+
+    // Accepts connections, but never answers them
+    fn unresponsive_server() -> (TcpListener, Client<RawMessage>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("rosrpc://{}", listener.local_addr().unwrap());
+        let client = Client::new("/caller", &uri, "/service");
+        (listener, client)
+    }
+
+    #[test]
+    fn times_out_waiting_for_header() {
+        let (_listener, client) = unresponsive_server();
+        let timeouts = ClientTimeouts::default().header(Some(Duration::from_millis(50)));
+        let err = client
+            .req_with_timeouts(&RawMessage(vec![0, 0, 0, 0]), &timeouts)
+            .unwrap_err();
+        match err.kind() {
+            ErrorKind::ServiceCallTimeout(stage) => assert_eq!(stage, "exchanging headers"),
+            kind => panic!("Expected timeout, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn timeout_limits_whole_stage_of_trickling_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("rosrpc://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // Promise a long header, then send it a byte at a time
+            stream.write_all(&[100, 0, 0, 0]).unwrap();
+            while stream.write_all(&[0]).is_ok() {
+                thread::sleep(Duration::from_millis(20));
+            }
+        });
+        let client = Client::<RawMessage>::new("/caller", &uri, "/service");
+        let timeouts = ClientTimeouts::default().header(Some(Duration::from_millis(100)));
+        let start = Instant::now();
+        match client
+            .req_with_timeouts(&RawMessage(vec![0, 0, 0, 0]), &timeouts)
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::ServiceCallTimeout(_) => {}
+            kind => panic!("Expected timeout, got {:?}", kind),
+        }
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn looks_service_up_again_after_failing_to_connect() {
+        let dead_uri = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("rosrpc://{}", listener.local_addr().unwrap())
+        };
+        let (_listener, moved) = unresponsive_server();
+        let moved_uri = moved.uri();
+        let client = Client::<RawMessage>::new("/caller", &dead_uri, "/service")
+            .with_lookup(move || Some(moved_uri.clone()))
+            .with_retry_policy(RetryPolicy::no_retries());
+        let timeouts = ClientTimeouts::default().header(Some(Duration::from_millis(50)));
+        // Timing out on the header means that connecting to the moved server worked
+        match client
+            .req_with_timeouts(&RawMessage(vec![0, 0, 0, 0]), &timeouts)
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::ServiceCallTimeout(_) => {}
+            kind => panic!("Expected timeout, got {:?}", kind),
+        }
+        assert_eq!(client.uri(), moved.uri());
+        assert!(client.is_available());
+    }
+
+    #[test]
+    fn reports_missing_service() {
+        let (_listener, client) = unresponsive_server();
+        let client = client.with_lookup(|| None);
+        assert!(!client.exists());
+        assert!(!client.is_available());
+    }
+
+    #[test]
+    fn probe_reports_checksum_match() {
+        let headers = |md5sum: &str| {
+            let mut headers = HashMap::new();
+            headers.insert("callerid".into(), "/server".into());
+            headers.insert("md5sum".into(), md5sum.into());
+            headers.insert("type".into(), "pkg/Srv".into());
+            headers
+        };
+        let probe = ServiceProbe::from_headers(headers("abc"), "abc").unwrap();
+        assert_eq!(probe.caller_id, "/server");
+        assert_eq!(probe.msg_type, "pkg/Srv");
+        assert!(probe.matches);
+        assert!(!ServiceProbe::from_headers(headers("abc"), "def").unwrap().matches);
+        assert!(ServiceProbe::from_headers(headers("abc"), "*").unwrap().matches);
+
+        let mut incomplete = headers("abc");
+        incomplete.remove("type");
+        ServiceProbe::from_headers(incomplete, "abc").unwrap_err();
+    }
+
+    #[test]
+    fn cancels_pending_call() {
+        let (_listener, client) = unresponsive_server();
+        let response = client.req_async(RawMessage(vec![0, 0, 0, 0]));
+        thread::sleep(Duration::from_millis(50));
+        response.cancel();
+        match response.read().unwrap_err().kind() {
+            ErrorKind::ServiceCallCancelled => {}
+            kind => panic!("Expected cancellation, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn read_timeout_cancels_call() {
+        let (_listener, client) = unresponsive_server();
+        let response = client.req_async(RawMessage(vec![0, 0, 0, 0]));
+        match response
+            .read_timeout(Duration::from_millis(50))
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::ServiceCallTimeout(_) => {}
+            kind => panic!("Expected timeout, got {:?}", kind),
+        }
+    }
+}
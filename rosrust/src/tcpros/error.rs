@@ -21,6 +21,10 @@ error_chain! {
             description("Data field within header missing")
             display("Data field '{}' within header missing", field)
         }
+        TopicTypeMismatch(mismatch: Box<super::TypeMismatch>) {
+            description("Publisher message type does not match subscriber")
+            display("{}", mismatch)
+        }
         MessageTypeMismatch(expected: String, actual: String) {
             description("Cannot publish with multiple message types")
             display("Cannot publish '{}' data on '{}' publisher", actual, expected)
@@ -29,6 +33,22 @@ error_chain! {
             description("Data stream interrupted while reading service response")
             display("Data stream interrupted while reading service response")
         }
+        MessageTooLarge(length: usize, limit: usize) {
+            description("Message exceeds the maximum size")
+            display("Message of {} bytes exceeds the maximum size of {} bytes", length, limit)
+        }
+        MessageLengthMismatch(declared: usize, consumed: usize) {
+            description("Message length does not match its contents")
+            display("Message declared {} bytes, but decoding consumed {} bytes", declared, consumed)
+        }
+        ServiceCallTimeout(stage: String) {
+            description("Service call timed out")
+            display("Service call timed out while {}", stage)
+        }
+        ServiceCallCancelled {
+            description("Service call was cancelled")
+            display("Service call was cancelled")
+        }
         ServiceResponseUnknown {
             description("Unknown error caused service response to panic")
             display("Unknown error caused service response to panic")
@@ -0,0 +1,100 @@
+use super::error::{ErrorKind, Result};
+use crate::rosmsg::RosMsg;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{self, Read};
+
+/// Largest message accepted from a peer, unless configured otherwise.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 1 << 30;
+
+/// Reads the length prefix of a frame, rejecting lengths over the limit.
+pub fn read_length<R: Read>(stream: &mut R, max_size: usize) -> Result<usize> {
+    let length = stream.read_u32::<LittleEndian>()? as usize;
+    if length > max_size {
+        bail!(ErrorKind::MessageTooLarge(length, max_size));
+    }
+    Ok(length)
+}
+
+/// Reads the body of a frame with the given length.
+///
+/// The buffer only grows as data arrives, so a peer cannot force a large allocation
+/// by declaring a length it never sends.
+pub fn read_body<R: Read>(stream: &mut R, length: usize) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    stream.take(length as u64).read_to_end(&mut body)?;
+    if body.len() != length {
+        bail!(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Stream ended before the declared message length",
+        ));
+    }
+    Ok(body)
+}
+
+/// Reads a whole frame, keeping the length prefix at the start of the returned buffer.
+pub fn read_frame<R: Read>(stream: &mut R, max_size: usize) -> Result<Vec<u8>> {
+    let length = read_length(stream, max_size)?;
+    let mut frame = Vec::new();
+    frame.write_u32::<LittleEndian>(length as u32)?;
+    stream.take(length as u64).read_to_end(&mut frame)?;
+    if frame.len() != length + 4 {
+        bail!(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Stream ended before the declared message length",
+        ));
+    }
+    Ok(frame)
+}
+
+/// Decodes a message from a frame body, checking that decoding consumed all of it.
+pub fn decode_body<T: RosMsg>(body: &[u8]) -> Result<T> {
+    let mut cursor = io::Cursor::new(body);
+    let value = T::decode(&mut cursor)?;
+    let consumed = cursor.position() as usize;
+    if consumed != body.len() {
+        bail!(ErrorKind::MessageLengthMismatch(body.len(), consumed));
+    }
+    Ok(value)
+}
+
+/// Decodes a message from a whole frame, as returned by `read_frame`.
+pub fn decode_frame<T: RosMsg>(frame: &[u8]) -> Result<T> {
+    if frame.len() < 4 {
+        bail!(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Frame is shorter than its length prefix",
+        ));
+    }
+    decode_body(&frame[4..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tcpros::error::Error;
+
+    #[test]
+    fn rejects_lengths_over_limit() {
+        let input = [0, 0, 0, 64, 1, 2, 3];
+        match read_frame(&mut io::Cursor::new(input), 1024) {
+            Err(Error(ErrorKind::MessageTooLarge(length, limit), _)) => {
+                assert_eq!(length, 1 << 30);
+                assert_eq!(limit, 1024);
+            }
+            other => panic!("Expected oversized frame error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decoding_must_consume_whole_body() {
+        let value: u32 = decode_frame(&[4, 0, 0, 0, 7, 0, 0, 0]).unwrap();
+        assert_eq!(value, 7);
+        match decode_frame::<u32>(&[6, 0, 0, 0, 7, 0, 0, 0, 1, 2]) {
+            Err(Error(ErrorKind::MessageLengthMismatch(declared, consumed), _)) => {
+                assert_eq!(declared, 6);
+                assert_eq!(consumed, 4);
+            }
+            other => panic!("Expected length mismatch, got {:?}", other),
+        }
+    }
+}
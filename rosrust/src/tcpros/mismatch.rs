@@ -0,0 +1,190 @@
+use super::Message;
+use crate::dynamic::{DynamicMsg, FieldDifference};
+use crate::util::FAILED_TO_LOCK;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Publisher whose message type does not match the type expected by a subscriber.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeMismatch {
+    pub topic: String,
+    /// Caller ID of the publisher, or its address if it did not send one.
+    pub publisher: String,
+    pub expected_type: String,
+    pub expected_md5sum: String,
+    pub actual_type: Option<String>,
+    pub actual_md5sum: Option<String>,
+    /// Reason given by the publisher, if it rejected the connection itself.
+    pub reason: Option<String>,
+    /// Field-level differences, if the publisher sent its message definition.
+    pub differences: Vec<FieldDifference>,
+}
+
+impl TypeMismatch {
+    /// Checks the publisher's connection header against the subscribed message type.
+    pub(crate) fn detect<T: Message>(
+        topic: &str,
+        publisher: &str,
+        headers: &HashMap<String, String>,
+    ) -> Option<Self> {
+        let reason = headers.get("error").cloned();
+        let actual_md5sum = headers.get("md5sum").cloned();
+        let actual_type = headers.get("type").cloned();
+        let differs = |expected: String, actual: &Option<String>| {
+            expected != "*" && actual.as_ref().is_some_and(|actual| *actual != expected)
+        };
+        if reason.is_none()
+            && !differs(T::md5sum(), &actual_md5sum)
+            && !differs(T::msg_type(), &actual_type)
+        {
+            return None;
+        }
+        let differences = match (
+            DynamicMsg::new(&T::msg_type(), &T::msg_definition()),
+            DynamicMsg::from_headers(headers),
+        ) {
+            (Ok(expected), Ok(actual)) => expected.diff(&actual),
+            _ => vec![],
+        };
+        Some(Self {
+            topic: topic.into(),
+            publisher: headers
+                .get("callerid")
+                .cloned()
+                .unwrap_or_else(|| publisher.into()),
+            expected_type: T::msg_type(),
+            expected_md5sum: T::md5sum(),
+            actual_type,
+            actual_md5sum,
+            reason,
+            differences,
+        })
+    }
+}
+
+impl fmt::Display for TypeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Publisher '{}' of topic '{}' does not match the subscribed type '{}' [{}]",
+            self.publisher, self.topic, self.expected_type, self.expected_md5sum
+        )?;
+        if let (Some(actual_type), Some(actual_md5sum)) = (&self.actual_type, &self.actual_md5sum) {
+            write!(f, ", it sends '{}' [{}]", actual_type, actual_md5sum)?;
+        }
+        if let Some(ref reason) = self.reason {
+            write!(f, ". Publisher reported: {}", reason)?;
+        }
+        if !self.differences.is_empty() {
+            let differences = self
+                .differences
+                .iter()
+                .map(|v| format!("{}", v))
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(f, ". Differences: {}", differences)?;
+        }
+        Ok(())
+    }
+}
+
+pub type TypeMismatchCallback = Arc<dyn Fn(&TypeMismatch) + Send + Sync>;
+
+/// Latest mismatch of each publisher of a subscription, and the callbacks notified of them.
+#[derive(Clone, Default)]
+pub(crate) struct TypeMismatches {
+    inner: Arc<Mutex<TypeMismatchesInner>>,
+}
+
+#[derive(Default)]
+struct TypeMismatchesInner {
+    mismatches: Vec<TypeMismatch>,
+    callbacks: Vec<TypeMismatchCallback>,
+}
+
+impl TypeMismatches {
+    pub fn record(&self, mismatch: TypeMismatch) {
+        let callbacks = {
+            let mut inner = self.inner.lock().expect(FAILED_TO_LOCK);
+            inner
+                .mismatches
+                .retain(|item| item.publisher != mismatch.publisher);
+            inner.mismatches.push(mismatch.clone());
+            inner.callbacks.clone()
+        };
+        for callback in callbacks {
+            callback(&mismatch);
+        }
+    }
+
+    pub fn get(&self) -> Vec<TypeMismatch> {
+        self.inner.lock().expect(FAILED_TO_LOCK).mismatches.clone()
+    }
+
+    /// Adds a callback, which is also called with all mismatches recorded so far.
+    pub fn add_callback(&self, callback: TypeMismatchCallback) {
+        let mismatches = {
+            let mut inner = self.inner.lock().expect(FAILED_TO_LOCK);
+            inner.callbacks.push(Arc::clone(&callback));
+            inner.mismatches.clone()
+        };
+        for mismatch in &mismatches {
+            callback(mismatch);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::rosgraph_msgs::Clock;
+
+    fn publisher_headers(md5sum: &str, definition: &str) -> HashMap<String, String> {
+        let mut headers = HashMap::new();
+        headers.insert("callerid".into(), "/talker".into());
+        headers.insert("type".into(), "rosgraph_msgs/Clock".into());
+        headers.insert("md5sum".into(), md5sum.into());
+        headers.insert("message_definition".into(), definition.into());
+        headers
+    }
+
+    #[test]
+    fn detects_mismatch_with_field_differences() {
+        let headers = publisher_headers(&Clock::md5sum(), &Clock::msg_definition());
+        assert!(TypeMismatch::detect::<Clock>("/clock", "addr", &headers).is_none());
+
+        let headers = publisher_headers("0123", "time clock\nuint32 seq\n");
+        let mismatch = TypeMismatch::detect::<Clock>("/clock", "addr", &headers).unwrap();
+        assert_eq!("/talker", mismatch.publisher);
+        assert_eq!(Some("0123".into()), mismatch.actual_md5sum);
+        assert_eq!(
+            vec![FieldDifference::Added {
+                path: "seq".into(),
+                datatype: "uint32".into(),
+            }],
+            mismatch.differences
+        );
+        assert!(mismatch
+            .to_string()
+            .ends_with("Differences: added field 'seq' of type 'uint32'"));
+    }
+
+    #[test]
+    fn keeps_latest_mismatch_per_publisher() {
+        let mismatches = TypeMismatches::default();
+        let headers = publisher_headers("0123", "time clock\n");
+        let mismatch = TypeMismatch::detect::<Clock>("/clock", "addr", &headers).unwrap();
+        mismatches.record(mismatch.clone());
+        mismatches.record(mismatch.clone());
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let target = Arc::clone(&seen);
+        mismatches.add_callback(Arc::new(move |mismatch: &TypeMismatch| {
+            target.lock().unwrap().push(mismatch.publisher.clone())
+        }));
+        mismatches.record(mismatch);
+        assert_eq!(1, mismatches.get().len());
+        assert_eq!(vec!["/talker", "/talker"], *seen.lock().unwrap());
+    }
+}
@@ -1,8 +1,11 @@
-pub use self::client::{Client, ClientResponse};
+pub use self::client::{Client, ClientResponse, ClientTimeouts, RetryPolicy, ServiceProbe};
 pub use self::error::Error;
-pub use self::publisher::{Publisher, PublisherStream};
-pub use self::service::Service;
+pub use self::frame::DEFAULT_MAX_MESSAGE_SIZE;
+pub use self::mismatch::{TypeMismatch, TypeMismatchCallback};
+pub use self::publisher::{Publisher, PublisherStream, SingleSubscriberPublisher};
+pub use self::service::{Service, ServiceConcurrency, ServiceEvent};
 pub use self::subscriber::Subscriber;
+pub use self::transport_hints::{Transport, TransportHints};
 use crate::rosmsg::RosMsg;
 
 use crate::Clock;
@@ -11,10 +14,13 @@ use std::sync::Arc;
 
 mod client;
 pub mod error;
+mod frame;
 mod header;
+mod mismatch;
 mod publisher;
 mod service;
 mod subscriber;
+mod transport_hints;
 mod util;
 
 pub type ServiceResult<T> = Result<T, String>;
@@ -1,14 +1,18 @@
 use super::error::{ErrorKind, Result, ResultExt};
 use super::header;
-use super::util::streamfork::{fork, DataStream, TargetList};
+use super::util::streamfork::{fork, DataStream, TargetHandle, TargetList};
 use super::util::tcpconnection;
 use super::{Message, Topic};
 use crate::util::FAILED_TO_LOCK;
+use crate::RawMessageDescription;
+use crossbeam::channel::{unbounded, Sender};
 use log::error;
 use std;
 use std::collections::HashMap;
+use std::io::Write;
 use std::net::{TcpListener, TcpStream, ToSocketAddrs};
 use std::sync::{atomic, Arc, Mutex};
+use std::thread;
 
 pub struct Publisher {
     subscriptions: DataStream,
@@ -17,6 +21,81 @@ pub struct Publisher {
     last_message: Arc<Mutex<Arc<Vec<u8>>>>,
     queue_size: usize,
     exists: Arc<atomic::AtomicBool>,
+    latching: Arc<atomic::AtomicBool>,
+    links: Arc<Mutex<SubscriberLinks>>,
+}
+
+type StatusCallback = Arc<dyn Fn(TargetHandle) + Send + Sync>;
+
+type Notification = (Vec<StatusCallback>, TargetHandle);
+
+/// Connected subscribers, and callbacks for them connecting and disconnecting.
+///
+/// Callbacks are called on a thread of their own, in the order of the events, so they
+/// block neither accepting new subscribers nor sending messages.
+struct SubscriberLinks {
+    targets: Vec<TargetHandle>,
+    on_connect: Vec<StatusCallback>,
+    on_disconnect: Vec<StatusCallback>,
+    notifications: Sender<Notification>,
+}
+
+impl SubscriberLinks {
+    fn new() -> Self {
+        let (notifications, pending) = unbounded::<Notification>();
+        // Stops once the links, and thus the sender, are dropped
+        thread::spawn(move || {
+            for (callbacks, target) in pending {
+                for callback in callbacks {
+                    callback(target.clone());
+                }
+            }
+        });
+        Self {
+            targets: vec![],
+            on_connect: vec![],
+            on_disconnect: vec![],
+            notifications,
+        }
+    }
+
+    /// Records a subscriber, which must happen before it can receive data and fail.
+    fn connect(links: &Mutex<SubscriberLinks>, target: TargetHandle) {
+        let mut links = links.lock().expect(FAILED_TO_LOCK);
+        links.targets.push(target.clone());
+        let callbacks = links.on_connect.clone();
+        links.notify(callbacks, target);
+    }
+
+    fn disconnect(links: &Mutex<SubscriberLinks>, target: TargetHandle) {
+        let mut links = links.lock().expect(FAILED_TO_LOCK);
+        links.targets.retain(|v| v.id() != target.id());
+        let callbacks = links.on_disconnect.clone();
+        links.notify(callbacks, target);
+    }
+
+    fn notify(&self, callbacks: Vec<StatusCallback>, target: TargetHandle) {
+        if !callbacks.is_empty() {
+            // The notification thread only stops after the links are dropped
+            let _ = self.notifications.send((callbacks, target));
+        }
+    }
+
+    /// Adds callbacks, calling the connection callback for already connected subscribers.
+    ///
+    /// Those calls are queued like any other event, so they precede later disconnections.
+    fn add_callbacks(
+        links: &Mutex<SubscriberLinks>,
+        on_connect: StatusCallback,
+        on_disconnect: StatusCallback,
+    ) {
+        let mut links = links.lock().expect(FAILED_TO_LOCK);
+        links.on_connect.push(Arc::clone(&on_connect));
+        links.on_disconnect.push(on_disconnect);
+        for target in links.targets.clone() {
+            links.notify(vec![Arc::clone(&on_connect)], target);
+        }
+    }
 }
 
 impl Drop for Publisher {
@@ -25,9 +104,13 @@ impl Drop for Publisher {
     }
 }
 
-fn match_concrete_headers<T: Message>(fields: &HashMap<String, String>, topic: &str) -> Result<()> {
-    header::match_field(fields, "md5sum", &T::md5sum())?;
-    header::match_field(fields, "type", &T::msg_type())?;
+fn match_concrete_headers(
+    fields: &HashMap<String, String>,
+    topic: &str,
+    message_description: &RawMessageDescription,
+) -> Result<()> {
+    header::match_field(fields, "md5sum", &message_description.md5sum)?;
+    header::match_field(fields, "type", &message_description.msg_type)?;
     header::match_field(fields, "topic", topic)?;
     Ok(())
 }
@@ -39,54 +122,125 @@ fn match_wildcard_headers(fields: &HashMap<String, String>, topic: &str) -> Resu
     Ok(())
 }
 
-fn read_request<T: Message, U: std::io::Read>(mut stream: &mut U, topic: &str) -> Result<()> {
+fn read_request<U: std::io::Read>(
+    mut stream: &mut U,
+    topic: &str,
+    message_description: &RawMessageDescription,
+) -> Result<HashMap<String, String>> {
     let fields = header::decode(&mut stream)?;
-    if let Err(err) = match_concrete_headers::<T>(&fields, topic) {
+    if let Err(err) = match_concrete_headers(&fields, topic, message_description) {
         match_wildcard_headers(&fields, topic).map_err(|_| err)?;
     }
-    if fields.get("callerid").is_none() {
+    if !fields.contains_key("callerid") {
         bail!(ErrorKind::HeaderMissingField("callerid".into()));
     }
-    Ok(())
+    Ok(fields)
+}
+
+fn write_response<U: std::io::Write>(
+    stream: &mut U,
+    caller_id: &str,
+    latching: bool,
+    message_description: &RawMessageDescription,
+) -> Result<()> {
+    write_header(stream, caller_id, latching, None, message_description)
+}
+
+/// Tells the subscriber why it was rejected, along with the published message description.
+fn write_rejection<U: std::io::Write>(
+    stream: &mut U,
+    caller_id: &str,
+    reason: &str,
+    message_description: &RawMessageDescription,
+) -> Result<()> {
+    write_header(stream, caller_id, false, Some(reason), message_description)
 }
 
-fn write_response<T: Message, U: std::io::Write>(mut stream: &mut U) -> Result<()> {
+fn write_header<U: std::io::Write>(
+    mut stream: &mut U,
+    caller_id: &str,
+    latching: bool,
+    error: Option<&str>,
+    message_description: &RawMessageDescription,
+) -> Result<()> {
     let mut fields = HashMap::<String, String>::new();
-    fields.insert(String::from("md5sum"), T::md5sum());
-    fields.insert(String::from("type"), T::msg_type());
+    if let Some(error) = error {
+        fields.insert(String::from("error"), error.into());
+    }
+    fields.insert(String::from("md5sum"), message_description.md5sum.clone());
+    fields.insert(String::from("type"), message_description.msg_type.clone());
+    fields.insert(
+        String::from("message_definition"),
+        message_description.msg_definition.clone(),
+    );
+    fields.insert(String::from("callerid"), caller_id.into());
+    fields.insert(
+        String::from("latching"),
+        String::from(if latching { "1" } else { "0" }),
+    );
     header::encode(&mut stream, &fields)?;
     Ok(())
 }
 
-fn exchange_headers<T, U>(mut stream: &mut U, topic: &str) -> Result<()>
+fn exchange_headers<U>(
+    mut stream: &mut U,
+    topic: &str,
+    caller_id: &str,
+    latching: bool,
+    message_description: &RawMessageDescription,
+) -> Result<HashMap<String, String>>
 where
-    T: Message,
     U: std::io::Write + std::io::Read,
 {
-    read_request::<T, U>(&mut stream, topic)?;
-    write_response::<T, U>(&mut stream)
+    let fields = match read_request(&mut stream, topic, message_description) {
+        Ok(fields) => fields,
+        Err(err) => {
+            // The connection is dropped anyway, so a failure to explain why is not relevant
+            write_rejection(
+                &mut stream,
+                caller_id,
+                &err.to_string(),
+                message_description,
+            )
+            .ok();
+            return Err(err);
+        }
+    };
+    write_response(&mut stream, caller_id, latching, message_description)?;
+    Ok(fields)
 }
 
-fn process_subscriber<T, U>(
+#[allow(clippy::too_many_arguments)]
+fn process_subscriber(
     topic: &str,
-    mut stream: U,
-    targets: &TargetList<U>,
+    mut stream: TcpStream,
+    targets: &TargetList<TcpStream>,
+    links: &Mutex<SubscriberLinks>,
+    latching: &atomic::AtomicBool,
     last_message: &Mutex<Arc<Vec<u8>>>,
-) -> tcpconnection::Feedback
-where
-    T: Message,
-    U: std::io::Read + std::io::Write + Send,
-{
-    let result = exchange_headers::<T, _>(&mut stream, topic)
+    caller_id: &str,
+    message_description: &RawMessageDescription,
+) -> tcpconnection::Feedback {
+    let latching = latching.load(atomic::Ordering::SeqCst);
+    let result = exchange_headers(&mut stream, topic, caller_id, latching, message_description)
         .chain_err(|| ErrorKind::TopicConnectionFail(topic.into()));
-    if let Err(err) = result {
-        let info = err
-            .iter()
-            .map(|v| format!("{}", v))
-            .collect::<Vec<_>>()
-            .join("\nCaused by:");
-        error!("{}", info);
-        return tcpconnection::Feedback::AcceptNextStream;
+    let fields = match result {
+        Ok(fields) => fields,
+        Err(err) => {
+            let info = err
+                .iter()
+                .map(|v| format!("{}", v))
+                .collect::<Vec<_>>()
+                .join("\nCaused by:");
+            error!("{}", info);
+            return tcpconnection::Feedback::AcceptNextStream;
+        }
+    };
+
+    if fields.get("tcp_nodelay").map(String::as_str) == Some("1") {
+        if let Err(err) = stream.set_nodelay(true) {
+            error!("Failed to set TCP_NODELAY for topic '{}': {}", topic, err);
+        }
     }
 
     if let Err(err) = stream.write_all(&last_message.lock().expect(FAILED_TO_LOCK)) {
@@ -94,7 +248,12 @@ where
         return tcpconnection::Feedback::AcceptNextStream;
     }
 
-    if targets.add(stream).is_err() {
+    let subscriber_id = fields.get("callerid").cloned().unwrap_or_default();
+    let target = targets.handle(&subscriber_id);
+    // Recorded before streamfork can drop it, so disconnection always follows connection
+    SubscriberLinks::connect(links, target.clone());
+    if targets.add(target.clone(), stream).is_err() {
+        SubscriberLinks::disconnect(links, target);
         // The TCP listener gets shut down when streamfork's thread deallocates.
         // This happens only when all the corresponding publisher streams get deallocated,
         // causing streamfork's data channel to shut down
@@ -105,9 +264,14 @@ where
 }
 
 impl Publisher {
-    pub fn new<T, U>(address: U, topic: &str, queue_size: usize) -> Result<Publisher>
+    pub fn new<U>(
+        address: U,
+        topic: &str,
+        queue_size: usize,
+        caller_id: &str,
+        message_description: RawMessageDescription,
+    ) -> Result<Publisher>
     where
-        T: Message,
         U: ToSocketAddrs,
     {
         let listener = TcpListener::bind(address)?;
@@ -116,19 +280,39 @@ impl Publisher {
         let publisher_exists = Arc::new(atomic::AtomicBool::new(true));
 
         let port = socket_address.port();
-        let (targets, data) = fork(queue_size);
+        let links = Arc::new(Mutex::new(SubscriberLinks::new()));
+        let (targets, data) = {
+            let links = Arc::clone(&links);
+            fork(queue_size, move |target| {
+                SubscriberLinks::disconnect(&links, target)
+            })
+        };
         let last_message = Arc::new(Mutex::new(Arc::new(Vec::new())));
+        let latching = Arc::new(atomic::AtomicBool::new(false));
 
         let iterate_handler = {
             let publisher_exists = publisher_exists.clone();
             let topic = String::from(topic);
             let last_message = Arc::clone(&last_message);
+            let links = Arc::clone(&links);
+            let latching = Arc::clone(&latching);
+            let caller_id = String::from(caller_id);
+            let message_description = message_description.clone();
 
             move |stream: TcpStream| {
                 if !publisher_exists.load(atomic::Ordering::SeqCst) {
                     return tcpconnection::Feedback::StopAccepting;
                 }
-                process_subscriber::<T, _>(&topic, stream, &targets, &last_message)
+                process_subscriber(
+                    &topic,
+                    stream,
+                    &targets,
+                    &links,
+                    &latching,
+                    &last_message,
+                    &caller_id,
+                    &message_description,
+                )
             }
         };
 
@@ -136,7 +320,7 @@ impl Publisher {
 
         let topic = Topic {
             name: String::from(topic),
-            msg_type: T::msg_type(),
+            msg_type: message_description.msg_type,
         };
 
         Ok(Publisher {
@@ -146,11 +330,17 @@ impl Publisher {
             last_message,
             queue_size,
             exists: publisher_exists,
+            latching,
+            links,
         })
     }
 
-    pub fn stream<T: Message>(&self, queue_size: usize) -> Result<PublisherStream<T>> {
-        let mut stream = PublisherStream::new(self)?;
+    pub fn stream<T: Message>(
+        &self,
+        queue_size: usize,
+        message_description: &RawMessageDescription,
+    ) -> Result<PublisherStream<T>> {
+        let mut stream = PublisherStream::new(self, message_description)?;
         stream.set_queue_size_max(queue_size);
         Ok(stream)
     }
@@ -170,15 +360,20 @@ pub struct PublisherStream<T: Message> {
     last_message: Arc<Mutex<Arc<Vec<u8>>>>,
     datatype: std::marker::PhantomData<T>,
     latching: bool,
+    advertised_latching: Arc<atomic::AtomicBool>,
+    topic: String,
+    links: Arc<Mutex<SubscriberLinks>>,
 }
 
 impl<T: Message> PublisherStream<T> {
-    fn new(publisher: &Publisher) -> Result<PublisherStream<T>> {
-        let msg_type = T::msg_type();
-        if publisher.topic.msg_type != msg_type {
+    fn new(
+        publisher: &Publisher,
+        message_description: &RawMessageDescription,
+    ) -> Result<PublisherStream<T>> {
+        if publisher.topic.msg_type != message_description.msg_type {
             bail!(ErrorKind::MessageTypeMismatch(
                 publisher.topic.msg_type.clone(),
-                msg_type,
+                message_description.msg_type.clone(),
             ));
         }
         let mut stream = PublisherStream {
@@ -186,6 +381,9 @@ impl<T: Message> PublisherStream<T> {
             datatype: std::marker::PhantomData,
             last_message: Arc::clone(&publisher.last_message),
             latching: false,
+            advertised_latching: Arc::clone(&publisher.latching),
+            topic: publisher.topic.name.clone(),
+            links: Arc::clone(&publisher.links),
         };
         stream.set_queue_size_max(publisher.queue_size);
         Ok(stream)
@@ -196,9 +394,25 @@ impl<T: Message> PublisherStream<T> {
         self.stream.get_target_count()
     }
 
+    /// Number of messages that were not yet written to subscribers.
+    #[inline]
+    pub fn pending(&self) -> usize {
+        self.stream.pending()
+    }
+
+    /// Once any stream of a topic latches, latching is advertised to new subscribers.
     #[inline]
     pub fn set_latching(&mut self, latching: bool) {
         self.latching = latching;
+        if latching {
+            self.advertised_latching
+                .store(true, atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[inline]
+    pub fn is_latching(&self) -> bool {
+        self.latching
     }
 
     #[inline]
@@ -211,10 +425,29 @@ impl<T: Message> PublisherStream<T> {
         self.stream.set_queue_size_max(queue_size);
     }
 
+    /// Adds callbacks for subscribers connecting and disconnecting.
+    ///
+    /// The connection callback is also called for subscribers that are already connected.
+    /// Callbacks run on a thread of the publisher, one at a time, so a slow callback delays
+    /// the following callbacks, but not new connections or published messages.
+    pub fn add_status_callbacks<F, G>(&self, on_connect: F, on_disconnect: G)
+    where
+        F: Fn(SingleSubscriberPublisher<T>) + Send + Sync + 'static,
+        G: Fn(SingleSubscriberPublisher<T>) + Send + Sync + 'static,
+    {
+        let topic = self.topic.clone();
+        let on_connect =
+            move |target| on_connect(SingleSubscriberPublisher::new(target, topic.clone()));
+        let topic = self.topic.clone();
+        let on_disconnect =
+            move |target| on_disconnect(SingleSubscriberPublisher::new(target, topic.clone()));
+        SubscriberLinks::add_callbacks(&self.links, Arc::new(on_connect), Arc::new(on_disconnect));
+    }
+
     pub fn send(&self, message: &T) -> Result<()> {
         let bytes = Arc::new(message.encode_vec()?);
 
-        if self.latching {
+        if self.is_latching() {
             *self.last_message.lock().expect(FAILED_TO_LOCK) = Arc::clone(&bytes);
         }
 
@@ -224,3 +457,134 @@ impl<T: Message> PublisherStream<T> {
         Ok(())
     }
 }
+
+/// Publisher that sends messages to a single subscriber of a topic.
+#[derive(Clone)]
+pub struct SingleSubscriberPublisher<T: Message> {
+    target: TargetHandle,
+    topic: String,
+    datatype: std::marker::PhantomData<T>,
+}
+
+impl<T: Message> SingleSubscriberPublisher<T> {
+    fn new(target: TargetHandle, topic: String) -> Self {
+        Self {
+            target,
+            topic,
+            datatype: std::marker::PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Name of the subscribing node.
+    #[inline]
+    pub fn caller_id(&self) -> &str {
+        self.target.caller_id()
+    }
+
+    /// Sends the message to this subscriber only.
+    ///
+    /// Messages sent after the subscriber disconnected are dropped.
+    pub fn send(&self, message: &T) -> Result<()> {
+        let bytes = Arc::new(message.encode_vec()?);
+        // The streamfork thread is gone only if the whole publisher is gone
+        if self.target.send(bytes).is_err() {
+            bail!(ErrorKind::TopicConnectionFail(self.topic.clone()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn description() -> RawMessageDescription {
+        RawMessageDescription {
+            msg_definition: "string data\n".into(),
+            md5sum: "992ce8a1687cec8c8bd883ec73ca41d1".into(),
+            msg_type: "std_msgs/String".into(),
+        }
+    }
+
+    #[test]
+    fn advertises_latching_in_header() {
+        for &(latching, expected) in &[(true, "1"), (false, "0")] {
+            let mut buffer = Vec::new();
+            write_response(&mut buffer, "/talker", latching, &description()).unwrap();
+            let fields = header::decode(&mut std::io::Cursor::new(buffer)).unwrap();
+            assert_eq!(Some(&String::from(expected)), fields.get("latching"));
+            assert_eq!(Some(&String::from("/talker")), fields.get("callerid"));
+        }
+    }
+
+    #[test]
+    fn reports_links_in_order_off_the_calling_thread() {
+        let links = Mutex::new(SubscriberLinks::new());
+        let (events_tx, events) = unbounded();
+        let caller = thread::current().id();
+        let on_connect = {
+            let events_tx = events_tx.clone();
+            move |target: TargetHandle| {
+                let off_thread = thread::current().id() != caller;
+                events_tx
+                    .send(("connect", target.id(), off_thread))
+                    .unwrap();
+            }
+        };
+        let on_disconnect = move |target: TargetHandle| {
+            let off_thread = thread::current().id() != caller;
+            events_tx
+                .send(("disconnect", target.id(), off_thread))
+                .unwrap();
+        };
+        SubscriberLinks::add_callbacks(&links, Arc::new(on_connect), Arc::new(on_disconnect));
+
+        let (targets, _data) = fork::<Vec<u8>, _>(1, |_| {});
+        let target = targets.handle("/listener");
+        let id = target.id();
+        SubscriberLinks::connect(&links, target.clone());
+        SubscriberLinks::disconnect(&links, target);
+        assert!(links.lock().unwrap().targets.is_empty());
+
+        let timeout = std::time::Duration::from_secs(5);
+        assert_eq!(("connect", id, true), events.recv_timeout(timeout).unwrap());
+        assert_eq!(
+            ("disconnect", id, true),
+            events.recv_timeout(timeout).unwrap()
+        );
+    }
+
+    #[test]
+    fn reports_existing_links_before_their_disconnection() {
+        let links = Mutex::new(SubscriberLinks::new());
+        let (targets, _data) = fork::<Vec<u8>, _>(1, |_| {});
+        let target = targets.handle("/listener");
+        SubscriberLinks::connect(&links, target.clone());
+
+        let (events_tx, events) = unbounded();
+        let caller = thread::current().id();
+        let on_connect = {
+            let events_tx = events_tx.clone();
+            move |_: TargetHandle| {
+                let off_thread = thread::current().id() != caller;
+                // A slow callback must still finish before the disconnection gets reported
+                thread::sleep(std::time::Duration::from_millis(50));
+                events_tx.send(("connect", off_thread)).unwrap();
+            }
+        };
+        let on_disconnect = move |_: TargetHandle| {
+            events_tx.send(("disconnect", true)).unwrap();
+        };
+        SubscriberLinks::add_callbacks(&links, Arc::new(on_connect), Arc::new(on_disconnect));
+        SubscriberLinks::disconnect(&links, target);
+
+        let timeout = std::time::Duration::from_secs(5);
+        assert_eq!(("connect", true), events.recv_timeout(timeout).unwrap());
+        assert_eq!(("disconnect", true), events.recv_timeout(timeout).unwrap());
+    }
+}
@@ -1,22 +1,107 @@
-use super::error::{ErrorKind, Result};
+use super::error::{Error, ErrorKind, Result};
+use super::frame;
 use super::header;
 use super::util::tcpconnection;
 use super::{ServicePair, ServiceResult};
 use crate::rosmsg::{encode_str, RosMsg};
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use crate::time::Time;
+use crate::Clock;
+use byteorder::WriteBytesExt;
+use crossbeam::channel::{unbounded, Sender};
 use log::error;
 use std;
 use std::collections::HashMap;
 use std::io;
 use std::net::{TcpListener, TcpStream};
-use std::sync::{atomic, Arc};
+use std::sync::{atomic, Arc, Weak};
 use std::thread;
 
+/// Details of a service call, passed to handlers alongside the request.
+#[derive(Clone, Debug)]
+pub struct ServiceEvent {
+    caller_id: String,
+    header: HashMap<String, String>,
+    receipt_time: Time,
+}
+
+impl ServiceEvent {
+    /// Name of the calling node.
+    #[inline]
+    pub fn caller_id(&self) -> &str {
+        &self.caller_id
+    }
+
+    /// Connection header sent by the client.
+    #[inline]
+    pub fn header(&self) -> &HashMap<String, String> {
+        &self.header
+    }
+
+    /// Time of receiving the connection header, measured with the node's clock.
+    #[inline]
+    pub fn receipt_time(&self) -> &Time {
+        &self.receipt_time
+    }
+}
+
+/// Limits how many requests of a service get handled at the same time.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ServiceConcurrency {
+    /// Every request is handled in its own thread.
+    #[default]
+    Unbounded,
+    /// Requests are handled one at a time, in the order they arrive.
+    Serial,
+    /// Requests are handled by a pool with the given number of threads.
+    Bounded(usize),
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+enum Dispatcher {
+    Unbounded,
+    Pool(Sender<Job>),
+}
+
+impl Dispatcher {
+    fn new(concurrency: ServiceConcurrency) -> Self {
+        let workers = match concurrency {
+            ServiceConcurrency::Unbounded => return Dispatcher::Unbounded,
+            ServiceConcurrency::Serial => 1,
+            ServiceConcurrency::Bounded(workers) => workers.max(1),
+        };
+        let (tx, rx) = unbounded::<Job>();
+        for _ in 0..workers {
+            let rx = rx.clone();
+            // Workers stop once the dispatcher, owned by the service, is dropped
+            thread::spawn(move || {
+                for job in rx {
+                    job();
+                }
+            });
+        }
+        Dispatcher::Pool(tx)
+    }
+
+    /// Runs the job, either right away in the calling connection thread, or in the pool.
+    fn dispatch(&self, job: Job) {
+        match *self {
+            Dispatcher::Unbounded => job(),
+            Dispatcher::Pool(ref tx) => {
+                if tx.send(job).is_err() {
+                    error!("Service worker pool stopped unexpectedly");
+                }
+            }
+        }
+    }
+}
+
 pub struct Service {
     pub api: String,
     pub msg_type: String,
     pub service: String,
     exists: Arc<atomic::AtomicBool>,
+    _dispatcher: Arc<Dispatcher>,
 }
 
 impl Drop for Service {
@@ -26,34 +111,48 @@ impl Drop for Service {
 }
 
 impl Service {
+    #[allow(clippy::too_many_arguments)]
     pub fn new<T, F>(
         hostname: &str,
         bind_address: &str,
         port: u16,
         service: &str,
         node_name: &str,
+        clock: Arc<dyn Clock>,
+        concurrency: ServiceConcurrency,
+        max_message_size: usize,
         handler: F,
     ) -> Result<Service>
     where
         T: ServicePair,
-        F: Fn(T::Request) -> ServiceResult<T::Response> + Send + Sync + 'static,
+        F: Fn(T::Request, ServiceEvent) -> ServiceResult<T::Response> + Send + Sync + 'static,
     {
         let listener = TcpListener::bind((bind_address, port))?;
         let socket_address = listener.local_addr()?;
         let api = format!("rosrpc://{}:{}", hostname, socket_address.port());
 
         let service_exists = Arc::new(atomic::AtomicBool::new(true));
+        let dispatcher = Arc::new(Dispatcher::new(concurrency));
 
         let iterate_handler = {
             let service_exists = service_exists.clone();
             let service = String::from(service);
             let node_name = String::from(node_name);
             let handler = Arc::new(handler);
+            let dispatcher = Arc::downgrade(&dispatcher);
             move |stream: TcpStream| {
                 if !service_exists.load(atomic::Ordering::SeqCst) {
                     return tcpconnection::Feedback::StopAccepting;
                 }
-                consume_client::<T, _, _>(&service, &node_name, Arc::clone(&handler), stream);
+                consume_client::<T, _, _>(
+                    &service,
+                    &node_name,
+                    &*clock,
+                    &dispatcher,
+                    max_message_size,
+                    Arc::clone(&handler),
+                    stream,
+                );
                 return tcpconnection::Feedback::AcceptNextStream;
             }
         };
@@ -65,20 +164,28 @@ impl Service {
             msg_type: T::msg_type(),
             service: String::from(service),
             exists: service_exists,
+            _dispatcher: dispatcher,
         })
     }
 }
 
 enum RequestType {
     Probe,
-    Action,
+    Action(HashMap<String, String>),
 }
 
-fn consume_client<T, U, F>(service: &str, node_name: &str, handler: Arc<F>, mut stream: U)
-where
+fn consume_client<T, U, F>(
+    service: &str,
+    node_name: &str,
+    clock: &dyn Clock,
+    dispatcher: &Weak<Dispatcher>,
+    max_message_size: usize,
+    handler: Arc<F>,
+    mut stream: U,
+) where
     T: ServicePair,
     U: std::io::Read + std::io::Write + Send + 'static,
-    F: Fn(T::Request) -> ServiceResult<T::Response> + Send + Sync + 'static,
+    F: Fn(T::Request, ServiceEvent) -> ServiceResult<T::Response> + Send + Sync + 'static,
 {
     // Service request starts by exchanging connection headers
     match exchange_headers::<T, _>(&mut stream, service, node_name) {
@@ -93,8 +200,32 @@ where
             return;
         }
 
-        // Spawn a thread for handling requests
-        Ok(RequestType::Action) => spawn_request_handler::<T, U, F>(stream, Arc::clone(&handler)),
+        // Read the request in its own thread, so slow clients don't hold up the handlers
+        Ok(RequestType::Action(header)) => {
+            let event = ServiceEvent {
+                caller_id: header.get("callerid").cloned().unwrap_or_default(),
+                header,
+                receipt_time: clock.now(),
+            };
+            let dispatcher = Weak::clone(dispatcher);
+            thread::spawn(move || {
+                let req = match receive_request::<T, U>(&mut stream, max_message_size) {
+                    Ok(Some(req)) => req,
+                    Ok(None) => return,
+                    Err(err) => return log_failure(&err),
+                };
+                // The service was dropped while the request was being read
+                let dispatcher = match dispatcher.upgrade() {
+                    Some(dispatcher) => dispatcher,
+                    None => return,
+                };
+                dispatcher.dispatch(Box::new(move || {
+                    if let Err(err) = respond::<T, U, F>(stream, req, event, &handler) {
+                        log_failure(&err);
+                    }
+                }));
+            });
+        }
         Ok(RequestType::Probe) => (),
     }
 }
@@ -121,8 +252,11 @@ fn read_request<T: ServicePair, U: std::io::Read>(
     if header::match_field(&fields, "probe", "1").is_ok() {
         return Ok(RequestType::Probe);
     }
-    header::match_field(&fields, "md5sum", &T::md5sum())?;
-    Ok(RequestType::Action)
+    // Clients that only know the service at runtime send a wildcard checksum
+    if fields.get("md5sum").map(String::as_str) != Some("*") {
+        header::match_field(&fields, "md5sum", &T::md5sum())?;
+    }
+    Ok(RequestType::Action(fields))
 }
 
 fn write_response<T, U>(stream: &mut U, node_name: &str) -> Result<()>
@@ -138,67 +272,117 @@ where
     Ok(())
 }
 
-fn spawn_request_handler<T, U, F>(stream: U, handler: Arc<F>)
-where
-    T: ServicePair,
-    U: std::io::Read + std::io::Write + Send + 'static,
-    F: Fn(T::Request) -> ServiceResult<T::Response> + Send + Sync + 'static,
-{
-    thread::spawn(move || {
-        if let Err(err) = handle_request_loop::<T, U, F>(stream, &handler) {
-            if !err.is_closed_connection() {
-                let info = err
-                    .iter()
-                    .map(|v| format!("{}", v))
-                    .collect::<Vec<_>>()
-                    .join("\nCaused by:");
-                error!("{}", info);
-            }
-        }
-    });
+fn log_failure(err: &Error) {
+    if !err.is_closed_connection() {
+        let info = err
+            .iter()
+            .map(|v| format!("{}", v))
+            .collect::<Vec<_>>()
+            .join("\nCaused by:");
+        error!("{}", info);
+    }
 }
 
-fn handle_request_loop<T, U, F>(mut stream: U, handler: &F) -> Result<()>
+fn receive_request<T, U>(stream: &mut U, max_message_size: usize) -> Result<Option<T::Request>>
 where
     T: ServicePair,
     U: std::io::Read + std::io::Write,
-    F: Fn(T::Request) -> ServiceResult<T::Response>,
 {
     // Receive request from client
-    // TODO: validate message length
-    let _length = stream.read_u32::<LittleEndian>();
-    // Break out of loop in case of failure to read request
+    let request = frame::read_length(stream, max_message_size)
+        .and_then(|length| frame::read_body(stream, length))
+        .and_then(|body| frame::decode_body(&body));
     // TODO: handle retained connections
-    if let Ok(req) = RosMsg::decode(&mut stream) {
-        // Call function that handles request and returns response
-        match handler(req) {
-            Ok(res) => {
-                // Send True flag and response in case of success
-                stream.write_u8(1)?;
-                let mut writer = io::Cursor::new(Vec::with_capacity(128));
-                // skip the first 4 bytes that will contain the message length
-                writer.set_position(4);
-
-                res.encode(&mut writer)?;
-
-                // write the message length to the start of the header
-                let message_length = (writer.position() - 4) as u32;
-                writer.set_position(0);
-                message_length.encode(&mut writer)?;
-
-                stream.write_all(&writer.into_inner())?;
-            }
-            Err(message) => {
-                // Send False flag and error message string in case of failure
-                stream.write_u8(0)?;
-                RosMsg::encode(&message, &mut stream)?;
+    match request {
+        Ok(req) => Ok(Some(req)),
+        Err(err) => {
+            // Upon failure to read request, send client failure message
+            // This can be caused by actual issues or by the client stopping the connection
+            stream.write_u8(0)?;
+            match *err.kind() {
+                ErrorKind::MessageTooLarge(..) | ErrorKind::MessageLengthMismatch(..) => {
+                    encode_str(&format!("Rejected request: {}", err), stream)?;
+                    Err(err)
+                }
+                _ => {
+                    encode_str("Failed to parse passed arguments", stream)?;
+                    Ok(None)
+                }
             }
-        };
+        }
     }
+}
+
+fn respond<T, U, F>(mut stream: U, req: T::Request, event: ServiceEvent, handler: &F) -> Result<()>
+where
+    T: ServicePair,
+    U: std::io::Write,
+    F: Fn(T::Request, ServiceEvent) -> ServiceResult<T::Response>,
+{
+    // Call function that handles request and returns response
+    match handler(req, event) {
+        Ok(res) => {
+            // Send True flag and response in case of success
+            stream.write_u8(1)?;
+            let mut writer = io::Cursor::new(Vec::with_capacity(128));
+            // skip the first 4 bytes that will contain the message length
+            writer.set_position(4);
+
+            res.encode(&mut writer)?;
+
+            // write the message length to the start of the header
+            let message_length = (writer.position() - 4) as u32;
+            writer.set_position(0);
+            message_length.encode(&mut writer)?;
 
-    // Upon failure to read request, send client failure message
-    // This can be caused by actual issues or by the client stopping the connection
-    stream.write_u8(0)?;
-    encode_str("Failed to parse passed arguments", &mut stream)?;
+            stream.write_all(&writer.into_inner())?;
+        }
+        Err(message) => {
+            // Send False flag and error message string in case of failure
+            stream.write_u8(0)?;
+            RosMsg::encode(&message, &mut stream)?;
+        }
+    };
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam::channel::bounded;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn max_parallel_jobs(concurrency: ServiceConcurrency, jobs: usize) -> usize {
+        let dispatcher = Dispatcher::new(concurrency);
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let (done_tx, done_rx) = bounded(jobs);
+        for _ in 0..jobs {
+            let running = Arc::clone(&running);
+            let max_running = Arc::clone(&max_running);
+            let done_tx = done_tx.clone();
+            dispatcher.dispatch(Box::new(move || {
+                let current = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(current, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(20));
+                running.fetch_sub(1, Ordering::SeqCst);
+                done_tx.send(()).unwrap();
+            }));
+        }
+        for _ in 0..jobs {
+            done_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+        max_running.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn serial_dispatcher_runs_one_job_at_a_time() {
+        assert_eq!(1, max_parallel_jobs(ServiceConcurrency::Serial, 4));
+    }
+
+    #[test]
+    fn bounded_dispatcher_limits_parallel_jobs() {
+        assert!(max_parallel_jobs(ServiceConcurrency::Bounded(2), 6) <= 2);
+    }
+}
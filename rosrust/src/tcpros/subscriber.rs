@@ -1,28 +1,49 @@
 use super::error::{ErrorKind, Result, ResultExt};
+use super::frame;
 use super::header::{decode, encode, match_field};
-use super::{Message, Topic};
-use crate::rosmsg::RosMsg;
+use super::mismatch::TypeMismatches;
+use super::{Message, Topic, TransportHints, TypeMismatch, TypeMismatchCallback};
 use crate::util::lossy_channel::{lossy_channel, LossyReceiver, LossySender};
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use crate::util::FAILED_TO_LOCK;
 use crossbeam::channel::{bounded, Receiver, Sender, TrySendError};
 use log::error;
+use net2::TcpStreamExt;
 use std;
 use std::collections::{BTreeSet, HashMap};
 use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
 
 pub struct Subscriber {
-    data_stream: LossySender<Vec<u8>>,
+    data_stream: LossySender<MessageInfo>,
     publishers_stream: Sender<SocketAddr>,
     pub topic: Topic,
     pub connected_publishers: BTreeSet<String>,
+    latched: Arc<AtomicBool>,
+    established: Arc<AtomicUsize>,
+    observers: Observers,
+    transport_hints: TransportHints,
+    type_mismatches: TypeMismatches,
 }
 
+/// One-shot callbacks for the raw data of the next message.
+type Observers = Arc<Mutex<Vec<Box<dyn FnOnce(&[u8]) + Send>>>>;
+
 impl Subscriber {
-    pub fn new<T, F>(caller_id: &str, topic: &str, queue_size: usize, callback: F) -> Subscriber
+    pub fn new<T, F, G>(
+        caller_id: &str,
+        topic: &str,
+        queue_size: usize,
+        transport_hints: TransportHints,
+        max_message_size: usize,
+        on_message: F,
+        on_connect: G,
+    ) -> Subscriber
     where
         T: Message,
-        F: Fn(T) -> () + Send + 'static,
+        F: Fn(T, &str) + Send + 'static,
+        G: Fn(HashMap<String, String>) + Send + 'static,
     {
         let (data_tx, data_rx) = lossy_channel(queue_size);
         let publisher_connection_queue_size = 8;
@@ -30,8 +51,37 @@ impl Subscriber {
         let caller_id = String::from(caller_id);
         let topic_name = String::from(topic);
         let data_stream = data_tx.clone();
-        thread::spawn(move || join_connections::<T>(&data_tx, pub_rx, &caller_id, &topic_name));
-        thread::spawn(move || handle_data::<T, F>(data_rx, callback));
+        let latched = Arc::new(AtomicBool::new(false));
+        let on_connect = {
+            let latched = Arc::clone(&latched);
+            move |headers: HashMap<String, String>| {
+                if headers.get("latching").map(String::as_str) == Some("1") {
+                    latched.store(true, Ordering::SeqCst);
+                }
+                on_connect(headers)
+            }
+        };
+        let hints = transport_hints.clone();
+        let type_mismatches = TypeMismatches::default();
+        let mismatches = type_mismatches.clone();
+        let established = Arc::new(AtomicUsize::new(0));
+        let links = Arc::clone(&established);
+        thread::spawn(move || {
+            join_connections::<T, _>(
+                &data_tx,
+                pub_rx,
+                &caller_id,
+                &topic_name,
+                &hints,
+                max_message_size,
+                &mismatches,
+                &links,
+                on_connect,
+            )
+        });
+        let observers = Observers::default();
+        let data_observers = Arc::clone(&observers);
+        thread::spawn(move || handle_data::<T, F>(data_rx, &data_observers, on_message));
         let topic = Topic {
             name: String::from(topic),
             msg_type: T::msg_type(),
@@ -41,12 +91,55 @@ impl Subscriber {
             publishers_stream: pub_tx,
             topic,
             connected_publishers: BTreeSet::new(),
+            latched,
+            established,
+            observers,
+            transport_hints,
+            type_mismatches,
         }
     }
 
+    #[inline]
+    pub fn get_transport_hints(&self) -> &TransportHints {
+        &self.transport_hints
+    }
+
+    /// Checks if any of the publishers that were connected to advertised latching.
+    #[inline]
+    pub fn is_latched(&self) -> bool {
+        self.latched.load(Ordering::SeqCst)
+    }
+
+    /// Latest type mismatch reported for each publisher that could not be connected.
+    #[inline]
+    pub fn type_mismatches(&self) -> Vec<TypeMismatch> {
+        self.type_mismatches.get()
+    }
+
+    /// Calls the callback for every type mismatch, including the ones reported so far.
+    #[inline]
+    pub fn add_type_mismatch_callback(&self, callback: TypeMismatchCallback) {
+        self.type_mismatches.add_callback(callback)
+    }
+
+    /// Number of publishers whose connection headers were accepted and are still connected.
     #[inline]
     pub fn publisher_count(&self) -> usize {
-        self.connected_publishers.len()
+        self.established.load(Ordering::SeqCst)
+    }
+
+    /// Decodes the next received message as `T`, alongside the subscription's own callback.
+    ///
+    /// The channel is disconnected without a message if the subscription ends first.
+    pub fn next_message<T: Message>(&self) -> Receiver<Result<T>> {
+        let (tx, rx) = bounded(1);
+        self.observers
+            .lock()
+            .expect(FAILED_TO_LOCK)
+            .push(Box::new(move |data: &[u8]| {
+                let _ = tx.try_send(frame::decode_frame(data));
+            }));
+        rx
     }
 
     pub fn connect_to<U: ToSocketAddrs>(
@@ -98,31 +191,62 @@ impl Drop for Subscriber {
     }
 }
 
-fn handle_data<T, F>(data: LossyReceiver<Vec<u8>>, callback: F)
+#[derive(Clone)]
+struct MessageInfo {
+    caller_id: Arc<String>,
+    data: Vec<u8>,
+}
+
+fn handle_data<T, F>(data: LossyReceiver<MessageInfo>, observers: &Observers, callback: F)
 where
     T: Message,
-    F: Fn(T) -> (),
+    F: Fn(T, &str),
 {
     for buffer in data {
-        match RosMsg::decode_slice(&buffer) {
-            Ok(value) => callback(value),
+        let pending = std::mem::take(&mut *observers.lock().expect(FAILED_TO_LOCK));
+        for observer in pending {
+            observer(&buffer.data);
+        }
+        match frame::decode_frame(&buffer.data) {
+            Ok(value) => callback(value, &buffer.caller_id),
             Err(err) => error!("Failed to decode message: {}", err),
         }
     }
 }
 
-fn join_connections<T>(
-    data_stream: &LossySender<Vec<u8>>,
+#[allow(clippy::too_many_arguments)]
+fn join_connections<T, G>(
+    data_stream: &LossySender<MessageInfo>,
     publishers: Receiver<SocketAddr>,
     caller_id: &str,
     topic: &str,
+    hints: &TransportHints,
+    max_message_size: usize,
+    type_mismatches: &TypeMismatches,
+    established: &Arc<AtomicUsize>,
+    on_connect: G,
 ) where
     T: Message,
+    G: Fn(HashMap<String, String>),
 {
     // Ends when publisher sender is destroyed, which happens at Subscriber destruction
     for publisher in publishers {
-        let result = join_connection::<T>(data_stream, &publisher, caller_id, topic)
-            .chain_err(|| ErrorKind::TopicConnectionFail(topic.into()));
+        let result = join_connection::<T, G>(
+            data_stream,
+            &publisher,
+            caller_id,
+            topic,
+            hints,
+            max_message_size,
+            established,
+            &on_connect,
+        );
+        if let Err(ErrorKind::TopicTypeMismatch(ref mismatch)) =
+            result.as_ref().map_err(|err| err.kind())
+        {
+            type_mismatches.record((**mismatch).clone());
+        }
+        let result = result.chain_err(|| ErrorKind::TopicConnectionFail(topic.into()));
         if let Err(err) = result {
             let info = err
                 .iter()
@@ -134,26 +258,56 @@ fn join_connections<T>(
     }
 }
 
-fn join_connection<T>(
-    data_stream: &LossySender<Vec<u8>>,
+#[allow(clippy::too_many_arguments)]
+fn join_connection<T, G>(
+    data_stream: &LossySender<MessageInfo>,
     publisher: &SocketAddr,
     caller_id: &str,
     topic: &str,
+    hints: &TransportHints,
+    max_message_size: usize,
+    established: &Arc<AtomicUsize>,
+    on_connect: &G,
 ) -> Result<()>
 where
     T: Message,
+    G: Fn(HashMap<String, String>),
 {
     let mut stream = TcpStream::connect(publisher)?;
-    exchange_headers::<T, _>(&mut stream, caller_id, topic)?;
+    if let Some(keepalive) = hints.get_keepalive() {
+        stream.set_keepalive(Some(keepalive))?;
+    }
+    let headers =
+        exchange_headers::<T, _>(&mut stream, caller_id, topic, &publisher.to_string(), hints)?;
+    let pub_caller_id = Arc::new(headers.get("callerid").cloned().unwrap_or_default());
+    // Called before any data is read, so the callback sees the headers before the messages
+    on_connect(headers);
     let target = data_stream.clone();
+    let topic = String::from(topic);
+    established.fetch_add(1, Ordering::SeqCst);
+    let established = Arc::clone(established);
     thread::spawn(move || {
-        while let Ok(buffer) = package_to_vector(&mut stream) {
-            if let Err(TrySendError::Disconnected(_)) = target.try_send(buffer) {
+        loop {
+            let buffer = match package_to_vector(&mut stream, max_message_size) {
+                Ok(buffer) => buffer,
+                Err(err) => {
+                    if let ErrorKind::MessageTooLarge(..) = err.kind() {
+                        error!("Dropping connection to publisher of '{}': {}", topic, err);
+                    }
+                    break;
+                }
+            };
+            let message = MessageInfo {
+                caller_id: Arc::clone(&pub_caller_id),
+                data: buffer,
+            };
+            if let Err(TrySendError::Disconnected(_)) = target.try_send(message) {
                 // Data receiver has been destroyed after
                 // Subscriber destructor's kill signal
                 break;
             }
         }
+        established.fetch_sub(1, Ordering::SeqCst);
     });
     Ok(())
 }
@@ -162,6 +316,7 @@ fn write_request<T: Message, U: std::io::Write>(
     mut stream: &mut U,
     caller_id: &str,
     topic: &str,
+    hints: &TransportHints,
 ) -> Result<()> {
     let mut fields = HashMap::<String, String>::new();
     fields.insert(String::from("message_definition"), T::msg_definition());
@@ -169,54 +324,55 @@ fn write_request<T: Message, U: std::io::Write>(
     fields.insert(String::from("topic"), String::from(topic));
     fields.insert(String::from("md5sum"), T::md5sum());
     fields.insert(String::from("type"), T::msg_type());
+    if hints.get_tcp_nodelay() {
+        fields.insert(String::from("tcp_nodelay"), String::from("1"));
+    }
+    if let Some(max_datagram_size) = hints.get_max_datagram_size() {
+        fields.insert(
+            String::from("max_datagram_size"),
+            max_datagram_size.to_string(),
+        );
+    }
     encode(&mut stream, &fields)?;
     Ok(())
 }
 
-fn read_response<T: Message, U: std::io::Read>(mut stream: &mut U) -> Result<()> {
+fn read_response<T: Message, U: std::io::Read>(
+    mut stream: &mut U,
+    topic: &str,
+    publisher: &str,
+) -> Result<HashMap<String, String>> {
     let fields = decode(&mut stream)?;
-    match_field(&fields, "md5sum", &T::md5sum())?;
-    match_field(&fields, "type", &T::msg_type())
+    if let Some(mismatch) = TypeMismatch::detect::<T>(topic, publisher, &fields) {
+        bail!(ErrorKind::TopicTypeMismatch(Box::new(mismatch)));
+    }
+    if T::md5sum() != "*" {
+        match_field(&fields, "md5sum", &T::md5sum())?;
+    }
+    if T::msg_type() != "*" {
+        match_field(&fields, "type", &T::msg_type())?;
+    }
+    Ok(fields)
 }
 
-fn exchange_headers<T, U>(stream: &mut U, caller_id: &str, topic: &str) -> Result<()>
+fn exchange_headers<T, U>(
+    stream: &mut U,
+    caller_id: &str,
+    topic: &str,
+    publisher: &str,
+    hints: &TransportHints,
+) -> Result<HashMap<String, String>>
 where
     T: Message,
     U: std::io::Write + std::io::Read,
 {
-    write_request::<T, U>(stream, caller_id, topic)?;
-    read_response::<T, U>(stream)
+    write_request::<T, U>(stream, caller_id, topic, hints)?;
+    read_response::<T, U>(stream, topic, publisher)
 }
 
 #[inline]
-fn package_to_vector<R: std::io::Read>(stream: &mut R) -> std::io::Result<Vec<u8>> {
-    let length = stream.read_u32::<LittleEndian>()?;
-    let u32_size = std::mem::size_of::<u32>();
-    let num_bytes = length as usize + u32_size;
-
-    // Allocate memory of the proper size for the incoming message. We
-    // do not initialize the memory to zero here (as would be safe)
-    // because it is expensive and ultimately unnecessary. We know the
-    // length of the message and if the length is incorrect, the
-    // stream reading functions will bail with an Error rather than
-    // leaving memory uninitialized.
-    let mut out = Vec::<u8>::with_capacity(num_bytes);
-
-    let out_ptr = out.as_mut_ptr();
-    // Read length from stream.
-    std::io::Cursor::new(unsafe { std::slice::from_raw_parts_mut(out_ptr as *mut u8, u32_size) })
-        .write_u32::<LittleEndian>(length)?;
-
-    // Read data from stream.
-    let read_buf = unsafe { std::slice::from_raw_parts_mut(out_ptr as *mut u8, num_bytes) };
-    stream.read_exact(&mut read_buf[u32_size..])?;
-
-    // Don't drop the original Vec which has size==0 and instead use
-    // its memory to initialize a new Vec with size == capacity == num_bytes.
-    std::mem::forget(out);
-
-    // Return the new, now full and "safely" initialized.
-    Ok(unsafe { Vec::from_raw_parts(out_ptr, num_bytes, num_bytes) })
+fn package_to_vector<R: std::io::Read>(stream: &mut R, max_message_size: usize) -> Result<Vec<u8>> {
+    frame::read_frame(stream, max_message_size)
 }
 
 #[cfg(test)]
@@ -229,32 +385,106 @@ mod tests {
     #[test]
     fn package_to_vector_creates_right_buffer_from_reader() {
         let input = [7, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7];
-        let data =
-            package_to_vector(&mut std::io::Cursor::new(input)).expect(FAILED_TO_READ_WRITE_VECTOR);
+        let data = package_to_vector(&mut std::io::Cursor::new(input), 64)
+            .expect(FAILED_TO_READ_WRITE_VECTOR);
         assert_eq!(data, [7, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7]);
     }
 
     #[test]
     fn package_to_vector_respects_provided_length() {
         let input = [7, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
-        let data =
-            package_to_vector(&mut std::io::Cursor::new(input)).expect(FAILED_TO_READ_WRITE_VECTOR);
+        let data = package_to_vector(&mut std::io::Cursor::new(input), 64)
+            .expect(FAILED_TO_READ_WRITE_VECTOR);
         assert_eq!(data, [7, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7]);
     }
 
     #[test]
     fn package_to_vector_fails_if_stream_is_shorter_than_annotated() {
         let input = [7, 0, 0, 0, 1, 2, 3, 4, 5];
-        package_to_vector(&mut std::io::Cursor::new(input)).unwrap_err();
+        package_to_vector(&mut std::io::Cursor::new(input), 64).unwrap_err();
+    }
+
+    #[test]
+    fn package_to_vector_rejects_frames_over_limit() {
+        let input = [7, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7];
+        let err = package_to_vector(&mut std::io::Cursor::new(input), 6).unwrap_err();
+        match err.kind() {
+            ErrorKind::MessageTooLarge(7, 6) => {}
+            kind => panic!("Expected oversized frame error, got {:?}", kind),
+        }
     }
 
     #[test]
     fn package_to_vector_fails_leaves_cursor_at_end_of_reading() {
         let input = [7, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 4, 0, 0, 0, 11, 12, 13, 14];
         let mut cursor = std::io::Cursor::new(input);
-        let data = package_to_vector(&mut cursor).expect(FAILED_TO_READ_WRITE_VECTOR);
+        let data = package_to_vector(&mut cursor, 64).expect(FAILED_TO_READ_WRITE_VECTOR);
         assert_eq!(data, [7, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7]);
-        let data = package_to_vector(&mut cursor).expect(FAILED_TO_READ_WRITE_VECTOR);
+        let data = package_to_vector(&mut cursor, 64).expect(FAILED_TO_READ_WRITE_VECTOR);
         assert_eq!(data, [4, 0, 0, 0, 11, 12, 13, 14]);
     }
+
+    #[test]
+    fn write_request_includes_tcp_nodelay_only_when_requested() {
+        let mut buffer = Vec::new();
+        write_request::<crate::RawMessage, _>(
+            &mut buffer,
+            "/caller",
+            "/topic",
+            &TransportHints::default(),
+        )
+        .expect(FAILED_TO_READ_WRITE_VECTOR);
+        let fields = decode(&mut std::io::Cursor::new(buffer)).expect(FAILED_TO_READ_WRITE_VECTOR);
+        assert!(!fields.contains_key("tcp_nodelay"));
+
+        let mut buffer = Vec::new();
+        write_request::<crate::RawMessage, _>(
+            &mut buffer,
+            "/caller",
+            "/topic",
+            &TransportHints::default().tcp_nodelay(true),
+        )
+        .expect(FAILED_TO_READ_WRITE_VECTOR);
+        let fields = decode(&mut std::io::Cursor::new(buffer)).expect(FAILED_TO_READ_WRITE_VECTOR);
+        assert_eq!(fields.get("tcp_nodelay").map(String::as_str), Some("1"));
+    }
+
+    #[test]
+    fn write_request_includes_max_datagram_size_when_set() {
+        let mut buffer = Vec::new();
+        write_request::<crate::RawMessage, _>(
+            &mut buffer,
+            "/caller",
+            "/topic",
+            &TransportHints::default().max_datagram_size(1500),
+        )
+        .expect(FAILED_TO_READ_WRITE_VECTOR);
+        let fields = decode(&mut std::io::Cursor::new(buffer)).expect(FAILED_TO_READ_WRITE_VECTOR);
+        assert_eq!(
+            fields.get("max_datagram_size").map(String::as_str),
+            Some("1500")
+        );
+    }
+
+    #[test]
+    fn publisher_count_ignores_connections_without_handshake() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+        let address = listener.local_addr().expect("Failed to get address");
+        let mut subscriber = Subscriber::new::<crate::RawMessage, _, _>(
+            "/caller",
+            "/topic",
+            1,
+            TransportHints::default(),
+            usize::MAX,
+            |_, _| {},
+            |_| {},
+        );
+        subscriber
+            .connect_to("http://silent:1234/", address)
+            .expect("Failed to connect");
+        let _stream = listener.accept().expect("Failed to accept");
+        thread::sleep(std::time::Duration::from_millis(100));
+        assert!(subscriber.is_connected_to("http://silent:1234/"));
+        assert_eq!(subscriber.publisher_count(), 0);
+    }
 }
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+/// Transport protocols that a subscriber can ask a publisher for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transport {
+    Tcpros,
+    /// Not supported yet, so it gets left out when requesting topics.
+    Udpros,
+}
+
+/// Options that a subscriber requests for its connections to publishers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransportHints {
+    tcp_nodelay: bool,
+    keepalive: Option<Duration>,
+    max_datagram_size: Option<u32>,
+    transports: Vec<Transport>,
+}
+
+impl Default for TransportHints {
+    fn default() -> Self {
+        Self {
+            tcp_nodelay: false,
+            keepalive: None,
+            max_datagram_size: None,
+            transports: vec![Transport::Tcpros],
+        }
+    }
+}
+
+impl TransportHints {
+    /// Asks publishers to disable Nagle's algorithm, trading bandwidth for latency.
+    pub fn tcp_nodelay(mut self, tcp_nodelay: bool) -> Self {
+        self.tcp_nodelay = tcp_nodelay;
+        self
+    }
+
+    /// Enables TCP keepalive probes with the given interval on the subscriber's sockets.
+    pub fn keepalive(mut self, keepalive: Option<Duration>) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+
+    /// Largest datagram size, sent to publishers as `max_datagram_size` in the connection header.
+    pub fn max_datagram_size(mut self, max_datagram_size: u32) -> Self {
+        self.max_datagram_size = Some(max_datagram_size);
+        self
+    }
+
+    /// Transports in order of preference.
+    ///
+    /// Only TCPROS is supported, so UDPROS entries are left out of `requestTopic` calls,
+    /// and TCPROS is requested when no supported transport remains.
+    pub fn transports(mut self, transports: &[Transport]) -> Self {
+        self.transports = transports.to_vec();
+        self
+    }
+
+    #[inline]
+    pub fn get_tcp_nodelay(&self) -> bool {
+        self.tcp_nodelay
+    }
+
+    #[inline]
+    pub fn get_keepalive(&self) -> Option<Duration> {
+        self.keepalive
+    }
+
+    #[inline]
+    pub fn get_max_datagram_size(&self) -> Option<u32> {
+        self.max_datagram_size
+    }
+
+    /// Protocols for the `requestTopic` call, falling back to TCPROS if none are supported.
+    pub fn protocols(&self) -> Vec<Vec<String>> {
+        let protocols = self
+            .transports
+            .iter()
+            .filter_map(|transport| match *transport {
+                Transport::Tcpros => Some(vec![String::from("TCPROS")]),
+                Transport::Udpros => None,
+            })
+            .collect::<Vec<_>>();
+        if protocols.is_empty() {
+            return vec![vec![String::from("TCPROS")]];
+        }
+        protocols
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_supported_protocols() {
+        let tcpros = vec![vec![String::from("TCPROS")]];
+        assert_eq!(tcpros, TransportHints::default().protocols());
+        assert_eq!(
+            tcpros,
+            TransportHints::default()
+                .transports(&[Transport::Udpros, Transport::Tcpros])
+                .protocols()
+        );
+        assert_eq!(
+            tcpros,
+            TransportHints::default()
+                .transports(&[Transport::Udpros])
+                .protocols()
+        );
+    }
+}
@@ -5,17 +5,27 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
 
-pub fn fork<T: Write + Send + 'static>(queue_size: usize) -> (TargetList<T>, DataStream) {
+/// Splits data to all targets, calling `on_disconnect` for targets that fail to receive it.
+pub fn fork<T, F>(queue_size: usize, on_disconnect: F) -> (TargetList<T>, DataStream)
+where
+    T: Write + Send + 'static,
+    F: Fn(TargetHandle) + Send + 'static,
+{
     let (streams_sender, streams) = unbounded();
+    let (direct_sender, direct) = unbounded();
     let (data_sender, data) = lossy_channel(queue_size);
 
-    let mut fork_thread = ForkThread::new();
+    let mut fork_thread = ForkThread::new(direct_sender.clone(), Box::new(on_disconnect));
     let target_count = fork_thread.clone_target_count();
 
-    thread::spawn(move || fork_thread.run(&streams, &data));
+    thread::spawn(move || fork_thread.run(&streams, &direct, &data));
 
     (
-        TargetList(streams_sender),
+        TargetList {
+            streams: streams_sender,
+            direct: direct_sender,
+            next_id: Arc::new(AtomicUsize::new(0)),
+        },
         DataStream {
             sender: data_sender,
             target_count,
@@ -23,16 +33,31 @@ pub fn fork<T: Write + Send + 'static>(queue_size: usize) -> (TargetList<T>, Dat
     )
 }
 
+type DirectData = (usize, Arc<Vec<u8>>);
+
+struct Target<T> {
+    handle: TargetHandle,
+    stream: T,
+}
+
 struct ForkThread<T: Write + Send + 'static> {
-    targets: Vec<T>,
+    targets: Vec<Target<T>>,
     target_count: Arc<AtomicUsize>,
+    // Keeps the channel of direct data open while targets come and go
+    _direct: Sender<DirectData>,
+    on_disconnect: Box<dyn Fn(TargetHandle) + Send>,
 }
 
 impl<T: Write + Send + 'static> ForkThread<T> {
-    pub fn new() -> Self {
+    pub fn new(
+        direct: Sender<DirectData>,
+        on_disconnect: Box<dyn Fn(TargetHandle) + Send>,
+    ) -> Self {
         Self {
             targets: vec![],
             target_count: Arc::new(AtomicUsize::new(0)),
+            _direct: direct,
+            on_disconnect,
         }
     }
 
@@ -43,7 +68,7 @@ impl<T: Write + Send + 'static> ForkThread<T> {
     fn publish_buffer_and_prune_targets(&mut self, buffer: &[u8]) {
         let mut dropped_targets = vec![];
         for (idx, target) in self.targets.iter_mut().enumerate() {
-            if target.write_all(&buffer).is_err() {
+            if target.stream.write_all(buffer).is_err() {
                 dropped_targets.push(idx);
             }
         }
@@ -51,23 +76,46 @@ impl<T: Write + Send + 'static> ForkThread<T> {
         if !dropped_targets.is_empty() {
             // We reverse the order, to remove bigger indices first.
             for idx in dropped_targets.into_iter().rev() {
-                self.targets.swap_remove(idx);
+                self.remove_target(idx);
             }
+        }
+    }
 
-            self.target_count
-                .store(self.targets.len(), Ordering::SeqCst);
+    fn publish_direct(&mut self, streams: &Receiver<(TargetHandle, T)>, (id, buffer): DirectData) {
+        // The target might still be waiting in the queue of new streams
+        if !self.targets.iter().any(|target| target.handle.id == id) {
+            while let Ok((handle, stream)) = streams.try_recv() {
+                self.add_target(handle, stream);
+            }
+        }
+        if let Some(idx) = self
+            .targets
+            .iter()
+            .position(|target| target.handle.id == id)
+        {
+            if self.targets[idx].stream.write_all(&buffer).is_err() {
+                self.remove_target(idx);
+            }
         }
     }
 
-    fn add_target(&mut self, target: T) {
-        self.targets.push(target);
+    fn add_target(&mut self, handle: TargetHandle, stream: T) {
+        self.targets.push(Target { handle, stream });
         self.target_count
             .store(self.targets.len(), Ordering::SeqCst);
     }
 
+    fn remove_target(&mut self, idx: usize) {
+        let target = self.targets.swap_remove(idx);
+        self.target_count
+            .store(self.targets.len(), Ordering::SeqCst);
+        (self.on_disconnect)(target.handle);
+    }
+
     fn step(
         &mut self,
-        streams: &Receiver<T>,
+        streams: &Receiver<(TargetHandle, T)>,
+        direct: &Receiver<DirectData>,
         data: &LossyReceiver<Arc<Vec<u8>>>,
     ) -> Result<(), channel::RecvError> {
         channel::select! {
@@ -77,25 +125,72 @@ impl<T: Write + Send + 'static> ForkThread<T> {
             recv(data.data_rx) -> msg => {
                 self.publish_buffer_and_prune_targets(&msg?);
             }
+            recv(direct) -> msg => {
+                self.publish_direct(streams, msg?);
+            }
             recv(streams) -> target => {
-                self.add_target(target?);
+                let (handle, stream) = target?;
+                self.add_target(handle, stream);
             }
         }
         Ok(())
     }
 
-    pub fn run(&mut self, streams: &Receiver<T>, data: &LossyReceiver<Arc<Vec<u8>>>) {
-        while self.step(streams, data).is_ok() {}
+    pub fn run(
+        &mut self,
+        streams: &Receiver<(TargetHandle, T)>,
+        direct: &Receiver<DirectData>,
+        data: &LossyReceiver<Arc<Vec<u8>>>,
+    ) {
+        while self.step(streams, direct, data).is_ok() {}
     }
 }
 
 pub type ForkResult = Result<(), ()>;
 
-pub struct TargetList<T: Write + Send + 'static>(Sender<T>);
+pub struct TargetList<T: Write + Send + 'static> {
+    streams: Sender<(TargetHandle, T)>,
+    direct: Sender<DirectData>,
+    next_id: Arc<AtomicUsize>,
+}
 
 impl<T: Write + Send + 'static> TargetList<T> {
-    pub fn add(&self, stream: T) -> ForkResult {
-        self.0.send(stream).or(Err(()))
+    /// Creates the handle of a new target, which receives data once it gets added.
+    pub fn handle(&self, caller_id: &str) -> TargetHandle {
+        TargetHandle {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            caller_id: caller_id.into(),
+            direct: self.direct.clone(),
+        }
+    }
+
+    pub fn add(&self, handle: TargetHandle, stream: T) -> ForkResult {
+        self.streams.send((handle, stream)).or(Err(()))
+    }
+}
+
+/// Handle for sending data to a single target.
+#[derive(Clone)]
+pub struct TargetHandle {
+    id: usize,
+    caller_id: String,
+    direct: Sender<DirectData>,
+}
+
+impl TargetHandle {
+    #[inline]
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    #[inline]
+    pub fn caller_id(&self) -> &str {
+        &self.caller_id
+    }
+
+    /// Queues data for this target only, which is dropped if the target is gone.
+    pub fn send(&self, data: Arc<Vec<u8>>) -> ForkResult {
+        self.direct.send((self.id, data)).or(Err(()))
     }
 }
 
@@ -110,6 +205,11 @@ impl DataStream {
         self.sender.try_send(data).or(Err(()))
     }
 
+    #[inline]
+    pub fn pending(&self) -> usize {
+        self.sender.len()
+    }
+
     #[inline]
     pub fn get_target_count(&self) -> usize {
         self.target_count.load(Ordering::SeqCst)
@@ -125,3 +225,71 @@ impl DataStream {
         self.sender.set_queue_size_max(queue_size);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer {
+        data: Arc<Mutex<Vec<u8>>>,
+        broken: bool,
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.broken {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            self.data.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn sends_to_single_targets_and_reports_disconnects() {
+        let (disconnect_tx, disconnect_rx) = unbounded();
+        let (targets, data) = fork(10, move |target: TargetHandle| {
+            disconnect_tx.send(target.caller_id().to_owned()).unwrap();
+        });
+
+        let first = SharedBuffer::default();
+        let second = SharedBuffer::default();
+        let broken = SharedBuffer {
+            broken: true,
+            ..SharedBuffer::default()
+        };
+        let first_handle = targets.handle("/first");
+        targets.add(first_handle.clone(), first.clone()).unwrap();
+        targets
+            .add(targets.handle("/second"), second.clone())
+            .unwrap();
+        targets.add(targets.handle("/broken"), broken).unwrap();
+
+        first_handle.send(Arc::new(vec![1])).unwrap();
+        while data.get_target_count() != 3 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        data.send(Arc::new(vec![2])).unwrap();
+
+        assert_eq!(
+            "/broken",
+            disconnect_rx.recv_timeout(Duration::from_secs(5)).unwrap()
+        );
+        while data.get_target_count() != 2 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        while second.data.lock().unwrap().is_empty() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(vec![1, 2], *first.data.lock().unwrap());
+        assert_eq!(vec![2], *second.data.lock().unwrap());
+    }
+}
@@ -0,0 +1,746 @@
+use crate::api::error::{self, Result};
+use crate::rosxmlrpc::{self, Response, ResponseError, Server};
+use crate::util::{kill, FAILED_TO_LOCK};
+use crate::{Ros, RosBuilder};
+use crossbeam::channel::TryRecvError;
+use log::error;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::ToSocketAddrs;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use xml_rpc::{Params, Value};
+
+const MASTER_CALLER_ID: &str = "/master";
+
+/// In-process master, with the parts of the master and parameter server APIs used by nodes.
+///
+/// The master listens on a random local port, and stops when dropped.
+pub struct MockMaster {
+    uri: String,
+    state: Arc<Mutex<MasterState>>,
+    shutdown_tx: kill::Sender,
+}
+
+impl MockMaster {
+    pub fn new() -> Result<MockMaster> {
+        let state = Arc::new(Mutex::new(MasterState::default()));
+        let mut server = Server::default();
+        register_handlers(&mut server, &state);
+
+        let socket_addr = match ("127.0.0.1", 0).to_socket_addrs()?.next() {
+            Some(socket_addr) => socket_addr,
+            None => bail!(error::ErrorKind::from(error::rosxmlrpc::ErrorKind::BadUri(
+                "127.0.0.1:0".into()
+            ))),
+        };
+        let bound_server = server
+            .bind(&socket_addr)
+            .map_err(rosxmlrpc::error::Error::from)?;
+        let uri = format!("http://127.0.0.1:{}/", bound_server.local_addr().port());
+        state.lock().expect(FAILED_TO_LOCK).uri = uri.clone();
+
+        let (shutdown_tx, shutdown_rx) = kill::channel(kill::KillMode::Sync);
+        thread::spawn(move || loop {
+            match shutdown_rx.try_recv() {
+                Ok(_) | Err(TryRecvError::Disconnected) => break,
+                Err(TryRecvError::Empty) => {}
+            }
+            bound_server.poll();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        });
+
+        Ok(MockMaster {
+            uri,
+            state,
+            shutdown_tx,
+        })
+    }
+
+    #[inline]
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Creates a node registered with this master, listening on the loopback interface.
+    pub fn node(&self, name: &str) -> Result<Ros> {
+        self.node_builder(name).build()
+    }
+
+    /// Creates a node builder that points to this master, for nodes with extra settings.
+    pub fn node_builder(&self, name: &str) -> RosBuilder {
+        Ros::builder(name)
+            .master_uri(&self.uri)
+            .hostname("127.0.0.1")
+    }
+
+    /// Names of all nodes that registered anything with the master.
+    pub fn nodes(&self) -> Vec<String> {
+        let state = self.state.lock().expect(FAILED_TO_LOCK);
+        state.nodes.keys().cloned().collect()
+    }
+
+    pub fn publishers(&self, topic: &str) -> Vec<String> {
+        let state = self.state.lock().expect(FAILED_TO_LOCK);
+        registered(&state.publishers, topic)
+    }
+
+    pub fn subscribers(&self, topic: &str) -> Vec<String> {
+        let state = self.state.lock().expect(FAILED_TO_LOCK);
+        registered(&state.subscribers, topic)
+    }
+
+    pub fn param(&self, key: &str) -> Option<Value> {
+        self.state.lock().expect(FAILED_TO_LOCK).params.get(key)
+    }
+
+    pub fn set_param(&self, key: &str, value: Value) {
+        self.state
+            .lock()
+            .expect(FAILED_TO_LOCK)
+            .params
+            .set(key, value)
+    }
+}
+
+impl Drop for MockMaster {
+    fn drop(&mut self) {
+        if self.shutdown_tx.send().is_err() {
+            error!("Mock master server has already been stopped");
+        }
+    }
+}
+
+#[derive(Default)]
+struct MasterState {
+    uri: String,
+    nodes: BTreeMap<String, String>,
+    publishers: BTreeMap<String, BTreeSet<String>>,
+    subscribers: BTreeMap<String, BTreeSet<String>>,
+    services: BTreeMap<String, (String, String)>,
+    topic_types: BTreeMap<String, String>,
+    params: ParamTree,
+}
+
+impl MasterState {
+    /// Records the API of a node, shutting down any other node with the same name.
+    fn register_node(&mut self, caller_id: &str, caller_api: String) {
+        match self.nodes.insert(caller_id.into(), caller_api.clone()) {
+            Some(old_api) if old_api != caller_api => shut_down_node(old_api),
+            _ => (),
+        }
+    }
+
+    fn apis(&self, names: Option<&BTreeSet<String>>) -> Vec<Value> {
+        names
+            .into_iter()
+            .flatten()
+            .filter_map(|name| self.nodes.get(name))
+            .map(|api| Value::String(api.clone()))
+            .collect()
+    }
+
+    /// Publisher update that every subscriber of the topic needs to receive.
+    fn publisher_update(&self, topic: &str) -> PublisherUpdate {
+        PublisherUpdate {
+            topic: topic.into(),
+            subscribers: self
+                .apis(self.subscribers.get(topic))
+                .into_iter()
+                .filter_map(|api| match api {
+                    Value::String(api) => Some(api),
+                    _ => None,
+                })
+                .collect(),
+            publishers: self.apis(self.publishers.get(topic)),
+        }
+    }
+}
+
+struct PublisherUpdate {
+    topic: String,
+    subscribers: Vec<String>,
+    publishers: Vec<Value>,
+}
+
+impl PublisherUpdate {
+    /// Notifies subscribers in the background, since they might be waiting for the master.
+    fn send(self) {
+        thread::spawn(move || {
+            for subscriber in self.subscribers {
+                let params = vec![
+                    Value::String(MASTER_CALLER_ID.into()),
+                    Value::String(self.topic.clone()),
+                    Value::Array(self.publishers.clone()),
+                ];
+                let result = rosxmlrpc::Client::new(&subscriber)
+                    .map_err(|err| ResponseError::Client(err.to_string()))
+                    .and_then(|client| client.request_tree_with_tree("publisherUpdate", params));
+                if let Err(err) = result {
+                    error!("Failed to update publishers of '{}': {}", self.topic, err);
+                }
+            }
+        });
+    }
+}
+
+/// Shuts down a replaced node in the background, with the message sent by rosmaster.
+fn shut_down_node(api: String) {
+    thread::spawn(move || {
+        let params = vec![
+            Value::String(MASTER_CALLER_ID.into()),
+            Value::String("new node registered with same name".into()),
+        ];
+        let result = rosxmlrpc::Client::new(&api)
+            .map_err(|err| ResponseError::Client(err.to_string()))
+            .and_then(|client| client.request_tree_with_tree("shutdown", params));
+        if let Err(err) = result {
+            error!("Failed to shut down node at '{}': {}", api, err);
+        }
+    });
+}
+
+fn register_handlers(server: &mut Server, state: &Arc<Mutex<MasterState>>) {
+    let handle = |server: &mut Server, name: &'static str, msg: &'static str, handler: Handler| {
+        let state = Arc::clone(state);
+        server.register_value(name, msg, move |params| {
+            let mut args = Arguments::new(params);
+            let caller_id = args.string("caller_id")?;
+            let (value, update) = {
+                let mut state = state.lock().expect(FAILED_TO_LOCK);
+                handler(&mut state, &caller_id, &mut args)?
+            };
+            if let Some(update) = update {
+                update.send();
+            }
+            Ok(value)
+        });
+    };
+
+    handle(
+        server,
+        "registerPublisher",
+        "Registered publisher",
+        |state, caller_id, args| {
+            let topic = args.string("topic")?;
+            let topic_type = args.string("topic_type")?;
+            let caller_api = args.string("caller_api")?;
+            state.register_node(caller_id, caller_api);
+            state.topic_types.insert(topic.clone(), topic_type);
+            add(&mut state.publishers, &topic, caller_id);
+            let subscribers = state.apis(state.subscribers.get(&topic));
+            Ok((
+                Value::Array(subscribers),
+                Some(state.publisher_update(&topic)),
+            ))
+        },
+    );
+
+    handle(
+        server,
+        "unregisterPublisher",
+        "Unregistered publisher",
+        |state, caller_id, args| {
+            let topic = args.string("topic")?;
+            let removed = remove(&mut state.publishers, &topic, caller_id);
+            Ok((count(removed), Some(state.publisher_update(&topic))))
+        },
+    );
+
+    handle(
+        server,
+        "registerSubscriber",
+        "Registered subscriber",
+        |state, caller_id, args| {
+            let topic = args.string("topic")?;
+            let topic_type = args.string("topic_type")?;
+            let caller_api = args.string("caller_api")?;
+            state.register_node(caller_id, caller_api);
+            if topic_type != "*" {
+                state.topic_types.entry(topic.clone()).or_insert(topic_type);
+            }
+            add(&mut state.subscribers, &topic, caller_id);
+            Ok((Value::Array(state.apis(state.publishers.get(&topic))), None))
+        },
+    );
+
+    handle(
+        server,
+        "unregisterSubscriber",
+        "Unregistered subscriber",
+        |state, caller_id, args| {
+            let topic = args.string("topic")?;
+            Ok((
+                count(remove(&mut state.subscribers, &topic, caller_id)),
+                None,
+            ))
+        },
+    );
+
+    handle(
+        server,
+        "registerService",
+        "Registered service",
+        |state, caller_id, args| {
+            let service = args.string("service")?;
+            let service_api = args.string("service_api")?;
+            let caller_api = args.string("caller_api")?;
+            state.register_node(caller_id, caller_api);
+            state
+                .services
+                .insert(service, (caller_id.into(), service_api));
+            Ok((Value::Int(1), None))
+        },
+    );
+
+    handle(
+        server,
+        "unregisterService",
+        "Unregistered service",
+        |state, caller_id, args| {
+            let service = args.string("service")?;
+            let service_api = args.string("service_api")?;
+            let registered = state.services.get(&service) == Some(&(caller_id.into(), service_api));
+            if registered {
+                state.services.remove(&service);
+            }
+            Ok((count(registered), None))
+        },
+    );
+
+    handle(
+        server,
+        "lookupService",
+        "Service URI",
+        |state, _caller_id, args| {
+            let service = args.string("service")?;
+            match state.services.get(&service) {
+                Some((_, api)) => Ok((Value::String(api.clone()), None)),
+                None => Err(ResponseError::Server(format!(
+                    "No provider for {}",
+                    service
+                ))),
+            }
+        },
+    );
+
+    handle(
+        server,
+        "lookupNode",
+        "Node URI",
+        |state, _caller_id, args| {
+            let node = args.string("node")?;
+            match state.nodes.get(&node) {
+                Some(api) => Ok((Value::String(api.clone()), None)),
+                None => Err(ResponseError::Server(format!("Unknown node {}", node))),
+            }
+        },
+    );
+
+    handle(
+        server,
+        "getPublishedTopics",
+        "Published topics",
+        |state, _caller_id, _args| {
+            let topics = state
+                .publishers
+                .iter()
+                .filter(|(_, publishers)| !publishers.is_empty())
+                .map(|(topic, _)| topic_entry(state, topic))
+                .collect();
+            Ok((Value::Array(topics), None))
+        },
+    );
+
+    handle(
+        server,
+        "getTopicTypes",
+        "Topic types",
+        |state, _caller_id, _args| {
+            let topics = state
+                .topic_types
+                .keys()
+                .map(|topic| topic_entry(state, topic))
+                .collect();
+            Ok((Value::Array(topics), None))
+        },
+    );
+
+    handle(
+        server,
+        "getSystemState",
+        "System state",
+        |state, _caller_id, _args| {
+            let services = state
+                .services
+                .iter()
+                .map(|(service, (provider, _))| {
+                    (service.clone(), BTreeSet::from([provider.clone()]))
+                })
+                .collect();
+            Ok((
+                Value::Array(vec![
+                    system_state_entries(&state.publishers),
+                    system_state_entries(&state.subscribers),
+                    system_state_entries(&services),
+                ]),
+                None,
+            ))
+        },
+    );
+
+    handle(
+        server,
+        "getUri",
+        "Master URI",
+        |state, _caller_id, _args| Ok((Value::String(state.uri.clone()), None)),
+    );
+
+    handle(
+        server,
+        "getParam",
+        "Parameter value",
+        |state, caller_id, args| {
+            let key = resolve(caller_id, &args.string("key")?);
+            match state.params.get(&key) {
+                Some(value) => Ok((value, None)),
+                None => Err(ResponseError::Server(format!(
+                    "Parameter {} is not set",
+                    key
+                ))),
+            }
+        },
+    );
+
+    handle(
+        server,
+        "setParam",
+        "Parameter set",
+        |state, caller_id, args| {
+            let key = resolve(caller_id, &args.string("key")?);
+            state.params.set(&key, args.value("value")?);
+            Ok((Value::Int(0), None))
+        },
+    );
+
+    handle(
+        server,
+        "deleteParam",
+        "Parameter deleted",
+        |state, caller_id, args| {
+            let key = resolve(caller_id, &args.string("key")?);
+            if state.params.delete(&key) {
+                Ok((Value::Int(0), None))
+            } else {
+                Err(ResponseError::Server(format!(
+                    "Parameter {} is not set",
+                    key
+                )))
+            }
+        },
+    );
+
+    handle(
+        server,
+        "hasParam",
+        "Parameter existence",
+        |state, caller_id, args| {
+            let key = resolve(caller_id, &args.string("key")?);
+            Ok((Value::Bool(state.params.get(&key).is_some()), None))
+        },
+    );
+
+    handle(
+        server,
+        "searchParam",
+        "Parameter search",
+        |state, caller_id, args| {
+            let key = args.string("key")?;
+            match state.params.search(caller_id, &key) {
+                Some(found) => Ok((Value::String(found), None)),
+                None => Err(ResponseError::Server(format!(
+                    "Parameter {} not found",
+                    key
+                ))),
+            }
+        },
+    );
+
+    handle(
+        server,
+        "getParamNames",
+        "Parameter names",
+        |state, _caller_id, _args| {
+            let names = state
+                .params
+                .names()
+                .into_iter()
+                .map(Value::String)
+                .collect();
+            Ok((Value::Array(names), None))
+        },
+    );
+
+    // Parameter updates are never sent, so subscribing only reads the current value
+    handle(
+        server,
+        "subscribeParam",
+        "Parameter subscribed",
+        |state, caller_id, args| {
+            let _caller_api = args.string("caller_api")?;
+            let key = resolve(caller_id, &args.string("key")?);
+            let value = state
+                .params
+                .get(&key)
+                .unwrap_or_else(|| Value::Struct(HashMap::new()));
+            Ok((value, None))
+        },
+    );
+
+    handle(
+        server,
+        "unsubscribeParam",
+        "Parameter unsubscribed",
+        |_state, _caller_id, _args| Ok((Value::Int(1), None)),
+    );
+}
+
+type Handler =
+    fn(&mut MasterState, &str, &mut Arguments) -> Response<(Value, Option<PublisherUpdate>)>;
+
+struct Arguments {
+    items: std::vec::IntoIter<Value>,
+}
+
+impl Arguments {
+    fn new(params: Params) -> Self {
+        // Some clients wrap all arguments in a single array
+        let params = match params.first() {
+            Some(Value::Array(items)) if params.len() == 1 => items.clone(),
+            _ => params,
+        };
+        Arguments {
+            items: params.into_iter(),
+        }
+    }
+
+    fn value(&mut self, name: &str) -> Response<Value> {
+        self.items
+            .next()
+            .ok_or_else(|| ResponseError::Client(format!("Missing argument '{}'", name)))
+    }
+
+    fn string(&mut self, name: &str) -> Response<String> {
+        match self.value(name)? {
+            Value::String(value) => Ok(value),
+            _ => Err(ResponseError::Client(format!(
+                "Argument '{}' needs to be a string",
+                name
+            ))),
+        }
+    }
+}
+
+fn add(registrations: &mut BTreeMap<String, BTreeSet<String>>, topic: &str, caller_id: &str) {
+    registrations
+        .entry(topic.into())
+        .or_default()
+        .insert(caller_id.into());
+}
+
+fn remove(
+    registrations: &mut BTreeMap<String, BTreeSet<String>>,
+    topic: &str,
+    caller_id: &str,
+) -> bool {
+    let removed = match registrations.get_mut(topic) {
+        Some(names) => names.remove(caller_id),
+        None => false,
+    };
+    if registrations.get(topic).is_some_and(BTreeSet::is_empty) {
+        registrations.remove(topic);
+    }
+    removed
+}
+
+fn registered(registrations: &BTreeMap<String, BTreeSet<String>>, topic: &str) -> Vec<String> {
+    registrations
+        .get(topic)
+        .map(|names| names.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+fn count(done: bool) -> Value {
+    Value::Int(if done { 1 } else { 0 })
+}
+
+fn topic_entry(state: &MasterState, topic: &str) -> Value {
+    Value::Array(vec![
+        Value::String(topic.into()),
+        Value::String(state.topic_types.get(topic).cloned().unwrap_or_default()),
+    ])
+}
+
+fn system_state_entries(registrations: &BTreeMap<String, BTreeSet<String>>) -> Value {
+    Value::Array(
+        registrations
+            .iter()
+            .map(|(name, nodes)| {
+                Value::Array(vec![
+                    Value::String(name.clone()),
+                    Value::Array(nodes.iter().cloned().map(Value::String).collect()),
+                ])
+            })
+            .collect(),
+    )
+}
+
+/// Resolves a parameter key relative to the namespace of the caller.
+fn resolve(caller_id: &str, key: &str) -> String {
+    if key.starts_with('/') {
+        return key.into();
+    }
+    format!("{}/{}", namespace_of(caller_id), key)
+}
+
+fn namespace_of(name: &str) -> &str {
+    match name.rfind('/') {
+        Some(index) => &name[..index],
+        None => "",
+    }
+}
+
+/// Parameter server, storing leaf values by their full key.
+#[derive(Default)]
+struct ParamTree {
+    leaves: BTreeMap<String, Value>,
+}
+
+impl ParamTree {
+    fn set(&mut self, key: &str, value: Value) {
+        let key = normalize(key);
+        self.delete(&key);
+        match value {
+            Value::Struct(ref members) if !members.is_empty() => {
+                for (name, member) in members.clone() {
+                    self.set(&format!("{}/{}", key, name), member);
+                }
+            }
+            value => {
+                self.leaves.insert(key, value);
+            }
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<Value> {
+        let key = normalize(key);
+        if let Some(value) = self.leaves.get(&key) {
+            return Some(value.clone());
+        }
+        let prefix = format!("{}/", key.trim_end_matches('/'));
+        let mut root = HashMap::new();
+        let mut found = false;
+        for (leaf, value) in self.leaves.range(prefix.clone()..) {
+            let relative = match leaf.strip_prefix(&prefix) {
+                Some(relative) => relative,
+                None => break,
+            };
+            found = true;
+            insert_nested(&mut root, relative, value.clone());
+        }
+        if found {
+            Some(Value::Struct(root))
+        } else {
+            None
+        }
+    }
+
+    fn delete(&mut self, key: &str) -> bool {
+        let key = normalize(key);
+        let prefix = format!("{}/", key.trim_end_matches('/'));
+        let before = self.leaves.len();
+        self.leaves
+            .retain(|leaf, _| *leaf != key && !leaf.starts_with(&prefix));
+        self.leaves.len() != before
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.leaves.keys().cloned().collect()
+    }
+
+    /// Finds the key in the closest namespace of the caller, like `searchParam` of roscore.
+    fn search(&self, caller_id: &str, key: &str) -> Option<String> {
+        if key.starts_with('/') {
+            return self.get(key).map(|_| key.into());
+        }
+        let first = key.split('/').next().unwrap_or(key);
+        let mut namespace = namespace_of(caller_id);
+        loop {
+            if self.get(&format!("{}/{}", namespace, first)).is_some() {
+                return Some(format!("{}/{}", namespace, key));
+            }
+            if namespace.is_empty() {
+                return None;
+            }
+            namespace = namespace_of(namespace);
+        }
+    }
+}
+
+fn normalize(key: &str) -> String {
+    if key == "/" {
+        return String::new();
+    }
+    key.trim_end_matches('/').into()
+}
+
+fn insert_nested(root: &mut HashMap<String, Value>, path: &str, value: Value) {
+    match path.find('/') {
+        None => {
+            root.insert(path.into(), value);
+        }
+        Some(index) => {
+            let child = root
+                .entry(path[..index].into())
+                .or_insert_with(|| Value::Struct(HashMap::new()));
+            if let Value::Struct(ref mut members) = *child {
+                insert_nested(members, &path[index + 1..], value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn param_tree_nests_and_searches() {
+        let mut params = ParamTree::default();
+        let mut members = HashMap::new();
+        members.insert("a".into(), Value::Int(1));
+        members.insert("b".into(), Value::String("x".into()));
+        params.set("/ns/group", Value::Struct(members));
+        params.set("/top", Value::Bool(true));
+
+        assert_eq!(Some(Value::Int(1)), params.get("/ns/group/a"));
+        match params.get("/ns") {
+            Some(Value::Struct(ns)) => match ns.get("group") {
+                Some(Value::Struct(group)) => assert_eq!(2, group.len()),
+                other => panic!("Unexpected group: {:?}", other),
+            },
+            other => panic!("Unexpected namespace: {:?}", other),
+        }
+        assert_eq!(3, params.names().len());
+        assert_eq!(
+            Some("/ns/group/a".into()),
+            params.search("/ns/node", "group/a")
+        );
+        assert_eq!(Some("/top".into()), params.search("/ns/node", "top"));
+        assert_eq!(None, params.search("/ns/node", "missing"));
+
+        assert!(params.delete("/ns/group"));
+        assert!(!params.delete("/ns/group"));
+        assert_eq!(None, params.get("/ns"));
+        assert!(params.get("/").is_some());
+    }
+}
@@ -0,0 +1,112 @@
+//! Helpers for testing nodes without a ROS installation.
+//!
+//! `MockMaster` runs a master inside the test process, and creates nodes connected to it.
+//! Like rosmaster, it shuts down a node when another one registers with the same name.
+//! `TopicRecorder` collects the messages of a topic, and fails tests that do not receive
+//! the expected messages in time. This module requires the `testing` feature.
+
+pub use self::master::MockMaster;
+pub use self::recorder::TopicRecorder;
+
+mod master;
+mod recorder;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::roscpp::GetLoggers;
+    use crate::msg::rosgraph_msgs::{Clock, Log};
+    use crate::{ShutdownReason, Time};
+    use std::time::Duration;
+
+    static TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn nodes_communicate_through_mock_master() {
+        let master = MockMaster::new().unwrap();
+        let talker = master.node("talker").unwrap();
+        let listener = master.node("listener").unwrap();
+
+        let recorder = TopicRecorder::<Clock>::new(&listener, "/clock").unwrap();
+        let publisher = talker.publish::<Clock>("/clock", 10).unwrap();
+        publisher.wait_for_subscribers(1, Some(TIMEOUT)).unwrap();
+        publisher
+            .send(Clock {
+                clock: Time { sec: 4, nsec: 2 },
+            })
+            .unwrap();
+        assert_eq!(
+            Time { sec: 4, nsec: 2 },
+            recorder.expect_message(TIMEOUT).clock
+        );
+        assert_eq!(vec!["/talker"], master.publishers("/clock"));
+        assert_eq!(vec!["/listener"], master.subscribers("/clock"));
+
+        let client = listener
+            .client::<GetLoggers>("/talker/get_loggers")
+            .unwrap();
+        assert!(client.req(&Default::default()).unwrap().is_ok());
+
+        listener.param("~rate").unwrap().set(&10).unwrap();
+        assert_eq!(
+            Some(xml_rpc::Value::Int(10)),
+            master.param("/listener/rate")
+        );
+        let rate = talker.param("/listener/rate").unwrap().get::<i32>();
+        assert_eq!(10, rate.unwrap());
+    }
+
+    #[test]
+    fn wait_for_message_observes_existing_subscription() {
+        let master = MockMaster::new().unwrap();
+        let talker = master.node("talker").unwrap();
+        let listener = master.node("listener").unwrap();
+
+        let recorder = TopicRecorder::<Clock>::new(&listener, "clock").unwrap();
+        let publisher = talker.publish::<Clock>("/clock", 10).unwrap();
+        publisher.wait_for_subscribers(1, Some(TIMEOUT)).unwrap();
+        let sender = std::thread::spawn(move || {
+            for sec in 1..=20 {
+                publisher
+                    .send(Clock {
+                        clock: Time { sec, nsec: 0 },
+                    })
+                    .unwrap();
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        });
+        let message = listener
+            .wait_for_message::<Clock>("/clock", Some(TIMEOUT))
+            .unwrap();
+        assert!(message.clock.sec >= 1);
+        assert!(listener
+            .wait_for_message::<Log>("/clock", Some(TIMEOUT))
+            .is_err());
+        sender.join().unwrap();
+        assert_eq!(20, recorder.expect_messages(20, TIMEOUT).len());
+    }
+
+    #[test]
+    fn duplicate_name_shuts_down_previous_node() {
+        let master = MockMaster::new().unwrap();
+        let first = master.node("talker").unwrap();
+        let _second = master.node("talker").unwrap();
+        let start = std::time::Instant::now();
+        while first.shutdown_reason().is_none() && start.elapsed() < TIMEOUT {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        match first.shutdown_reason() {
+            Some(ShutdownReason::DuplicateName(..)) => {}
+            reason => panic!("Expected duplicate name shutdown, got {:?}", reason),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected 1 messages on topic '/clock'")]
+    fn recorder_fails_without_messages() {
+        let master = MockMaster::new().unwrap();
+        let listener = master.node("listener").unwrap();
+        let recorder = TopicRecorder::<Clock>::new(&listener, "/clock").unwrap();
+        recorder.expect_message(Duration::from_millis(100));
+    }
+}
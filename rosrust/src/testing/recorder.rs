@@ -0,0 +1,73 @@
+use crate::api::error::Result;
+use crate::util::FAILED_TO_LOCK;
+use crate::{Message, Ros, Subscriber};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Subscriber that keeps every message it receives, for assertions in tests.
+pub struct TopicRecorder<T> {
+    topic: String,
+    messages: Arc<(Mutex<Vec<T>>, Condvar)>,
+    _subscriber: Subscriber,
+}
+
+impl<T: Message> TopicRecorder<T> {
+    pub fn new(ros: &Ros, topic: &str) -> Result<Self> {
+        let messages = Arc::new((Mutex::new(Vec::new()), Condvar::new()));
+        let target = Arc::clone(&messages);
+        let subscriber = ros.subscribe::<T, _>(topic, 0, move |message| {
+            let (ref messages, ref received) = *target;
+            messages.lock().expect(FAILED_TO_LOCK).push(message);
+            received.notify_all();
+        })?;
+        Ok(TopicRecorder {
+            topic: topic.into(),
+            messages,
+            _subscriber: subscriber,
+        })
+    }
+
+    /// Messages received so far, in the order of arrival.
+    pub fn messages(&self) -> Vec<T> {
+        self.messages.0.lock().expect(FAILED_TO_LOCK).clone()
+    }
+
+    /// Waits until at least `count` messages arrive, returning all of them.
+    ///
+    /// Returns `None` if fewer messages arrived before the timeout, measured in wall time.
+    pub fn wait_for_messages(&self, count: usize, timeout: Duration) -> Option<Vec<T>> {
+        let deadline = Instant::now() + timeout;
+        let (ref messages, ref received) = *self.messages;
+        let mut messages = messages.lock().expect(FAILED_TO_LOCK);
+        while messages.len() < count {
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            messages = received
+                .wait_timeout(messages, deadline - now)
+                .expect(FAILED_TO_LOCK)
+                .0;
+        }
+        Some(messages.clone())
+    }
+
+    /// Returns the first message, panicking if none arrives within the timeout.
+    pub fn expect_message(&self, timeout: Duration) -> T {
+        self.expect_messages(1, timeout).remove(0)
+    }
+
+    /// Returns at least `count` messages, panicking if fewer arrive within the timeout.
+    pub fn expect_messages(&self, count: usize, timeout: Duration) -> Vec<T> {
+        match self.wait_for_messages(count, timeout) {
+            Some(messages) => messages,
+            None => panic!(
+                "Expected {} messages on topic '{}' within {:?}, received {}",
+                count,
+                self.topic,
+                timeout,
+                self.messages.0.lock().expect(FAILED_TO_LOCK).len()
+            ),
+        }
+    }
+}
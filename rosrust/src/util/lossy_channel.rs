@@ -38,6 +38,11 @@ impl<T> LossySender<T> {
         Ok(())
     }
 
+    /// Number of messages waiting to be received.
+    pub fn len(&self) -> usize {
+        self.data_rx.len()
+    }
+
     pub fn close(&mut self) -> Result<(), channel::SendError<()>> {
         self.is_open.store(false, Ordering::SeqCst);
         self.killer.send()
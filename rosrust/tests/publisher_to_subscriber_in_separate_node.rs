@@ -0,0 +1,75 @@
+use crossbeam::channel::unbounded;
+use rosrust::Ros;
+use std::env;
+
+mod util;
+
+mod msg {
+    rosrust::rosmsg_include!(std_msgs / String);
+}
+
+#[test]
+fn publisher_to_subscriber_in_separate_node() {
+    let _roscore = util::run_roscore_for(util::Language::None, util::Feature::Composition);
+    let master_uri = env::var("ROS_MASTER_URI").unwrap();
+
+    let talker = Ros::builder("talker")
+        .master_uri(&master_uri)
+        .namespace("/composed")
+        .build()
+        .unwrap();
+    let listener = Ros::builder("listener")
+        .master_uri(&master_uri)
+        .namespace("/composed")
+        .remap("heard", "/composed/chatter")
+        .param("~greeting", "hello world")
+        .build()
+        .unwrap();
+
+    assert_eq!(talker.name(), "/composed/talker");
+    assert_eq!(listener.name(), "/composed/listener");
+    assert_ne!(talker.uri(), listener.uri());
+    assert_eq!(
+        listener
+            .param("~greeting")
+            .unwrap()
+            .get::<String>()
+            .unwrap(),
+        "hello world"
+    );
+
+    let (tx, rx) = unbounded();
+
+    let subscriber = listener
+        .subscribe::<msg::std_msgs::String, _>("heard", 100, move |data| {
+            tx.send(data.data).unwrap();
+        })
+        .unwrap();
+
+    let publisher = talker
+        .publish::<msg::std_msgs::String>("chatter", 100)
+        .unwrap();
+
+    let message = msg::std_msgs::String {
+        data: "hello world".to_owned(),
+    };
+
+    let mut rate = talker.rate(10.0);
+    let mut received = false;
+    for _ in 0..50 {
+        publisher.send(message.clone()).unwrap();
+        rate.sleep();
+        if rx.try_iter().any(|data| data == "hello world") {
+            received = true;
+            break;
+        }
+    }
+    assert!(received, "Failed to receive data from the other node");
+
+    assert_eq!(publisher.subscriber_count(), 1);
+    assert_eq!(subscriber.publisher_count(), 1);
+
+    talker.shutdown_sender().shutdown();
+    assert!(!talker.is_ok());
+    assert!(listener.is_ok());
+}
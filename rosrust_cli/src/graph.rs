@@ -0,0 +1,17 @@
+use clap::ArgMatches;
+use rosrust::error::Result;
+use std::fs;
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    // The tool's own node is not part of the system being inspected
+    let graph = rosrust::graph()?.without_node(&rosrust::name());
+    let data = match matches.value_of("format") {
+        Some("json") => graph.to_json() + "\n",
+        _ => graph.to_dot(),
+    };
+    match matches.value_of("file") {
+        Some(file) if file != "-" => fs::write(file, data)?,
+        _ => print!("{}", data),
+    }
+    Ok(())
+}
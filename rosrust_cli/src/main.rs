@@ -0,0 +1,286 @@
+use clap::{App, AppSettings, Arg, SubCommand};
+
+mod graph;
+mod node;
+mod param;
+mod service;
+mod stats;
+mod topic;
+
+fn main() {
+    let topic_arg = || {
+        Arg::with_name("topic")
+            .help("Name of the topic")
+            .required(true)
+    };
+    let window_arg = || {
+        Arg::with_name("window")
+            .short("w")
+            .long("window")
+            .takes_value(true)
+            .help("Number of messages to average over")
+    };
+    let service_arg = || {
+        Arg::with_name("service")
+            .help("Name of the service")
+            .required(true)
+    };
+    let node_arg = || {
+        Arg::with_name("node")
+            .help("Name of the node")
+            .required(true)
+    };
+    let name_arg = || {
+        Arg::with_name("name")
+            .help("Name of the parameter")
+            .required(true)
+    };
+    let file_arg = || {
+        Arg::with_name("file")
+            .help("Path to the file")
+            .required(true)
+    };
+    let namespace_arg = || Arg::with_name("namespace").help("Namespace of the parameters");
+    let matches = App::new("rosrust_cli")
+        .about("Command line tools for ROS, written with rosrust")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("topic")
+                .about("Inspects and interacts with topics")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("Lists active topics")
+                        .arg(
+                            Arg::with_name("verbose")
+                                .short("v")
+                                .long("verbose")
+                                .help("Lists types, publishers and subscribers"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("info")
+                        .about("Prints information about a topic")
+                        .arg(topic_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("type")
+                        .about("Prints the type of a topic")
+                        .arg(topic_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("echo")
+                        .about("Prints messages to the screen")
+                        .arg(topic_arg())
+                        .arg(
+                            Arg::with_name("count")
+                                .short("n")
+                                .takes_value(true)
+                                .help("Exits after printing this many messages"),
+                        )
+                        .arg(
+                            Arg::with_name("noarr")
+                                .long("noarr")
+                                .help("Hides the contents of arrays"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("hz")
+                        .about("Displays the publishing rate of a topic")
+                        .arg(topic_arg())
+                        .arg(window_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("bw")
+                        .about("Displays the bandwidth used by a topic")
+                        .arg(topic_arg())
+                        .arg(window_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("delay")
+                        .about("Displays the delay of a topic, based on the header timestamp")
+                        .arg(topic_arg())
+                        .arg(window_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("pub")
+                        .about("Publishes data to a topic")
+                        .setting(AppSettings::AllowLeadingHyphen)
+                        .arg(topic_arg())
+                        .arg(
+                            Arg::with_name("type")
+                                .help("Type of the message")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("args")
+                                .help("Message fields, in YAML")
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("rate")
+                                .short("r")
+                                .long("rate")
+                                .takes_value(true)
+                                .help("Publishing rate in Hz"),
+                        )
+                        .arg(
+                            Arg::with_name("once")
+                                .short("1")
+                                .long("once")
+                                .help("Publishes once and exits"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("service")
+                .about("Inspects and calls services")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("Lists active services")
+                        .arg(
+                            Arg::with_name("nodes")
+                                .short("n")
+                                .long("nodes")
+                                .help("Lists the nodes providing each service"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("info")
+                        .about("Prints information about a service")
+                        .arg(service_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("type")
+                        .about("Prints the type of a service")
+                        .arg(service_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("call")
+                        .about("Calls a service with the provided arguments")
+                        .setting(AppSettings::AllowLeadingHyphen)
+                        .arg(service_arg())
+                        .arg(
+                            Arg::with_name("args")
+                                .help("Request fields, in YAML")
+                                .multiple(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("node")
+                .about("Inspects and manages nodes")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(SubCommand::with_name("list").about("Lists active nodes"))
+                .subcommand(
+                    SubCommand::with_name("info")
+                        .about("Prints information about a node")
+                        .arg(node_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("ping")
+                        .about("Tests connectivity to nodes")
+                        .arg(node_arg().required_unless("all"))
+                        .arg(
+                            Arg::with_name("all")
+                                .short("a")
+                                .long("all")
+                                .help("Pings every node"),
+                        )
+                        .arg(
+                            Arg::with_name("count")
+                                .short("c")
+                                .takes_value(true)
+                                .help("Exits after this many pings"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("kill")
+                        .about("Shuts down nodes")
+                        .arg(node_arg().multiple(true)),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("param")
+                .about("Reads and modifies the parameter server")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(SubCommand::with_name("list").about("Lists parameter names"))
+                .subcommand(
+                    SubCommand::with_name("get")
+                        .about("Prints a parameter value, in YAML")
+                        .arg(name_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("set")
+                        .about("Sets a parameter value, given in YAML")
+                        .setting(AppSettings::AllowLeadingHyphen)
+                        .arg(name_arg())
+                        .arg(
+                            Arg::with_name("value")
+                                .help("Value of the parameter")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("delete")
+                        .about("Deletes a parameter")
+                        .arg(name_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("load")
+                        .about("Loads parameters from a YAML file")
+                        .arg(file_arg())
+                        .arg(namespace_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("dump")
+                        .about("Dumps parameters to a YAML file, or to the screen for '-'")
+                        .arg(file_arg())
+                        .arg(namespace_arg()),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("graph")
+                .about("Exports the computation graph")
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["dot", "json"])
+                        .default_value("dot")
+                        .help("Output format"),
+                )
+                .arg(Arg::with_name("file").help("Path to the output file, or '-' for the screen")),
+        )
+        .get_matches_from(rosrust::args());
+
+    rosrust::try_init_with(
+        &format!("rosrust_cli_{}", std::process::id()),
+        rosrust::InitOptions {
+            no_log_file: true,
+            ..Default::default()
+        },
+    )
+    .expect("ROS init failed!");
+
+    let result = match matches.subcommand() {
+        ("topic", Some(args)) => topic::run(args),
+        ("service", Some(args)) => service::run(args),
+        ("node", Some(args)) => node::run(args),
+        ("param", Some(args)) => param::run(args),
+        ("graph", Some(args)) => graph::run(args),
+        _ => unreachable!(),
+    };
+
+    if let Err(err) = result {
+        let info = err
+            .iter()
+            .map(|v| format!("{}", v))
+            .collect::<Vec<_>>()
+            .join("\nCaused by: ");
+        eprintln!("ERROR: {}", info);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::topic::{global_name, parse_arg};
+use clap::ArgMatches;
+use rosrust::api::{BusDirection, TopicData};
+use rosrust::error::Result;
+use std::collections::HashMap;
+use std::time::Instant;
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        ("list", Some(_)) => list(),
+        ("info", Some(args)) => info(&node_arg(args)),
+        ("ping", Some(args)) => {
+            let nodes = if args.is_present("all") {
+                rosrust::nodes()?
+            } else {
+                vec![node_arg(args)]
+            };
+            ping(&nodes, parse_arg(args, "count")?)
+        }
+        ("kill", Some(args)) => kill(
+            &args
+                .values_of("node")
+                .map(|v| v.map(global_name).collect())
+                .unwrap_or_else(Vec::new),
+        ),
+        _ => unreachable!(),
+    }
+}
+
+fn node_arg(args: &ArgMatches) -> String {
+    global_name(args.value_of("node").unwrap_or(""))
+}
+
+fn list() -> Result<()> {
+    for node in rosrust::nodes()? {
+        println!("{}", node);
+    }
+    Ok(())
+}
+
+fn print_entries(title: &str, entries: &[TopicData], node: &str, types: &HashMap<String, String>) {
+    let mut names = entries
+        .iter()
+        .filter(|entry| entry.connections.iter().any(|v| v == node))
+        .map(|entry| entry.name.as_str())
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        println!("{} None\n", title);
+        return;
+    }
+    names.sort();
+    println!("{}", title);
+    for name in names {
+        match types.get(name) {
+            Some(datatype) => println!(" * {} [{}]", name, datatype),
+            None => println!(" * {}", name),
+        }
+    }
+    println!();
+}
+
+fn info(node: &str) -> Result<()> {
+    let types = rosrust::topics()?
+        .into_iter()
+        .map(|topic| (topic.name, topic.datatype))
+        .collect::<HashMap<_, _>>();
+    let state = rosrust::state()?;
+    println!("{}\nNode [{}]", "-".repeat(80), node);
+    print_entries("Publications:", &state.publishers, node, &types);
+    print_entries("Subscriptions:", &state.subscribers, node, &types);
+    print_entries("Services:", &state.services, node, &HashMap::new());
+
+    let remote = rosrust::remote_node(node)?;
+    println!("\ncontacting node {} ...", remote.uri());
+    println!("Pid: {}", remote.pid()?);
+    match remote.bus_info() {
+        Ok(connections) => {
+            println!("Connections:");
+            for connection in connections {
+                let direction = match connection.direction {
+                    BusDirection::Inbound => "inbound",
+                    BusDirection::Outbound => "outbound",
+                    BusDirection::Both => "inbound/outbound",
+                };
+                println!(" * topic: {}", connection.topic);
+                println!("    * to: {}", connection.destination_id);
+                println!("    * direction: {}", direction);
+                println!("    * transport: {}", connection.transport);
+            }
+        }
+        Err(err) => println!("Connections: unavailable ({})", err),
+    }
+    println!();
+    Ok(())
+}
+
+fn ping(nodes: &[String], count: Option<usize>) -> Result<()> {
+    for node in nodes {
+        println!("rosnode: node is [{}]", node);
+    }
+    let mut rounds = 0;
+    let mut rate = rosrust::rate(1.0);
+    while rosrust::is_ok() && count.is_none_or(|count| rounds < count) {
+        for node in nodes {
+            ping_node(node);
+        }
+        rounds += 1;
+        rate.sleep();
+    }
+    Ok(())
+}
+
+/// Pings a node once, reporting failures without stopping the other pings.
+fn ping_node(node: &str) {
+    // Looked up on every ping, so restarted nodes are reached at their new address
+    let remote = match rosrust::remote_node(node) {
+        Ok(remote) => remote,
+        Err(err) => {
+            eprintln!("ERROR: failed to look up node [{}]: {}", node, err);
+            return;
+        }
+    };
+    let start = Instant::now();
+    match remote.pid() {
+        Ok(_) => {
+            let elapsed = start.elapsed();
+            println!(
+                "xmlrpc reply from {}\ttime={:.6}ms",
+                remote.uri(),
+                elapsed.as_secs() as f64 * 1e3 + f64::from(elapsed.subsec_nanos()) * 1e-6
+            );
+        }
+        Err(err) => eprintln!(
+            "ERROR: connection to [{}] at {} failed: {}",
+            node,
+            remote.uri(),
+            err
+        ),
+    }
+}
+
+fn kill(nodes: &[String]) -> Result<()> {
+    for node in nodes {
+        rosrust::remote_node(node)?.shutdown(&format!("user request from {}", rosrust::name()))?;
+        println!("killed {}", node);
+    }
+    Ok(())
+}
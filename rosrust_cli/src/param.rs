@@ -0,0 +1,101 @@
+use clap::ArgMatches;
+use rosrust::error::Result;
+use rosrust::Parameter;
+use std::fs;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        ("list", Some(_)) => list(),
+        ("get", Some(args)) => get(&name_arg(args)),
+        ("set", Some(args)) => set(&name_arg(args), args.value_of("value").unwrap_or("")),
+        ("delete", Some(args)) => delete(&name_arg(args)),
+        ("load", Some(args)) => load(
+            args.value_of("file").unwrap_or(""),
+            args.value_of("namespace").unwrap_or("/"),
+        ),
+        ("dump", Some(args)) => dump(
+            args.value_of("file").unwrap_or(""),
+            args.value_of("namespace").unwrap_or("/"),
+        ),
+        _ => unreachable!(),
+    }
+}
+
+fn name_arg(args: &ArgMatches) -> String {
+    args.value_of("name").unwrap_or("").into()
+}
+
+fn param(name: &str) -> Result<Parameter> {
+    rosrust::param(name).ok_or_else(|| format!("Bad parameter name {}", name).into())
+}
+
+fn parse_yaml(data: &str) -> Result<Yaml> {
+    YamlLoader::load_from_str(data)
+        .map_err(|err| format!("Bad YAML data: {}", err))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "No YAML data provided".into())
+}
+
+fn yaml_string(value: &Yaml) -> String {
+    let mut output = String::new();
+    {
+        let mut emitter = YamlEmitter::new(&mut output);
+        // Writing into a string can not fail
+        let _ = emitter.dump(value);
+    }
+    output.trim_start_matches("---").trim_start().into()
+}
+
+fn list() -> Result<()> {
+    let mut names = rosrust::parameters()?;
+    names.sort();
+    for name in names {
+        println!("{}", name);
+    }
+    Ok(())
+}
+
+fn get(name: &str) -> Result<()> {
+    println!("{}", yaml_string(&param(name)?.get_yaml()?));
+    Ok(())
+}
+
+fn set(name: &str, value: &str) -> Result<()> {
+    param(name)?.set_yaml(parse_yaml(value)?)
+}
+
+fn delete(name: &str) -> Result<()> {
+    param(name)?.delete()?;
+    Ok(())
+}
+
+fn load(file: &str, namespace: &str) -> Result<()> {
+    let data = fs::read_to_string(file)?;
+    let items = match parse_yaml(&data)? {
+        Yaml::Hash(items) => items,
+        value => return param(namespace)?.set_yaml(value),
+    };
+    // Parameters are set one by one, because setting a dictionary
+    // replaces everything that was stored in the namespace before
+    let namespace = namespace.trim_end_matches('/');
+    for (key, value) in items {
+        let key = match key {
+            Yaml::String(key) => key,
+            key => return Err(format!("Bad parameter name {:?}", key).into()),
+        };
+        param(&format!("{}/{}", namespace, key))?.set_yaml(value)?;
+    }
+    Ok(())
+}
+
+fn dump(file: &str, namespace: &str) -> Result<()> {
+    let data = yaml_string(&param(namespace)?.get_yaml()?);
+    if file == "-" {
+        println!("{}", data);
+    } else {
+        fs::write(file, data + "\n")?;
+    }
+    Ok(())
+}
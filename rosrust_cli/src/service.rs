@@ -0,0 +1,87 @@
+use crate::topic::global_name;
+use clap::ArgMatches;
+use rosrust::dynamic::{find_service_definitions, to_yaml_string, DynamicMsg};
+use rosrust::error::Result;
+use rosrust::RawMessage;
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        ("list", Some(args)) => list(args.is_present("nodes")),
+        ("info", Some(args)) => info(&service_arg(args)),
+        ("type", Some(args)) => print_type(&service_arg(args)),
+        ("call", Some(args)) => call(
+            &service_arg(args),
+            &args
+                .values_of("args")
+                .map(Iterator::collect)
+                .unwrap_or_else(Vec::new),
+        ),
+        _ => unreachable!(),
+    }
+}
+
+fn service_arg(args: &ArgMatches) -> String {
+    global_name(args.value_of("service").unwrap_or(""))
+}
+
+fn list(nodes: bool) -> Result<()> {
+    let mut services = rosrust::state()?.services;
+    services.sort_by(|a, b| a.name.cmp(&b.name));
+    for service in services {
+        if nodes {
+            println!("{} {}", service.name, service.connections.join(" "));
+        } else {
+            println!("{}", service.name);
+        }
+    }
+    Ok(())
+}
+
+fn print_type(service: &str) -> Result<()> {
+    println!("{}", rosrust::service_type(service)?);
+    Ok(())
+}
+
+fn info(service: &str) -> Result<()> {
+    let nodes = rosrust::state()?
+        .services
+        .into_iter()
+        .filter(|entry| entry.name == service)
+        .flat_map(|entry| entry.connections)
+        .collect::<Vec<_>>();
+    let uri = rosrust::service_uri(service)?;
+    let datatype = rosrust::service_type(service)?;
+    println!("Node: {}", nodes.join(" "));
+    println!("URI: {}", uri);
+    println!("Type: {}", datatype);
+    match find_service_definitions(&datatype) {
+        Ok((request, _)) => {
+            let request = DynamicMsg::new(&format!("{}Request", datatype), &request)?;
+            let args = request
+                .msg()
+                .fields
+                .iter()
+                .filter(|field| !field.is_constant())
+                .map(|field| field.name.as_str())
+                .collect::<Vec<_>>();
+            println!("Args: {}", args.join(" "));
+        }
+        Err(err) => println!("Args: unknown ({})", err),
+    }
+    Ok(())
+}
+
+fn call(service: &str, args: &[&str]) -> Result<()> {
+    let datatype = rosrust::service_type(service)?;
+    let (request, response) = find_service_definitions(&datatype)?;
+    let request = DynamicMsg::new(&format!("{}Request", datatype), &request)?;
+    let response = DynamicMsg::new(&format!("{}Response", datatype), &response)?;
+    let message = request.encode_raw(&request.from_yaml_args(args)?)?;
+    match rosrust::client::<RawMessage>(service)?.req(&message)? {
+        Ok(message) => {
+            println!("{}", to_yaml_string(&response.decode_raw(&message)?));
+            Ok(())
+        }
+        Err(err) => Err(format!("Service call failed: {}", err).into()),
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::VecDeque;
+
+/// Summary of the values within a window.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Statistics {
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    pub std_dev: f64,
+    pub count: usize,
+}
+
+/// Keeps the last values, together with the time they were received at.
+pub struct Window {
+    size: usize,
+    samples: VecDeque<(f64, f64)>,
+}
+
+impl Window {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size: size.max(1),
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, time: f64, value: f64) {
+        if self.samples.back().is_some_and(|&(last, _)| last > time) {
+            // Time went backwards, which happens when simulated time restarts
+            self.samples.clear();
+        }
+        if self.samples.len() >= self.size {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((time, value));
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.samples.iter().map(|&(_, value)| value).sum()
+    }
+
+    /// Time passed since the oldest value in the window was received.
+    pub fn span(&self, now: f64) -> f64 {
+        self.samples.front().map_or(0.0, |&(time, _)| now - time)
+    }
+
+    pub fn statistics(&self) -> Option<Statistics> {
+        let count = self.samples.len();
+        if count == 0 {
+            return None;
+        }
+        let values = || self.samples.iter().map(|&(_, value)| value);
+        let mean = self.sum() / count as f64;
+        let variance = values().map(|v| (v - mean) * (v - mean)).sum::<f64>() / count as f64;
+        Some(Statistics {
+            mean,
+            min: values().fold(f64::INFINITY, f64::min),
+            max: values().fold(f64::NEG_INFINITY, f64::max),
+            std_dev: variance.sqrt(),
+            count,
+        })
+    }
+}
+
+/// Tracks the intervals between received messages.
+pub struct RateTracker {
+    last_time: Option<f64>,
+    intervals: Window,
+}
+
+impl RateTracker {
+    pub fn new(size: usize) -> Self {
+        Self {
+            last_time: None,
+            intervals: Window::new(size),
+        }
+    }
+
+    pub fn tick(&mut self, time: f64) {
+        if let Some(last_time) = self.last_time {
+            if time < last_time {
+                self.intervals.clear();
+            } else {
+                self.intervals.push(time, time - last_time);
+            }
+        }
+        self.last_time = Some(time);
+    }
+
+    /// Average rate in Hz, together with statistics of the intervals in seconds.
+    pub fn rate(&self) -> Option<(f64, Statistics)> {
+        let statistics = self.intervals.statistics()?;
+        if statistics.mean <= 0.0 {
+            return None;
+        }
+        Some((1.0 / statistics.mean, statistics))
+    }
+}
+
+/// Formats a byte count in the style of `rostopic bw`.
+pub fn format_bytes(bytes: f64) -> String {
+    if bytes < 1000.0 {
+        format!("{:.2}B", bytes)
+    } else if bytes < 1_000_000.0 {
+        format!("{:.2}KB", bytes / 1000.0)
+    } else {
+        format!("{:.2}MB", bytes / 1_000_000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_keeps_latest_values() {
+        let mut window = Window::new(3);
+        assert_eq!(None, window.statistics());
+        for (time, value) in &[(1.0, 4.0), (2.0, 1.0), (3.0, 2.0), (4.0, 3.0)] {
+            window.push(*time, *value);
+        }
+        assert_eq!(6.0, window.sum());
+        assert_eq!(3.0, window.span(5.0));
+        let statistics = window.statistics().unwrap();
+        assert_eq!(2.0, statistics.mean);
+        assert_eq!(1.0, statistics.min);
+        assert_eq!(3.0, statistics.max);
+        assert_eq!(3, statistics.count);
+        assert!((statistics.std_dev - (2.0f64 / 3.0).sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn window_resets_when_time_goes_back() {
+        let mut window = Window::new(10);
+        window.push(5.0, 1.0);
+        window.push(6.0, 1.0);
+        window.push(1.0, 3.0);
+        assert_eq!(3.0, window.sum());
+    }
+
+    #[test]
+    fn rate_tracker_measures_intervals() {
+        let mut tracker = RateTracker::new(10);
+        assert_eq!(None, tracker.rate());
+        for time in &[0.0, 0.1, 0.2, 0.3, 0.4] {
+            tracker.tick(*time);
+        }
+        let (rate, statistics) = tracker.rate().unwrap();
+        assert!((rate - 10.0).abs() < 1e-6);
+        assert_eq!(4, statistics.count);
+    }
+
+    #[test]
+    fn bytes_are_formatted_with_units() {
+        assert_eq!("12.00B", format_bytes(12.0));
+        assert_eq!("1.50KB", format_bytes(1500.0));
+        assert_eq!("2.25MB", format_bytes(2_250_000.0));
+    }
+}
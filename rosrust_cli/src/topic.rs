@@ -0,0 +1,356 @@
+use crate::stats::{format_bytes, RateTracker, Window};
+use clap::ArgMatches;
+use rosrust::dynamic::{to_yaml_string, DynamicMsg, Value};
+use rosrust::error::Result;
+use rosrust::{RawMessage, Subscriber};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        ("list", Some(args)) => list(args.is_present("verbose")),
+        ("info", Some(args)) => info(&topic_arg(args)),
+        ("type", Some(args)) => print_type(&topic_arg(args)),
+        ("echo", Some(args)) => echo(
+            &topic_arg(args),
+            parse_arg(args, "count")?,
+            args.is_present("noarr"),
+        ),
+        ("hz", Some(args)) => hz(
+            &topic_arg(args),
+            parse_arg(args, "window")?.unwrap_or(50000),
+        ),
+        ("bw", Some(args)) => bw(&topic_arg(args), parse_arg(args, "window")?.unwrap_or(100)),
+        ("delay", Some(args)) => delay(
+            &topic_arg(args),
+            parse_arg(args, "window")?.unwrap_or(50000),
+        ),
+        ("pub", Some(args)) => publish(
+            &topic_arg(args),
+            args.value_of("type").unwrap_or(""),
+            &args
+                .values_of("args")
+                .map(Iterator::collect)
+                .unwrap_or_else(Vec::new),
+            parse_arg(args, "rate")?,
+            args.is_present("once"),
+        ),
+        _ => unreachable!(),
+    }
+}
+
+fn topic_arg(args: &ArgMatches) -> String {
+    global_name(args.value_of("topic").unwrap_or(""))
+}
+
+pub fn global_name(name: &str) -> String {
+    if name.starts_with('/') {
+        name.into()
+    } else {
+        format!("/{}", name)
+    }
+}
+
+pub fn parse_arg<T: std::str::FromStr>(args: &ArgMatches, name: &str) -> Result<Option<T>> {
+    match args.value_of(name) {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Bad value for argument '{}': {}", name, value).into()),
+        None => Ok(None),
+    }
+}
+
+fn list(verbose: bool) -> Result<()> {
+    if !verbose {
+        let mut topics = rosrust::topics()?
+            .into_iter()
+            .map(|topic| topic.name)
+            .collect::<Vec<_>>();
+        topics.sort();
+        for topic in topics {
+            println!("{}", topic);
+        }
+        return Ok(());
+    }
+    let types = rosrust::topics()?
+        .into_iter()
+        .map(|topic| (topic.name, topic.datatype))
+        .collect::<HashMap<_, _>>();
+    let state = rosrust::state()?;
+    for (title, entries) in &[
+        ("Published topics:", &state.publishers),
+        ("Subscribed topics:", &state.subscribers),
+    ] {
+        println!("\n{}", title);
+        let mut entries = entries.iter().collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        for entry in entries {
+            let datatype = types.get(&entry.name).map_or("*", String::as_str);
+            let count = entry.connections.len();
+            let suffix = if count == 1 { "" } else { "s" };
+            let role = if *title == "Published topics:" {
+                "publisher"
+            } else {
+                "subscriber"
+            };
+            println!(
+                " * {} [{}] {} {}{}",
+                entry.name, datatype, count, role, suffix
+            );
+        }
+    }
+    println!();
+    Ok(())
+}
+
+fn topic_type(topic: &str) -> Result<Option<String>> {
+    Ok(rosrust::topics()?
+        .into_iter()
+        .find(|v| v.name == topic)
+        .map(|v| v.datatype))
+}
+
+fn print_type(topic: &str) -> Result<()> {
+    match topic_type(topic)? {
+        Some(datatype) => println!("{}", datatype),
+        None => return bail_unknown_topic(topic),
+    }
+    Ok(())
+}
+
+fn bail_unknown_topic(topic: &str) -> Result<()> {
+    Err(format!("Unknown topic {}", topic).into())
+}
+
+fn info(topic: &str) -> Result<()> {
+    let datatype = match topic_type(topic)? {
+        Some(datatype) => datatype,
+        None => return bail_unknown_topic(topic),
+    };
+    let state = rosrust::state()?;
+    println!("Type: {}\n", datatype);
+    for (title, entries) in &[
+        ("Publishers:", &state.publishers),
+        ("Subscribers:", &state.subscribers),
+    ] {
+        let nodes = entries
+            .iter()
+            .filter(|entry| entry.name == topic)
+            .flat_map(|entry| entry.connections.iter())
+            .collect::<Vec<_>>();
+        if nodes.is_empty() {
+            println!("{} None\n", title);
+            continue;
+        }
+        println!("{}", title);
+        for node in nodes {
+            println!(" * {}", node);
+        }
+        println!();
+    }
+    Ok(())
+}
+
+/// Subscribes to the topic, decoding messages with the definition sent by their publisher.
+fn subscribe_dynamic<F>(topic: &str, callback: F) -> Result<Subscriber>
+where
+    F: Fn(&RawMessage, Option<&DynamicMsg>) + Send + 'static,
+{
+    let definitions = Arc::new(Mutex::new(HashMap::<String, DynamicMsg>::new()));
+    let connection_definitions = Arc::clone(&definitions);
+    rosrust::subscribe_with_ids_and_headers(
+        topic,
+        100,
+        move |message: RawMessage, caller_id: &str| {
+            let definitions = definitions.lock().expect(FAILED_TO_LOCK);
+            callback(&message, definitions.get(caller_id));
+        },
+        move |headers| {
+            let caller_id = headers.get("callerid").cloned().unwrap_or_default();
+            match DynamicMsg::from_headers(&headers) {
+                Ok(definition) => {
+                    connection_definitions
+                        .lock()
+                        .expect(FAILED_TO_LOCK)
+                        .insert(caller_id, definition);
+                }
+                Err(err) => eprintln!("Cannot decode data from {}: {}", caller_id, err),
+            }
+        },
+    )
+}
+
+fn echo(topic: &str, count: Option<usize>, no_arrays: bool) -> Result<()> {
+    let received = AtomicUsize::new(0);
+    let _subscriber = subscribe_dynamic(topic, move |message, definition| {
+        let definition = match definition {
+            Some(definition) => definition,
+            None => return,
+        };
+        let mut value = match definition.decode_raw(message) {
+            Ok(value) => value,
+            Err(err) => {
+                eprintln!("Failed to decode message: {}", err);
+                return;
+            }
+        };
+        if no_arrays {
+            strip_arrays(&mut value);
+        }
+        let received = received.fetch_add(1, Ordering::SeqCst) + 1;
+        if count.is_some_and(|count| received > count) {
+            return;
+        }
+        println!("{}\n---", to_yaml_string(&value));
+        if count.is_some_and(|count| received >= count) {
+            rosrust::shutdown();
+        }
+    })?;
+    rosrust::spin();
+    Ok(())
+}
+
+fn strip_arrays(value: &mut Value) {
+    if let Value::Message(ref mut fields) = *value {
+        for field in fields.iter_mut() {
+            if let Value::Array(..) = field.1 {
+                field.1 = Value::String("<array type>".into());
+            } else {
+                strip_arrays(&mut field.1);
+            }
+        }
+    }
+}
+
+fn report_every_second<F: Fn()>(report: F) {
+    let mut rate = rosrust::rate(1.0);
+    while rosrust::is_ok() {
+        rate.sleep();
+        report();
+    }
+}
+
+fn hz(topic: &str, window: usize) -> Result<()> {
+    let tracker = Arc::new(Mutex::new(RateTracker::new(window)));
+    let subscriber_tracker = Arc::clone(&tracker);
+    let _subscriber = subscribe_dynamic(topic, move |_, _| {
+        subscriber_tracker
+            .lock()
+            .expect(FAILED_TO_LOCK)
+            .tick(rosrust::now().seconds());
+    })?;
+    println!("subscribed to [{}]", topic);
+    report_every_second(|| match tracker.lock().expect(FAILED_TO_LOCK).rate() {
+        Some((rate, statistics)) => println!(
+            "average rate: {:.3}\n\tmin: {:.3}s max: {:.3}s std dev: {:.5}s window: {}",
+            rate,
+            statistics.min,
+            statistics.max,
+            statistics.std_dev,
+            statistics.count + 1
+        ),
+        None => println!("no new messages"),
+    });
+    Ok(())
+}
+
+fn bw(topic: &str, window: usize) -> Result<()> {
+    let sizes = Arc::new(Mutex::new(Window::new(window)));
+    let subscriber_sizes = Arc::clone(&sizes);
+    let _subscriber = subscribe_dynamic(topic, move |message, _| {
+        subscriber_sizes
+            .lock()
+            .expect(FAILED_TO_LOCK)
+            .push(rosrust::now().seconds(), message.0.len() as f64);
+    })?;
+    println!("subscribed to [{}]", topic);
+    report_every_second(|| {
+        let sizes = sizes.lock().expect(FAILED_TO_LOCK);
+        let span = sizes.span(rosrust::now().seconds());
+        match sizes.statistics() {
+            Some(statistics) if span > 0.0 => println!(
+                "average: {}/s\n\tmean: {} min: {} max: {} window: {}",
+                format_bytes(sizes.sum() / span),
+                format_bytes(statistics.mean),
+                format_bytes(statistics.min),
+                format_bytes(statistics.max),
+                statistics.count
+            ),
+            _ => println!("no new messages"),
+        }
+    });
+    Ok(())
+}
+
+fn delay(topic: &str, window: usize) -> Result<()> {
+    let delays = Arc::new(Mutex::new(Window::new(window)));
+    let subscriber_delays = Arc::clone(&delays);
+    let _subscriber = subscribe_dynamic(topic, move |message, definition| {
+        let stamp = definition
+            .and_then(|definition| definition.decode_raw(message).ok())
+            .and_then(|value| value.get("header.stamp").and_then(Value::as_f64));
+        let stamp = match stamp {
+            Some(stamp) => stamp,
+            None => {
+                eprintln!("msg does not have header");
+                return;
+            }
+        };
+        let now = rosrust::now().seconds();
+        subscriber_delays
+            .lock()
+            .expect(FAILED_TO_LOCK)
+            .push(now, now - stamp);
+    })?;
+    println!("subscribed to [{}]", topic);
+    report_every_second(|| match delays.lock().expect(FAILED_TO_LOCK).statistics() {
+        Some(statistics) => println!(
+            "average delay: {:.3}\n\tmin: {:.3}s max: {:.3}s std dev: {:.5}s window: {}",
+            statistics.mean, statistics.min, statistics.max, statistics.std_dev, statistics.count
+        ),
+        None => println!("no new messages"),
+    });
+    Ok(())
+}
+
+fn publish(
+    topic: &str,
+    message_type: &str,
+    args: &[&str],
+    rate: Option<f64>,
+    once: bool,
+) -> Result<()> {
+    let definition = DynamicMsg::find(message_type)?;
+    let message = definition.encode_raw(&definition.from_yaml_args(args)?)?;
+    let mut publisher =
+        rosrust::publish_with_description::<RawMessage>(topic, 1, definition.description()?)?;
+    match rate {
+        Some(rate) => {
+            println!("publishing at {} Hz", rate);
+            let mut rate = rosrust::rate(rate);
+            while rosrust::is_ok() {
+                publisher.send(message.clone())?;
+                if once {
+                    break;
+                }
+                rate.sleep();
+            }
+        }
+        None => {
+            publisher.set_latching(true);
+            publisher.send(message)?;
+            if once {
+                println!("publishing and latching message for 3.0 seconds");
+                rosrust::sleep(rosrust::Duration::from_seconds(3));
+            } else {
+                println!("publishing and latching message. Press ctrl-C to terminate");
+                rosrust::spin();
+            }
+        }
+    }
+    Ok(())
+}
+
+static FAILED_TO_LOCK: &str = "Failed to acquire lock";
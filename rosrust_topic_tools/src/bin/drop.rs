@@ -0,0 +1,30 @@
+use rosrust_topic_tools::Dropper;
+
+fn main() {
+    let args = rosrust::args();
+    if args.len() < 4 || args.len() > 5 {
+        exit_with_usage();
+    }
+    let input = &args[1];
+    let x = args[2].parse().unwrap_or_else(|_| exit_with_usage());
+    let y = args[3].parse().unwrap_or_else(|_| exit_with_usage());
+    let output = args
+        .get(4)
+        .cloned()
+        .unwrap_or_else(|| format!("{}_drop", input));
+
+    rosrust::init(&format!("{}_drop", node_name_base(input)));
+
+    let _dropper = Dropper::new(input, &output, x, y).expect("Failed to start drop");
+
+    rosrust::spin();
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("usage: drop IN_TOPIC X Y [OUT_TOPIC]");
+    std::process::exit(1);
+}
+
+fn node_name_base(topic: &str) -> String {
+    topic.trim_start_matches('/').replace('/', "_")
+}
@@ -0,0 +1,17 @@
+use rosrust_topic_tools::Mux;
+
+fn main() {
+    let args = rosrust::args();
+    if args.len() < 3 {
+        eprintln!("usage: mux OUT_TOPIC IN_TOPIC1 [IN_TOPIC2 [...]]");
+        std::process::exit(1);
+    }
+    let output = &args[1];
+    let inputs = args[2..].iter().map(String::as_str).collect::<Vec<_>>();
+
+    rosrust::init("mux");
+
+    let _mux = Mux::new(output, &inputs).expect("Failed to start mux");
+
+    rosrust::spin();
+}
@@ -0,0 +1,32 @@
+use rosrust_topic_tools::Relay;
+
+fn main() {
+    let args = rosrust::args();
+    if args.len() < 2 || args.len() > 3 {
+        eprintln!("usage: relay IN_TOPIC [OUT_TOPIC]");
+        std::process::exit(1);
+    }
+    let input = &args[1];
+    let output = args
+        .get(2)
+        .cloned()
+        .unwrap_or_else(|| format!("{}_relay", input));
+
+    rosrust::init(&format!("{}_relay", node_name_base(input)));
+
+    let lazy = rosrust::param("~lazy")
+        .and_then(|param| param.get().ok())
+        .unwrap_or(false);
+    let _relay = if lazy {
+        Relay::new_lazy(input, &output)
+    } else {
+        Relay::new(input, &output)
+    }
+    .expect("Failed to start relay");
+
+    rosrust::spin();
+}
+
+fn node_name_base(topic: &str) -> String {
+    topic.trim_start_matches('/').replace('/', "_")
+}
@@ -0,0 +1,46 @@
+use rosrust_topic_tools::Throttle;
+
+static USAGE: &str = "usage: throttle messages IN_TOPIC MSGS_PER_SEC [OUT_TOPIC]
+       throttle bytes IN_TOPIC BYTES_PER_SEC WINDOW [OUT_TOPIC]";
+
+fn main() {
+    let args = rosrust::args();
+    if args.len() < 4 {
+        exit_with_usage();
+    }
+    let input = &args[2];
+    let default_output = format!("{}_throttle", input);
+
+    rosrust::init(&format!("{}_throttle", node_name_base(input)));
+
+    let _throttle = match args[1].as_str() {
+        "messages" if args.len() <= 5 => {
+            let rate = parse_arg(&args[3]);
+            let output = args.get(4).unwrap_or(&default_output);
+            Throttle::messages(input, output, rate)
+        }
+        "bytes" if args.len() >= 5 && args.len() <= 6 => {
+            let bytes_per_second = parse_arg(&args[3]);
+            let window = parse_arg(&args[4]);
+            let output = args.get(5).unwrap_or(&default_output);
+            Throttle::bytes(input, output, bytes_per_second, window)
+        }
+        _ => exit_with_usage(),
+    }
+    .expect("Failed to start throttle");
+
+    rosrust::spin();
+}
+
+fn parse_arg(value: &str) -> f64 {
+    value.parse().unwrap_or_else(|_| exit_with_usage())
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(1);
+}
+
+fn node_name_base(topic: &str) -> String {
+    topic.trim_start_matches('/').replace('/', "_")
+}
@@ -0,0 +1,79 @@
+use crate::output::{forward, Output, FAILED_TO_LOCK};
+use rosrust::error::Result;
+use rosrust::Subscriber;
+use std::sync::{Arc, Mutex};
+
+/// Forwards messages from one topic to another, while dropping a fixed share of them.
+///
+/// Dropping stops when the structure gets dropped.
+pub struct Dropper {
+    _subscriber: Subscriber,
+}
+
+impl Dropper {
+    /// Starts forwarding from `input` to `output`, dropping `x` out of every `y` messages.
+    ///
+    /// Fails if `y` is zero or smaller than `x`.
+    pub fn new(input: &str, output: &str, x: usize, y: usize) -> Result<Self> {
+        if y == 0 || x > y {
+            return Err(format!("Cannot drop {} out of every {} messages", x, y).into());
+        }
+        let counter = Mutex::new(DropCounter::new(x, y));
+        let output = Arc::new(Output::new(output));
+        let subscriber = forward(input, &output, move |_| {
+            counter.lock().expect(FAILED_TO_LOCK).admit()
+        })?;
+        Ok(Self {
+            _subscriber: subscriber,
+        })
+    }
+}
+
+struct DropCounter {
+    x: usize,
+    y: usize,
+    count: usize,
+}
+
+impl DropCounter {
+    fn new(x: usize, y: usize) -> Self {
+        Self { x, y, count: 0 }
+    }
+
+    fn admit(&mut self) -> bool {
+        self.count += 1;
+        let admitted = self.count > self.x;
+        if self.count >= self.y {
+            self.count = 0;
+        }
+        admitted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_x_out_of_y() {
+        let mut counter = DropCounter::new(2, 3);
+        let admitted = (0..9).map(|_| counter.admit()).collect::<Vec<_>>();
+        assert_eq!(
+            vec![false, false, true, false, false, true, false, false, true],
+            admitted
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_shares() {
+        assert!(Dropper::new("/in", "/out", 1, 0).is_err());
+        assert!(Dropper::new("/in", "/out", 0, 0).is_err());
+        assert!(Dropper::new("/in", "/out", 4, 3).is_err());
+    }
+
+    #[test]
+    fn drops_nothing_when_x_is_zero() {
+        let mut counter = DropCounter::new(0, 5);
+        assert!((0..12).all(|_| counter.admit()));
+    }
+}
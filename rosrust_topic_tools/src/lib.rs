@@ -0,0 +1,27 @@
+/*!
+This crate provides topic manipulation tools for `rosrust`.
+
+Functionality mirrors the ROS [topic_tools] package. Every tool forwards messages without knowing
+their type at compile time, by subscribing with `rosrust::RawMessage` and advertising the output
+with the description received from the input's publishers.
+
+Each tool is available both as a library structure, which keeps working until it is dropped, and
+as a binary with the same command line interface as its `topic_tools` counterpart.
+
+[topic_tools]: http://wiki.ros.org/topic_tools
+*/
+#![deny(missing_docs)]
+
+pub use drop::Dropper;
+pub use mux::{Mux, NONE_TOPIC};
+pub use relay::Relay;
+pub use throttle::Throttle;
+pub use transform::Transform;
+
+mod drop;
+pub mod msg;
+mod mux;
+mod output;
+mod relay;
+mod throttle;
+mod transform;
@@ -0,0 +1,4 @@
+//! Generated implementations of ROS messages needed for this library.
+
+#![allow(missing_docs)]
+rosrust::rosmsg_include!(topic_tools / MuxSelect);
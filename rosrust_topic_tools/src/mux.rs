@@ -0,0 +1,160 @@
+use crate::msg::topic_tools::{MuxSelect, MuxSelectRes};
+use crate::output::{Output, FAILED_TO_LOCK, QUEUE_SIZE};
+use rosrust::error::Result;
+use rosrust::{RawMessage, RawMessageDescription, Service, Subscriber};
+use std::sync::{Arc, Mutex};
+
+/// Name used for selecting none of the inputs.
+pub const NONE_TOPIC: &str = "__none";
+
+/// Forwards messages from one of several topics to a single output topic.
+///
+/// The selected input can be switched programmatically or with the `~select` service, which
+/// uses the `topic_tools/MuxSelect` service type. Selecting `__none` stops forwarding.
+///
+/// Multiplexing stops when the structure gets dropped.
+pub struct Mux {
+    selected: Arc<Mutex<Option<String>>>,
+    inputs: Arc<Vec<String>>,
+    _subscribers: Vec<Subscriber>,
+    _service: Service,
+}
+
+impl Mux {
+    /// Starts forwarding from the first of `inputs` to `output`.
+    ///
+    /// Inputs are identified by their resolved names, so repeated inputs are only used once.
+    pub fn new(output: &str, inputs: &[&str]) -> Result<Self> {
+        let output = Arc::new(Output::new(output));
+        let resolved = resolve_inputs(inputs, rosrust::resolve_name)?;
+        let selected = Arc::new(Mutex::new(resolved.first().cloned()));
+        let subscribers = resolved
+            .iter()
+            .map(|input| subscribe_input(input, &output, &selected))
+            .collect::<Result<Vec<_>>>()?;
+        let inputs = Arc::new(resolved);
+
+        let service_selected = Arc::clone(&selected);
+        let service_inputs = Arc::clone(&inputs);
+        let service = rosrust::service::<MuxSelect, _>("~select", move |req| {
+            let topic = resolve_selection(&req.topic)?;
+            let prev_topic = select(&service_selected, &service_inputs, &topic)?;
+            Ok(MuxSelectRes {
+                prev_topic: prev_topic.unwrap_or_else(|| NONE_TOPIC.into()),
+            })
+        })?;
+
+        Ok(Self {
+            selected,
+            inputs,
+            _subscribers: subscribers,
+            _service: service,
+        })
+    }
+
+    /// Returns the resolved name of the currently selected input topic.
+    pub fn selected(&self) -> Option<String> {
+        self.selected.lock().expect(FAILED_TO_LOCK).clone()
+    }
+
+    /// Selects the input topic, returning the previously selected one.
+    ///
+    /// Fails if the topic is not one of the inputs.
+    pub fn select(&self, topic: &str) -> std::result::Result<Option<String>, String> {
+        select(&self.selected, &self.inputs, &resolve_selection(topic)?)
+    }
+}
+
+/// Resolves the names of inputs, leaving out repeated ones.
+fn resolve_inputs<F>(inputs: &[&str], resolve: F) -> Result<Vec<String>>
+where
+    F: Fn(&str) -> Result<String>,
+{
+    let mut resolved = Vec::<String>::new();
+    for &input in inputs {
+        let name = resolve(input)?;
+        if !resolved.contains(&name) {
+            resolved.push(name);
+        }
+    }
+    Ok(resolved)
+}
+
+fn resolve_selection(topic: &str) -> std::result::Result<String, String> {
+    if topic == NONE_TOPIC {
+        return Ok(topic.into());
+    }
+    rosrust::resolve_name(topic).map_err(|err| format!("Invalid topic '{}': {}", topic, err))
+}
+
+fn select(
+    selected: &Mutex<Option<String>>,
+    inputs: &[String],
+    topic: &str,
+) -> std::result::Result<Option<String>, String> {
+    let next = if topic == NONE_TOPIC {
+        None
+    } else if inputs.iter().any(|input| input == topic) {
+        Some(String::from(topic))
+    } else {
+        return Err(format!("Topic '{}' is not an input of the mux", topic));
+    };
+    let mut selected = selected.lock().expect(FAILED_TO_LOCK);
+    Ok(std::mem::replace(&mut *selected, next))
+}
+
+fn subscribe_input(
+    input: &str,
+    output: &Arc<Output>,
+    selected: &Arc<Mutex<Option<String>>>,
+) -> Result<Subscriber> {
+    let description = Arc::new(Mutex::new(None::<RawMessageDescription>));
+    let connection_description = Arc::clone(&description);
+    let output = Arc::clone(output);
+    let selected = Arc::clone(selected);
+    let topic = String::from(input);
+    rosrust::subscribe_with_ids_and_headers(
+        input,
+        QUEUE_SIZE,
+        move |message: RawMessage, _: &str| {
+            if selected.lock().expect(FAILED_TO_LOCK).as_ref() != Some(&topic) {
+                return;
+            }
+            if let Some(ref description) = *description.lock().expect(FAILED_TO_LOCK) {
+                if let Err(err) = output.advertise(description) {
+                    rosrust::ros_err!("Failed to advertise mux output: {}", err);
+                    return;
+                }
+            }
+            output.send(message);
+        },
+        move |headers| {
+            if let Some(value) = RawMessageDescription::from_headers(&headers) {
+                *connection_description.lock().expect(FAILED_TO_LOCK) = Some(value);
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selection_switches_between_inputs() {
+        let selected = Mutex::new(Some(String::from("a")));
+        let inputs = vec![String::from("a"), String::from("b")];
+        assert_eq!(Ok(Some("a".into())), select(&selected, &inputs, "b"));
+        assert_eq!(Ok(Some("b".into())), select(&selected, &inputs, NONE_TOPIC));
+        assert_eq!(Ok(None), select(&selected, &inputs, "a"));
+        assert!(select(&selected, &inputs, "c").is_err());
+        assert_eq!(Some(String::from("a")), *selected.lock().unwrap());
+    }
+
+    #[test]
+    fn inputs_are_resolved_and_deduplicated() {
+        let resolve = |name: &str| Ok(format!("/{}", name.trim_start_matches('/')));
+        let inputs = resolve_inputs(&["a", "/b", "/a", "b"], resolve).unwrap();
+        assert_eq!(vec![String::from("/a"), String::from("/b")], inputs);
+    }
+}
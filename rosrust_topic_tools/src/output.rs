@@ -0,0 +1,88 @@
+use rosrust::error::Result;
+use rosrust::{Publisher, RawMessage, RawMessageDescription, Subscriber};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+pub(crate) const QUEUE_SIZE: usize = 100;
+
+/// Output topic that gets advertised once the type of the forwarded data is known.
+pub(crate) struct Output {
+    topic: String,
+    publisher: Mutex<Option<Publisher<RawMessage>>>,
+}
+
+impl Output {
+    pub fn new(topic: &str) -> Self {
+        Self {
+            topic: topic.into(),
+            publisher: Mutex::new(None),
+        }
+    }
+
+    pub fn advertise(&self, description: &RawMessageDescription) -> Result<()> {
+        let mut publisher = self.publisher.lock().expect(FAILED_TO_LOCK);
+        if publisher.is_none() {
+            *publisher = Some(rosrust::publish_with_description(
+                &self.topic,
+                QUEUE_SIZE,
+                description.clone(),
+            )?);
+        }
+        Ok(())
+    }
+
+    pub fn is_advertised(&self) -> bool {
+        self.publisher.lock().expect(FAILED_TO_LOCK).is_some()
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.publisher
+            .lock()
+            .expect(FAILED_TO_LOCK)
+            .as_ref()
+            .map(Publisher::subscriber_count)
+            .unwrap_or(0)
+    }
+
+    pub fn send(&self, message: RawMessage) {
+        if let Some(ref publisher) = *self.publisher.lock().expect(FAILED_TO_LOCK) {
+            if let Err(err) = publisher.send(message) {
+                rosrust::ros_err!("Failed to forward message to '{}': {}", self.topic, err);
+            }
+        }
+    }
+
+    pub fn advertise_from_headers(&self, headers: &HashMap<String, String>) {
+        let description = match RawMessageDescription::from_headers(headers) {
+            Some(description) => description,
+            None => {
+                rosrust::ros_err!("Publisher did not report the type of topic data");
+                return;
+            }
+        };
+        if let Err(err) = self.advertise(&description) {
+            rosrust::ros_err!("Failed to advertise topic '{}': {}", self.topic, err);
+        }
+    }
+}
+
+/// Subscribes to the input topic and forwards every message that passes the filter.
+pub(crate) fn forward<F>(input: &str, output: &Arc<Output>, filter: F) -> Result<Subscriber>
+where
+    F: Fn(&RawMessage) -> bool + Send + 'static,
+{
+    let message_output = Arc::clone(output);
+    let connection_output = Arc::clone(output);
+    rosrust::subscribe_with_ids_and_headers(
+        input,
+        QUEUE_SIZE,
+        move |message: RawMessage, _: &str| {
+            if filter(&message) {
+                message_output.send(message);
+            }
+        },
+        move |headers| connection_output.advertise_from_headers(&headers),
+    )
+}
+
+pub(crate) static FAILED_TO_LOCK: &str = "Failed to acquire lock";
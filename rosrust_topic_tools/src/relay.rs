@@ -0,0 +1,69 @@
+use crate::output::{forward, Output, FAILED_TO_LOCK};
+use rosrust::error::Result;
+use rosrust::Subscriber;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Forwards all messages from one topic to another.
+///
+/// In lazy mode, the input topic is only subscribed to while the output topic has subscribers.
+/// The input is still subscribed to at the start, until the output gets advertised, since the
+/// message type is only known once a publisher of the input topic gets connected.
+///
+/// Relaying stops when the structure gets dropped.
+pub struct Relay {
+    subscriber: Arc<Mutex<Option<Subscriber>>>,
+    running: Arc<AtomicBool>,
+}
+
+impl Relay {
+    /// Starts relaying messages from `input` to `output`.
+    pub fn new(input: &str, output: &str) -> Result<Self> {
+        let output = Arc::new(Output::new(output));
+        let subscriber = forward(input, &output, |_| true)?;
+        Ok(Self {
+            subscriber: Arc::new(Mutex::new(Some(subscriber))),
+            running: Arc::new(AtomicBool::new(true)),
+        })
+    }
+
+    /// Starts relaying messages from `input` to `output` in lazy mode.
+    pub fn new_lazy(input: &str, output: &str) -> Result<Self> {
+        let output = Arc::new(Output::new(output));
+        let relay = Self {
+            subscriber: Arc::new(Mutex::new(Some(forward(input, &output, |_| true)?))),
+            running: Arc::new(AtomicBool::new(true)),
+        };
+
+        let input = String::from(input);
+        let subscriber = Arc::clone(&relay.subscriber);
+        let running = Arc::clone(&relay.running);
+        thread::spawn(move || {
+            let mut rate = rosrust::rate(10.0);
+            while running.load(Ordering::Relaxed) && rosrust::is_ok() {
+                let mut subscriber = subscriber.lock().expect(FAILED_TO_LOCK);
+                let demanded = output.subscriber_count() > 0;
+                if subscriber.is_some() && !demanded && output.is_advertised() {
+                    *subscriber = None;
+                } else if subscriber.is_none() && demanded {
+                    match forward(&input, &output, |_| true) {
+                        Ok(value) => *subscriber = Some(value),
+                        Err(err) => {
+                            rosrust::ros_err!("Failed to subscribe to '{}': {}", input, err);
+                        }
+                    }
+                }
+                drop(subscriber);
+                rate.sleep();
+            }
+        });
+        Ok(relay)
+    }
+}
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::output::{forward, Output, FAILED_TO_LOCK};
+use rosrust::error::Result;
+use rosrust::{Subscriber, Time};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Forwards messages from one topic to another, while limiting the rate of data.
+///
+/// Time is measured with the node's clock, so simulated time is respected.
+///
+/// Throttling stops when the structure gets dropped.
+pub struct Throttle {
+    _subscriber: Subscriber,
+}
+
+impl Throttle {
+    /// Starts forwarding at most `rate` messages per second from `input` to `output`.
+    ///
+    /// Fails if the rate is not a positive number.
+    pub fn messages(input: &str, output: &str, rate: f64) -> Result<Self> {
+        check_positive("rate", rate)?;
+        let limiter = Mutex::new(MessageLimiter::new(rate));
+        let output = Arc::new(Output::new(output));
+        let subscriber = forward(input, &output, move |_| {
+            limiter.lock().expect(FAILED_TO_LOCK).admit(&rosrust::now())
+        })?;
+        Ok(Self {
+            _subscriber: subscriber,
+        })
+    }
+
+    /// Starts forwarding at most `bytes_per_second` bytes per second from `input` to `output`.
+    ///
+    /// The bandwidth is averaged over the last `window` seconds. Fails if either of them
+    /// is not a positive number.
+    pub fn bytes(input: &str, output: &str, bytes_per_second: f64, window: f64) -> Result<Self> {
+        check_positive("bandwidth", bytes_per_second)?;
+        check_positive("window", window)?;
+        let limiter = Mutex::new(ByteLimiter::new(bytes_per_second, window));
+        let output = Arc::new(Output::new(output));
+        let subscriber = forward(input, &output, move |message| {
+            limiter
+                .lock()
+                .expect(FAILED_TO_LOCK)
+                .admit(&rosrust::now(), message.0.len())
+        })?;
+        Ok(Self {
+            _subscriber: subscriber,
+        })
+    }
+}
+
+fn check_positive(name: &str, value: f64) -> Result<()> {
+    if value.is_finite() && value > 0.0 {
+        return Ok(());
+    }
+    Err(format!("Throttle {} must be a positive number, got {}", name, value).into())
+}
+
+fn seconds_to_nanos(seconds: f64) -> i64 {
+    (seconds * 1e9) as i64
+}
+
+struct MessageLimiter {
+    period: i64,
+    last_time: Option<i64>,
+}
+
+impl MessageLimiter {
+    fn new(rate: f64) -> Self {
+        Self {
+            period: seconds_to_nanos(1.0 / rate),
+            last_time: None,
+        }
+    }
+
+    fn admit(&mut self, now: &Time) -> bool {
+        let now = now.nanos();
+        if let Some(last_time) = self.last_time {
+            // Time going backwards, usually due to a simulation restart, resets the limiter
+            if last_time <= now && now - last_time < self.period {
+                return false;
+            }
+        }
+        self.last_time = Some(now);
+        true
+    }
+}
+
+struct ByteLimiter {
+    bytes_per_window: f64,
+    window: i64,
+    sent: VecDeque<(i64, usize)>,
+}
+
+impl ByteLimiter {
+    fn new(bytes_per_second: f64, window: f64) -> Self {
+        Self {
+            bytes_per_window: bytes_per_second * window,
+            window: seconds_to_nanos(window),
+            sent: VecDeque::new(),
+        }
+    }
+
+    fn admit(&mut self, now: &Time, size: usize) -> bool {
+        let now = now.nanos();
+        let window = self.window;
+        self.sent
+            .retain(|&(time, _)| time <= now && now - time < window);
+        let total = self.sent.iter().map(|&(_, size)| size).sum::<usize>() + size;
+        if total as f64 > self.bytes_per_window {
+            return false;
+        }
+        self.sent.push_back((now, size));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: f64) -> Time {
+        Time::from_nanos((seconds * 1e9) as i64)
+    }
+
+    #[test]
+    fn message_limiter_respects_rate() {
+        let mut limiter = MessageLimiter::new(2.0);
+        assert!(limiter.admit(&at(10.0)));
+        assert!(!limiter.admit(&at(10.2)));
+        assert!(!limiter.admit(&at(10.4)));
+        assert!(limiter.admit(&at(10.5)));
+        assert!(!limiter.admit(&at(10.9)));
+        assert!(limiter.admit(&at(11.2)));
+    }
+
+    #[test]
+    fn message_limiter_resets_on_time_jump() {
+        let mut limiter = MessageLimiter::new(1.0);
+        assert!(limiter.admit(&at(10.0)));
+        assert!(limiter.admit(&at(2.0)));
+        assert!(!limiter.admit(&at(2.5)));
+    }
+
+    #[test]
+    fn rejects_invalid_limits() {
+        for &rate in &[0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(Throttle::messages("/in", "/out", rate).is_err());
+            assert!(Throttle::bytes("/in", "/out", rate, 1.0).is_err());
+            assert!(Throttle::bytes("/in", "/out", 100.0, rate).is_err());
+        }
+    }
+
+    #[test]
+    fn byte_limiter_respects_bandwidth() {
+        let mut limiter = ByteLimiter::new(100.0, 2.0);
+        assert!(limiter.admit(&at(10.0), 150));
+        assert!(!limiter.admit(&at(10.5), 100));
+        assert!(limiter.admit(&at(11.0), 50));
+        assert!(!limiter.admit(&at(11.5), 1));
+        assert!(limiter.admit(&at(12.0), 150));
+        assert!(limiter.admit(&at(13.5), 50));
+    }
+}
@@ -0,0 +1,32 @@
+use rosrust::error::Result;
+use rosrust::{Message, Subscriber};
+
+/// Forwards messages from one topic to another, converting them with a user provided function.
+///
+/// Unlike the other tools, both message types need to be known at compile time.
+///
+/// Transforming stops when the structure gets dropped.
+pub struct Transform {
+    _subscriber: Subscriber,
+}
+
+impl Transform {
+    /// Starts publishing the result of `transform` on `output` for every message on `input`.
+    pub fn new<I, O, F>(input: &str, output: &str, queue_size: usize, transform: F) -> Result<Self>
+    where
+        I: Message,
+        O: Message,
+        F: Fn(I) -> O + Send + 'static,
+    {
+        let publisher = rosrust::publish::<O>(output, queue_size)?;
+        let output = String::from(output);
+        let subscriber = rosrust::subscribe(input, queue_size, move |message: I| {
+            if let Err(err) = publisher.send(transform(message)) {
+                rosrust::ros_err!("Failed to publish to '{}': {}", output, err);
+            }
+        })?;
+        Ok(Self {
+            _subscriber: subscriber,
+        })
+    }
+}